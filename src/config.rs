@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::sync::OnceLock;
 
@@ -15,6 +17,22 @@ pub struct AppConfig {
     pub database: DatabaseConfig,
     pub logging: LoggingConfig,
     pub cache: CacheConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub transform: TransformConfig,
+    #[serde(default)]
+    pub watermark: WatermarkConfig,
+    #[serde(default)]
+    pub encoder: EncoderConfig,
+    #[serde(default)]
+    pub presets: PresetsConfig,
+    #[serde(default)]
+    pub placeholder: PlaceholderConfig,
+    #[serde(default)]
+    pub colors: ColorsConfig,
+    #[serde(default)]
+    pub compat: CompatConfig,
 }
 
 /// 服务器配置
@@ -37,6 +55,13 @@ pub struct StorageConfig {
     pub upload_dir: String,
     /// 最大文件大小
     pub max_file_size: ByteSize,
+    /// 过期图片清理间隔
+    #[serde(default = "default_expired_cleanup_interval")]
+    pub expired_cleanup_interval: Duration,
+}
+
+fn default_expired_cleanup_interval() -> Duration {
+    Duration::minutes(10)
 }
 
 /// 数据库配置
@@ -96,6 +121,195 @@ pub struct CacheConfig {
     pub space_threshold_percent: f64,
 }
 
+/// 访问控制配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// API密钥列表，持有者可访问私有图片并签发访问令牌
+    pub api_keys: Vec<String>,
+    /// 访问令牌签名密钥（留空时启动随机生成，重启后已签发令牌失效）
+    pub token_secret: String,
+    /// 密码解锁后签发令牌的有效期
+    pub unlock_token_ttl: Duration,
+    /// 通过API密钥签发分享令牌的默认有效期
+    pub share_token_ttl: Duration,
+    /// 同一图片（不区分来源）累计解锁失败达到此次数后对所有来源退避，0表示关闭。
+    /// 开启后任何人都能通过故意输错密码让其他用户暂时无法解锁该图片
+    pub unlock_image_lockout_attempts: u32,
+    /// 受信任的反向代理地址，来自这些地址的请求按 X-Forwarded-For 识别客户端IP
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            api_keys: Vec::new(),
+            token_secret: String::new(),
+            unlock_token_ttl: Duration::hours(1),
+            share_token_ttl: Duration::days(1),
+            unlock_image_lockout_attempts: 0,
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+/// 图片转换配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TransformConfig {
+    /// 解码时按EXIF方向信息自动旋转
+    pub auto_orient: bool,
+    /// 动图转换允许的最大帧数
+    pub max_animation_frames: u32,
+    /// 动图转换允许的最大总像素数（画布宽 x 高 x 帧数），超出的请求被拒绝
+    pub max_animation_pixels: u64,
+    /// 设备像素比上限（dpr参数和客户端提示均受此限制）
+    pub max_dpr: f32,
+    /// 根据客户端提示（Sec-CH-DPR、Sec-CH-Width、Sec-CH-Viewport-Width）调整带转换参数请求的尺寸
+    pub client_hints: bool,
+    /// 未指定格式的转换请求按Accept请求头自动选择格式（等同于auto参数）
+    pub auto_format: bool,
+    /// 生成srcset时默认使用的宽度列表
+    pub srcset_widths: Vec<u32>,
+}
+
+impl Default for TransformConfig {
+    fn default() -> Self {
+        Self {
+            auto_orient: true,
+            max_animation_frames: 300,
+            max_animation_pixels: 100_000_000,
+            max_dpr: 3.0,
+            client_hints: false,
+            auto_format: false,
+            srcset_widths: vec![320, 640, 960, 1280, 1920],
+        }
+    }
+}
+
+/// 编码器配置（URL参数未指定时使用的默认值）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EncoderConfig {
+    /// AVIF默认质量（1-100）
+    pub avif_quality: u8,
+    /// AVIF默认编码速度（1-10，越小压缩率越高、越慢）
+    pub avif_speed: u8,
+    /// AVIF透明通道质量（1-100，不设置时与图片质量相同）
+    pub avif_alpha_quality: Option<u8>,
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        Self {
+            avif_quality: 70,
+            avif_speed: 6,
+            avif_alpha_quality: None,
+        }
+    }
+}
+
+/// 转换预设配置
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PresetsConfig {
+    /// 严格模式：只允许单独使用预设，拒绝任意转换参数以限制变体数量
+    pub strict: bool,
+    /// 预设名称到转换参数字符串的映射
+    #[serde(flatten)]
+    pub definitions: BTreeMap<String, String>,
+}
+
+/// 占位图配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PlaceholderConfig {
+    /// 上传时计算BlurHash和ThumbHash
+    pub enabled: bool,
+    /// BlurHash水平分量数（1-9）
+    pub blurhash_components_x: u32,
+    /// BlurHash垂直分量数（1-9）
+    pub blurhash_components_y: u32,
+    /// 启动时为缺少占位图哈希的已有图片补算
+    pub backfill_on_startup: bool,
+}
+
+impl Default for PlaceholderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            blurhash_components_x: 4,
+            blurhash_components_y: 3,
+            backfill_on_startup: true,
+        }
+    }
+}
+
+/// 颜色提取配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ColorsConfig {
+    /// 上传时提取主色调和调色板
+    pub enabled: bool,
+    /// 调色板颜色数（1-16）
+    pub palette_size: usize,
+    /// 启动时为缺少颜色信息的已有图片补算
+    pub backfill_on_startup: bool,
+}
+
+impl Default for ColorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            palette_size: 5,
+            backfill_on_startup: true,
+        }
+    }
+}
+
+/// imgproxy/thumbor兼容路由配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CompatConfig {
+    /// imgproxy风格URL的路由前缀（留空不启用，"/" 挂载到根路径）
+    pub imgproxy_prefix: String,
+    /// thumbor风格URL的路由前缀（留空不启用，"/" 挂载到根路径）
+    pub thumbor_prefix: String,
+}
+
+impl Default for CompatConfig {
+    fn default() -> Self {
+        Self {
+            imgproxy_prefix: "/imgproxy".to_string(),
+            thumbor_prefix: "/thumbor".to_string(),
+        }
+    }
+}
+
+/// 水印配置
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WatermarkConfig {
+    /// 文字水印字体文件路径（留空使用内置的DejaVu Sans，不包含中文字形）
+    pub font_path: String,
+    /// 强制水印规则，命中的请求无法通过URL去除或替换水印
+    pub forced: Vec<ForcedWatermarkRule>,
+}
+
+/// 强制水印规则
+///
+/// 请求路径匹配任一前缀，或携带任一API密钥时生效；两者都为空时对所有图片请求生效
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ForcedWatermarkRule {
+    /// 请求路径前缀
+    pub path_prefixes: Vec<String>,
+    /// API密钥
+    pub api_keys: Vec<String>,
+    /// 水印参数，与URL中的wm参数写法相同，如 "wmilogo_wmpse_wmo60"
+    pub params: String,
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
@@ -108,6 +322,7 @@ impl Default for AppConfig {
             storage: StorageConfig {
                 upload_dir: "uploads".to_string(),
                 max_file_size: ByteSize::mb(10), // 10MB
+                expired_cleanup_interval: default_expired_cleanup_interval(),
             },
             database: DatabaseConfig {
                 database_type: "sqlite".to_string(),
@@ -136,6 +351,14 @@ impl Default for AppConfig {
                 min_heat_score: 0.1,
                 space_threshold_percent: 0.8, // 80%使用率时才触发热度清理
             },
+            auth: AuthConfig::default(),
+            transform: TransformConfig::default(),
+            watermark: WatermarkConfig::default(),
+            encoder: EncoderConfig::default(),
+            presets: PresetsConfig::default(),
+            placeholder: PlaceholderConfig::default(),
+            colors: ColorsConfig::default(),
+            compat: CompatConfig::default(),
         }
     }
 }
@@ -189,6 +412,8 @@ request_timeout = "1m"
 upload_dir = "uploads"
 # 最大文件大小
 max_file_size = "10MB"
+# 过期图片清理间隔（上传时可通过 expires_in/expires_at 设置过期时间）
+expired_cleanup_interval = "10m"
 
 # ========================================
 # 日志配置  
@@ -230,6 +455,124 @@ min_heat_score = 0.1
 # 空间使用阈值百分比（0.0-1.0），超过此阈值才触发基于热度的清理
 space_threshold_percent = 0.8
 
+# ========================================
+# 图片转换配置
+# ========================================
+
+[transform]
+# 解码时按EXIF方向信息自动旋转（手机照片通常依赖此设置）
+auto_orient = true
+# 动图逐帧转换的最大帧数
+max_animation_frames = 300
+# 动图逐帧转换的最大总像素数（画布宽 x 高 x 帧数），用于限制CPU和内存占用
+max_animation_pixels = 100000000
+# 设备像素比上限（dpr参数和客户端提示均受此限制）
+max_dpr = 3.0
+# 根据客户端提示调整带转换参数请求的尺寸，并返回 Accept-CH 和 Vary 响应头
+client_hints = false
+# 未指定格式的转换请求按 Accept 请求头自动选择 AVIF/WebP/JPEG/PNG（等同于 auto 参数）
+auto_format = false
+# 生成srcset时默认使用的宽度列表（/images/{标识符}/srcset 未指定 widths 时）
+srcset_widths = [320, 640, 960, 1280, 1920]
+
+# ========================================
+# 水印配置
+# ========================================
+
+[watermark]
+# 文字水印字体文件路径（留空使用内置的DejaVu Sans，中文水印请指定CJK字体）
+font_path = ""
+
+# 强制水印规则（可配置多条），命中的请求无法通过URL去除或替换水印
+# 请求路径匹配任一前缀，或携带任一API密钥时生效；两者都为空时对所有图片请求生效
+# [[watermark.forced]]
+# path_prefixes = ["/images/"]
+# api_keys = ["partner-key"]
+# params = "wmilogo_wmpse_wmo60_wms20"
+
+# ========================================
+# 编码器配置
+# ========================================
+
+[encoder]
+# AVIF默认质量（1-100），可通过URL参数 q 覆盖
+avif_quality = 70
+# AVIF默认编码速度（1-10，越小压缩率越高、越慢），可通过URL参数 speed 覆盖
+avif_speed = 6
+# AVIF透明通道质量（1-100），不设置时与图片质量相同
+# avif_alpha_quality = 90
+
+# ========================================
+# 转换预设配置
+# ========================================
+
+[presets]
+# 严格模式：只允许 @预设名 形式的转换请求，拒绝任意参数组合
+strict = false
+# 预设名称 = 转换参数，通过 @thumb 访问，也可追加参数覆盖（如 @thumb_q60）
+# 预设名称不能包含 _、@ 和 ~，也不能与内置转换参数同名（如 png、gray、w100）
+# thumb = "w200_h200_fill_webp_q75"
+# card = "w600_h400_fill_jpeg_q80"
+# og = "w1200_h630_fill_jpeg_naw_q80"
+
+# ========================================
+# 占位图配置
+# ========================================
+
+[placeholder]
+# 上传时计算 BlurHash 和 ThumbHash，在上传响应和图片信息中返回
+enabled = true
+# BlurHash 水平/垂直分量数（1-9），越大细节越多、哈希越长
+blurhash_components_x = 4
+blurhash_components_y = 3
+# 启动时在后台为缺少占位图哈希的已有图片补算
+backfill_on_startup = true
+
+# ========================================
+# 颜色提取配置
+# ========================================
+
+[colors]
+# 上传时提取主色调和调色板，在图片信息中返回，并支持按颜色查询图片列表
+enabled = true
+# 调色板颜色数（1-16）
+palette_size = 5
+# 启动时在后台为缺少颜色信息的已有图片补算
+backfill_on_startup = true
+
+# ========================================
+# 兼容路由配置
+# ========================================
+
+[compat]
+# imgproxy风格URL的路由前缀，如 /imgproxy/insecure/rs:fill:300:200/plain/<标识符>@webp
+# 留空不启用，设为 "/" 时挂载到根路径（不能与thumbor同时使用根路径）
+imgproxy_prefix = "/imgproxy"
+# thumbor风格URL的路由前缀，如 /thumbor/unsafe/300x200/smart/<标识符>
+thumbor_prefix = "/thumbor"
+
+# ========================================
+# 访问控制配置
+# ========================================
+
+[auth]
+# API密钥列表（通过 X-API-Key 或 Authorization: Bearer 传递）
+# 持有者可访问私有图片、在列表中看到非公开图片并签发分享令牌
+api_keys = []
+# 访问令牌签名密钥（留空时启动随机生成，重启后已签发令牌失效）
+token_secret = ""
+# 密码解锁后签发令牌的有效期
+unlock_token_ttl = "1h"
+# 分享令牌默认有效期
+share_token_ttl = "1d"
+# 同一来源对同一图片连续5次解锁失败后按指数退避（最长15分钟）
+# 同一图片累计失败达到此次数后对所有来源退避，0表示关闭
+# 开启可限制分布式暴力破解，但任何人都能借此让其他用户暂时无法解锁
+unlock_image_lockout_attempts = 0
+# 受信任的反向代理地址（如 ["127.0.0.1"]），来自这些地址的请求按 X-Forwarded-For 识别客户端IP
+# 部署在反向代理之后时必须配置，否则所有用户共用代理地址的解锁失败计数
+trusted_proxies = []
+
 # ========================================
 # 数据库配置
 # ========================================
@@ -334,6 +677,14 @@ max_lifetime = "30m"
                         eprintln!("   - [storage] 文件存储目录和大小限制");
                         eprintln!("   - [cache] 缓存策略和清理设置");
                         eprintln!("   - [logging] 日志级别和输出设置");
+                        eprintln!("   - [transform] 图片转换设置");
+                        eprintln!("   - [watermark] 水印字体和强制水印设置");
+                        eprintln!("   - [auth] API密钥和访问令牌设置");
+                        eprintln!("   - [encoder] 编码器默认参数");
+                        eprintln!("   - [presets] 命名转换预设");
+                        eprintln!("   - [placeholder] BlurHash/ThumbHash占位图");
+                        eprintln!("   - [colors] 主色调和调色板提取");
+                        eprintln!("   - [compat] imgproxy/thumbor兼容路由");
 
                         return Err(AppError::Internal(
                             "已创建默认配置文件，请修改后重新启动".to_string(),
@@ -351,6 +702,12 @@ max_lifetime = "30m"
     }
 
     /// 获取全局配置
+    /// 测试中使用默认配置初始化（已初始化时不做任何事）
+    #[cfg(test)]
+    pub fn init_for_tests() {
+        let _ = CONFIG.set(Self::default());
+    }
+
     pub fn get() -> &'static AppConfig {
         CONFIG
             .get()
@@ -39,6 +39,9 @@ pub struct Model {
     /// 缓存热度评分（基于访问频率和时间的综合评分）
     #[sea_orm(default_value = 0.0)]
     pub heat_score: f64,
+
+    /// 按目标体积编码时选定的质量
+    pub output_quality: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -59,6 +62,7 @@ impl From<Model> for crate::models::CacheInfo {
             last_accessed: model.last_accessed,
             access_count: model.access_count,
             heat_score: model.heat_score,
+            output_quality: model.output_quality.map(|quality| quality as u8),
         }
     }
 }
@@ -76,6 +80,7 @@ impl From<&crate::models::CacheInfo> for ActiveModel {
             last_accessed: Set(info.last_accessed),
             access_count: Set(info.access_count),
             heat_score: Set(info.heat_score),
+            output_quality: Set(info.output_quality.map(i32::from)),
         }
     }
 }
@@ -3,6 +3,8 @@ use sea_orm::entity::prelude::*;
 use sea_orm::Set;
 use serde::{Deserialize, Serialize};
 
+use crate::models::{ImageTransformParams, Visibility};
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "images")]
 pub struct Model {
@@ -28,6 +30,45 @@ pub struct Model {
     /// 访问次数
     #[sea_orm(default_value = 0)]
     pub access_count: i64,
+
+    /// 短ID（base62）
+    #[sea_orm(unique)]
+    pub short_id: Option<String>,
+
+    /// 用户自定义别名
+    #[sea_orm(unique)]
+    pub alias: Option<String>,
+
+    /// 过期时间（为空表示永久保存）
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// 最大查看次数（为空表示不限制）
+    pub max_views: Option<i64>,
+
+    /// 可见性（public/unlisted/private）
+    #[sea_orm(default_value = "public")]
+    pub visibility: String,
+
+    /// 访问密码哈希（为空表示无需密码）
+    pub password_hash: Option<String>,
+
+    /// BlurHash占位图哈希
+    pub blurhash: Option<String>,
+
+    /// ThumbHash占位图哈希（base64编码）
+    pub thumbhash: Option<String>,
+
+    /// 主色调红色分量（分通道存储，便于按颜色范围查询）
+    pub dominant_red: Option<i16>,
+
+    /// 主色调绿色分量
+    pub dominant_green: Option<i16>,
+
+    /// 主色调蓝色分量
+    pub dominant_blue: Option<i16>,
+
+    /// 调色板（逗号分隔的 #rrggbb）
+    pub palette: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -45,12 +86,44 @@ impl From<Model> for crate::models::ImageInfo {
             last_accessed: model.last_accessed,
             extension: model.extension,
             access_count: model.access_count,
+            short_id: model.short_id,
+            alias: model.alias,
+            expires_at: model.expires_at,
+            max_views: model.max_views,
+            // 无法识别的值按私有处理，避免意外公开
+            visibility: model.visibility.parse().unwrap_or(Visibility::Private),
+            password_hash: model.password_hash,
+            blurhash: model.blurhash,
+            thumbhash: model.thumbhash,
+            dominant_color: match (
+                model.dominant_red,
+                model.dominant_green,
+                model.dominant_blue,
+            ) {
+                (Some(r), Some(g), Some(b)) => Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+                _ => None,
+            },
+            palette: model
+                .palette
+                .map(|palette| {
+                    palette
+                        .split(',')
+                        .filter(|color| !color.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
         }
     }
 }
 
 impl From<&crate::models::ImageInfo> for ActiveModel {
     fn from(info: &crate::models::ImageInfo) -> Self {
+        let dominant = info
+            .dominant_color
+            .as_deref()
+            .and_then(|color| ImageTransformParams::parse_hex_color(color).ok());
+
         Self {
             hash: Set(info.hash.clone()),
             size: Set(info.size as i64),
@@ -59,6 +132,18 @@ impl From<&crate::models::ImageInfo> for ActiveModel {
             last_accessed: Set(info.last_accessed),
             extension: Set(info.extension.clone()),
             access_count: Set(info.access_count),
+            short_id: Set(info.short_id.clone()),
+            alias: Set(info.alias.clone()),
+            expires_at: Set(info.expires_at),
+            max_views: Set(info.max_views),
+            visibility: Set(info.visibility.as_str().to_string()),
+            password_hash: Set(info.password_hash.clone()),
+            blurhash: Set(info.blurhash.clone()),
+            thumbhash: Set(info.thumbhash.clone()),
+            dominant_red: Set(dominant.map(|(r, _, _)| r as i16)),
+            dominant_green: Set(dominant.map(|(_, g, _)| g as i16)),
+            dominant_blue: Set(dominant.map(|(_, _, b)| b as i16)),
+            palette: Set((!info.palette.is_empty()).then(|| info.palette.join(","))),
         }
     }
 }
@@ -0,0 +1,65 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, Uri},
+    response::{IntoResponse, Response},
+};
+use tracing::info;
+
+use crate::app_state::AppState;
+use crate::handlers::get_image;
+use crate::models::{AccessQuery, TransformQuery};
+use crate::services::CompatUrl;
+use crate::utils::AppError;
+
+/// imgproxy风格的图片接口：{前缀}/{签名}/{处理选项}/plain/{标识符}@{格式}
+pub async fn imgproxy_image(
+    State(app_state): State<AppState>,
+    Path((_signature, path)): Path<(String, String)>,
+    access: Query<AccessQuery>,
+    request_headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let (identifier, params) = CompatUrl::imgproxy(&path)?;
+    info!("imgproxy兼容请求: {} -> {}@{}", path, identifier, params);
+    serve_image(app_state, identifier, params, access, request_headers).await
+}
+
+/// thumbor风格的图片接口：{前缀}/{签名}/[fit-in/]{宽}x{高}/[smart/][filters:.../]{标识符}
+pub async fn thumbor_image(
+    State(app_state): State<AppState>,
+    Path((_signature, path)): Path<(String, String)>,
+    access: Query<AccessQuery>,
+    request_headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let (identifier, params) = CompatUrl::thumbor(&path)?;
+    info!("thumbor兼容请求: {} -> {}@{}", path, identifier, params);
+    serve_image(app_state, identifier, params, access, request_headers).await
+}
+
+/// 按等价的 /images/{标识符}@{参数} 请求处理，强制水印规则按 /images 路径匹配
+async fn serve_image(
+    app_state: AppState,
+    identifier: String,
+    params: String,
+    access: Query<AccessQuery>,
+    request_headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let uri: Uri = format!("/images/{}", identifier)
+        .parse()
+        .map_err(|_| AppError::BadRequest("无效的图片标识符".to_string()))?;
+    let identifier = if params.is_empty() {
+        identifier
+    } else {
+        format!("{}@{}", identifier, params)
+    };
+
+    get_image(
+        State(app_state),
+        Path(identifier),
+        access,
+        Query(TransformQuery::default()),
+        uri,
+        request_headers,
+    )
+    .await
+    .map(IntoResponse::into_response)
+}
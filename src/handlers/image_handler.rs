@@ -1,156 +1,332 @@
+use std::net::SocketAddr;
+
 use axum::{
-    extract::{Multipart, Path, Query, State},
-    http::{header, StatusCode},
+    extract::{ConnectInfo, Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode, Uri},
     response::IntoResponse,
     Json,
 };
 use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
 use tracing::{error, info, warn};
 
 use crate::app_state::AppState;
 use crate::config::AppConfig;
-use crate::models::{Base64ImageResponse, ImageQuery, ImageTransformParams, UploadResponse};
-use crate::services::{CacheService, ImageService, ImageTransformService};
-use crate::utils::AppError;
+use crate::models::{
+    AccessQuery, AccessTokenData, Base64ImageResponse, ClientHints, ImageInfo, ImageQuery,
+    ImageTransformParams, ShareTokenQuery, SrcsetQuery, TokenScope, TransformQuery, UnlockRequest,
+    UploadOptions, UploadResponse, Visibility,
+};
+use crate::services::{
+    AccessCredentials, AccessService, CacheService, ImageService, ImageTransformService,
+    PlaceholderService, SrcsetService, WatermarkService,
+};
+use crate::utils::{AppError, Duration};
 
 /// 图片上传接口
 pub async fn upload_image(
     State(app_state): State<AppState>,
+    request_headers: HeaderMap,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
     info!("收到图片上传请求");
 
+    let mut file_data = None;
+    let mut options = UploadOptions {
+        trusted: AccessCredentials::from_request(&request_headers, None).has_valid_api_key(),
+        ..Default::default()
+    };
+
     while let Some(field) = multipart.next_field().await.map_err(|e| {
         error!("解析multipart数据失败: {}", e);
         AppError::BadRequest("无效的multipart数据".to_string())
     })? {
         let name = field.name().unwrap_or("").to_string();
 
-        // 只处理名为 "file" 的字段
-        if name == "file" {
-            // 读取文件数据
-            let data = field.bytes().await.map_err(|e| {
-                error!("读取文件数据失败: {}", e);
-                AppError::BadRequest("读取文件数据失败".to_string())
-            })?;
-
-            if data.is_empty() {
-                error!("上传的文件为空");
-                return Err(AppError::InvalidFile);
+        match name.as_str() {
+            "file" => {
+                // 读取文件数据
+                let data = field.bytes().await.map_err(|e| {
+                    error!("读取文件数据失败: {}", e);
+                    AppError::BadRequest("读取文件数据失败".to_string())
+                })?;
+                file_data = Some(data);
             }
+            "alias" | "expires_in" | "expires_at" | "max_views" | "visibility" | "password" => {
+                let text = field.text().await.map_err(|e| {
+                    error!("读取{}字段失败: {}", name, e);
+                    AppError::BadRequest(format!("读取{}字段失败", name))
+                })?;
+                let text = text.trim();
+                if text.is_empty() {
+                    continue;
+                }
 
-            info!("开始保存图片: {}字节", data.len());
+                match name.as_str() {
+                    // 可选的自定义别名
+                    "alias" => options.alias = Some(text.to_string()),
+                    // 可见性：public/unlisted/private
+                    "visibility" => {
+                        options.visibility =
+                            Some(text.parse::<Visibility>().map_err(AppError::BadRequest)?);
+                    }
+                    // 访问密码，设置后需先解锁才能查看
+                    "password" => {
+                        AccessService::validate_password(text)?;
+                        options.password = Some(text.to_string());
+                    }
+                    // 最大查看次数，1表示阅后即焚
+                    "max_views" => {
+                        let max_views =
+                            text.parse::<i64>().ok().filter(|v| *v > 0).ok_or_else(|| {
+                                AppError::BadRequest("max_views必须为正整数".to_string())
+                            })?;
+                        options.max_views = Some(max_views);
+                    }
+                    // 相对过期时间，如 "30m"、"1d"
+                    "expires_in" => {
+                        let duration = text.parse::<Duration>().map_err(|e| {
+                            AppError::BadRequest(format!("无效的expires_in: {}", e))
+                        })?;
+                        if duration.as_seconds() == 0 {
+                            return Err(AppError::BadRequest("expires_in必须大于0".to_string()));
+                        }
+                        options.expires_at = Some(
+                            Utc::now() + chrono::Duration::seconds(duration.as_seconds() as i64),
+                        );
+                    }
+                    // 绝对过期时间（RFC 3339）
+                    _ => {
+                        let expires_at = DateTime::parse_from_rfc3339(text)
+                            .map_err(|e| AppError::BadRequest(format!("无效的expires_at: {}", e)))?
+                            .with_timezone(&Utc);
+                        if expires_at <= Utc::now() {
+                            return Err(AppError::BadRequest(
+                                "expires_at必须晚于当前时间".to_string(),
+                            ));
+                        }
+                        options.expires_at = Some(expires_at);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
 
-            // 保存图片（后端会自动检测真实文件类型）
-            let image_info = ImageService::save_image(app_state.db_pool(), &data).await?;
+    let Some(data) = file_data else {
+        error!("未找到有效的文件字段");
+        return Err(AppError::BadRequest("请选择要上传的图片文件".to_string()));
+    };
 
-            info!("图片保存成功: {}", image_info.stored_name());
+    if data.is_empty() {
+        error!("上传的文件为空");
+        return Err(AppError::InvalidFile);
+    }
 
-            let response = UploadResponse {
-                success: true,
-                message: "图片上传成功".to_string(),
-                data: Some(image_info),
-            };
+    info!("开始保存图片: {}字节", data.len());
 
-            return Ok((StatusCode::OK, Json(response)));
-        }
-    }
+    // 保存图片（后端会自动检测真实文件类型）
+    let image_info = ImageService::save_image(app_state.db_pool(), &data, &options).await?;
+
+    info!("图片保存成功: {}", image_info.stored_name());
 
-    error!("未找到有效的文件字段");
-    Err(AppError::BadRequest("请选择要上传的图片文件".to_string()))
+    let response = UploadResponse {
+        success: true,
+        message: "图片上传成功".to_string(),
+        data: Some(image_info),
+    };
+
+    Ok((StatusCode::OK, Json(response)))
 }
 
 /// 获取图片接口（通过哈希值，支持格式转换）
+///
+/// 转换参数可写在路径中（hash@w100_h100），也可使用查询字符串（?w=100&h=100）
 pub async fn get_image(
     State(app_state): State<AppState>,
     Path(identifier): Path<String>,
+    Query(access): Query<AccessQuery>,
+    Query(transform_query): Query<TransformQuery>,
+    uri: Uri,
+    request_headers: HeaderMap,
 ) -> Result<impl IntoResponse, AppError> {
-    // 解析标识符，检查是否包含转换参数
-    let (hash, transform_params) = if let Some(at_pos) = identifier.find('@') {
-        let hash = &identifier[..at_pos];
-        let params_str = &identifier[at_pos + 1..];
+    let config = AppConfig::get();
+    // 严格预设模式下不根据客户端提示生成新的尺寸变体
+    let client_hints_enabled = config.transform.client_hints && !config.presets.strict;
 
-        info!("解析转换参数: {}", params_str);
+    // 解析标识符，检查路径和查询字符串中是否包含转换参数
+    let (hash, path_params) = match identifier.split_once('@') {
+        Some((hash, params)) => (hash, Some(params)),
+        None => (identifier.as_str(), None),
+    };
+    let query_params = transform_query
+        .to_params_string()
+        .map_err(AppError::BadRequest)?;
+    let params_str = match (path_params, query_params) {
+        (Some(path), Some(query)) => {
+            Some(ImageTransformParams::merge_into_first_stage(path, &query))
+        }
+        (path, query) => path.map(str::to_string).or(query),
+    };
 
-        let params = ImageTransformParams::parse(params_str)
-            .map_err(|e| AppError::BadRequest(format!("转换参数解析失败: {}", e)))?;
+    let mut transform_params = if let Some(params_str) = params_str {
+        let hints = if client_hints_enabled {
+            client_hints_from_headers(&request_headers)
+        } else {
+            ClientHints::default()
+        };
 
-        // 检查是否真的需要转换
-        if !params.needs_transform() {
+        let params = ImageTransformService::resolve_params(&params_str, &hints)?;
+        if params.is_none() {
             info!("转换参数为空，返回原图: {}", hash);
-            (hash, None)
-        } else {
-            // 验证参数合理性
-            ImageTransformService::validate_params(&params)?;
-            (hash, Some(params))
         }
+        params
     } else {
-        (identifier.as_str(), None)
+        None
     };
 
-    // 读取原始图片文件
-    let image_data = ImageService::read_image_file(app_state.db_pool(), hash).await?;
-
-    // 获取图片信息
+    // 获取图片信息（标识符可以是hash、短ID、别名或hash前缀）
     let image_info = ImageService::get_image_info(app_state.db_pool(), hash)
         .await?
         .ok_or(AppError::FileNotFound)?;
+    let hash = image_info.hash.as_str();
+
+    // 已过期或已达到查看上限的图片等待定时任务清理，期间返回410
+    ImageService::ensure_available(&image_info)?;
+
+    // 私有或受密码保护的图片需要API密钥或访问令牌
+    let credentials = AccessCredentials::from_request(&request_headers, access.token);
+    AccessService::authorize(&image_info, &credentials)?;
+
+    // 占位图由存储的哈希解码生成，不读取原图，也不计入查看次数
+    let placeholder_requested = transform_params
+        .as_ref()
+        .is_some_and(|params| params.placeholder.is_some());
+
+    // 命中强制水印规则时覆盖URL中的水印参数，原图请求也会加上水印（占位图除外）
+    let forced_watermark = WatermarkService::forced_watermark(uri.path(), &credentials)
+        .filter(|_| !placeholder_requested);
+    let watermark_forced = forced_watermark.is_some();
+    if let Some(watermark) = forced_watermark {
+        info!("应用强制水印: {}", watermark.to_normalized_string());
+        transform_params
+            .get_or_insert_with(Default::default)
+            .force_watermark(watermark);
+    }
 
-    let config = AppConfig::get();
+    // 图片水印的标识符解析为哈希后再参与缓存键
+    let watermark_image = match transform_params.as_mut() {
+        Some(params) if !placeholder_requested => {
+            resolve_watermark_image(&app_state, params, watermark_forced).await?
+        }
+        _ => None,
+    };
+
+    // 读取原始图片文件，查看次数在响应内容生成成功后再计入
+    let image_data = if placeholder_requested {
+        Vec::new()
+    } else {
+        ImageService::read_image_file_untracked(&image_info).await?
+    };
+
+    // 自动格式按Accept请求头协商为具体格式，协商结果参与缓存键
+    let format_negotiated = transform_params
+        .as_ref()
+        .is_some_and(|params| params.is_auto_format());
+    if let Some(params) = transform_params.as_mut().filter(|_| format_negotiated) {
+        let accept = request_headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+        let format = ImageTransformService::negotiate_format(
+            accept,
+            &image_info.mime_type,
+            &image_data,
+            params,
+        )?;
+        info!("自动协商输出格式: {} (Accept: {})", format, accept);
+    }
 
     // 根据是否需要转换决定处理方式
-    let (final_data, final_mime) = if let Some(ref params) = transform_params {
-        let config = AppConfig::get();
-
-        // 检查是否启用缓存
-        if config.cache.enable_transform_cache {
-            let cache_key = CacheService::generate_cache_key(hash, params);
-
-            // 尝试从缓存获取
-            let connection = app_state.db_pool().get_connection();
-            let cache_service = CacheService::new(connection.clone())?;
-            cache_service.ensure_cache_dir().await?;
-
-            if let Ok(Some(cached)) = cache_service.get_cache(&cache_key).await {
-                info!("缓存命中: {}", cache_key);
-                let cached_data = cache_service.read_cache(&cached).await?;
-                (cached_data, cached.mime_type)
+    let (final_data, final_mime, output_quality) =
+        if let Some(params) = transform_params.as_ref().filter(|_| placeholder_requested) {
+            // 占位图解码开销很小，不写入转换缓存；旧图片缺少哈希时先补算
+            let mut image_info = image_info.clone();
+            ImageService::ensure_placeholders(app_state.db_pool(), &mut image_info).await?;
+            let (data, mime_type) = PlaceholderService::render(&image_info, params)?;
+            (data, mime_type.to_string(), None)
+        } else if let Some(ref params) = transform_params {
+            // 检查是否启用缓存
+            if config.cache.enable_transform_cache {
+                let cache_key = CacheService::generate_cache_key(hash, params);
+
+                // 尝试从缓存获取
+                let connection = app_state.db_pool().get_connection();
+                let cache_service = CacheService::new(connection.clone())?;
+                cache_service.ensure_cache_dir().await?;
+
+                if let Ok(Some(cached)) = cache_service.get_cache(&cache_key).await {
+                    info!("缓存命中: {}", cache_key);
+                    let cached_data = cache_service.read_cache(&cached).await?;
+                    (cached_data, cached.mime_type, cached.output_quality)
+                } else {
+                    // 缓存未命中，进行转换
+                    info!(
+                        "缓存未命中，开始图片转换: {} -> {:?}",
+                        image_info.mime_type, params
+                    );
+                    let watermark_data =
+                        WatermarkService::load_watermark_image(watermark_image.as_ref()).await?;
+                    let output = ImageTransformService::transform_image(
+                        &image_data,
+                        &image_info.mime_type,
+                        params,
+                        watermark_data.as_deref(),
+                    )
+                    .await?;
+
+                    // 保存到缓存
+                    if let Err(e) = cache_service
+                        .save_cache(
+                            hash,
+                            params,
+                            &output.data,
+                            &output.mime_type,
+                            output.quality,
+                        )
+                        .await
+                    {
+                        warn!("保存缓存失败: {}", e);
+                    }
+
+                    (output.data, output.mime_type, output.quality)
+                }
             } else {
-                // 缓存未命中，进行转换
+                // 缓存未启用，直接转换
                 info!(
-                    "缓存未命中，开始图片转换: {} -> {:?}",
+                    "开始图片转换（缓存未启用）: {} -> {:?}",
                     image_info.mime_type, params
                 );
-                let (transformed_data, transformed_mime) = ImageTransformService::transform_image(
+                let watermark_data =
+                    WatermarkService::load_watermark_image(watermark_image.as_ref()).await?;
+                let output = ImageTransformService::transform_image(
                     &image_data,
                     &image_info.mime_type,
                     params,
+                    watermark_data.as_deref(),
                 )
                 .await?;
-
-                // 保存到缓存
-                if let Err(e) = cache_service
-                    .save_cache(hash, params, &transformed_data, &transformed_mime)
-                    .await
-                {
-                    warn!("保存缓存失败: {}", e);
-                }
-
-                (transformed_data, transformed_mime)
+                (output.data, output.mime_type, output.quality)
             }
         } else {
-            // 缓存未启用，直接转换
-            info!(
-                "开始图片转换（缓存未启用）: {} -> {:?}",
-                image_info.mime_type, params
-            );
-            ImageTransformService::transform_image(&image_data, &image_info.mime_type, params)
-                .await?
-        }
-    } else {
-        // 不需要转换，返回原始数据
-        (image_data, image_info.mime_type.clone())
-    };
+            // 不需要转换，返回原始数据
+            (image_data, image_info.mime_type.clone(), None)
+        };
+
+    // 转换成功后才计入查看次数，无效参数或编码失败不消耗限次图片的查看次数
+    if !placeholder_requested {
+        ImageService::record_view(app_state.db_pool(), &image_info).await?;
+    }
 
     // 生成文件名（如果进行了转换，使用新的扩展名）
     let filename = if let Some(ref params) = transform_params {
@@ -165,6 +341,8 @@ pub async fn get_image(
                 _ => "jpg",
             };
             format!("{}.{}", hash, ext)
+        } else if params.placeholder.is_some() {
+            format!("{}.{}", hash, if params.svg { "svg" } else { "png" })
         } else {
             image_info.stored_name()
         }
@@ -173,10 +351,25 @@ pub async fn get_image(
     };
 
     let content_disposition = format!(r#"inline; filename="{}""#, filename);
-    let cache_control = config.cache_control_header();
+    let cache_control = if image_info.max_views.is_some()
+        || image_info.visibility == Visibility::Private
+        || image_info.is_password_protected()
+    {
+        // 限制查看次数的图片不能被浏览器或CDN缓存，否则计数会失效；
+        // 私有或受密码保护的图片不能被共享缓存，否则访问控制会被绕过
+        "private, no-store".to_string()
+    } else if let Some(expires_at) = image_info.expires_at {
+        // 缓存时间不超过图片剩余有效期
+        let remaining = (expires_at - Utc::now()).num_seconds().max(0) as u64;
+        format!(
+            "public, max-age={}",
+            remaining.min(config.cache.max_age.as_seconds())
+        )
+    } else {
+        config.cache_control_header()
+    };
 
     // 构建扩展的响应头，包含图片信息
-    use axum::http::HeaderMap;
     let mut headers = HeaderMap::new();
 
     // 基础响应头
@@ -187,6 +380,28 @@ pub async fn get_image(
     );
     headers.insert(header::CACHE_CONTROL, cache_control.parse().unwrap());
 
+    // 声明接受的客户端提示；带转换参数的响应随提示变化
+    if client_hints_enabled {
+        headers.insert(
+            "accept-ch",
+            "Sec-CH-DPR, Sec-CH-Width, Sec-CH-Viewport-Width"
+                .parse()
+                .unwrap(),
+        );
+        if transform_params.is_some() {
+            headers.append(
+                header::VARY,
+                "Sec-CH-DPR, DPR, Sec-CH-Width, Width, Sec-CH-Viewport-Width, Viewport-Width"
+                    .parse()
+                    .unwrap(),
+            );
+        }
+    }
+
+    if format_negotiated {
+        headers.append(header::VARY, "Accept".parse().unwrap());
+    }
+
     // 原始图片信息
     headers.insert("x-original-hash", image_info.hash.parse().unwrap());
     headers.insert(
@@ -211,6 +426,16 @@ pub async fn get_image(
         "x-access-count",
         image_info.access_count.to_string().parse().unwrap(),
     );
+    if let Some(remaining) = image_info
+        .remaining_views()
+        .filter(|_| !placeholder_requested)
+    {
+        // 本次访问已计入
+        headers.insert(
+            "x-remaining-views",
+            (remaining - 1).max(0).to_string().parse().unwrap(),
+        );
+    }
 
     // 处理后的信息
     headers.insert("x-final-mime", final_mime.parse().unwrap());
@@ -245,6 +470,18 @@ pub async fn get_image(
         if let Some(quality) = params.quality {
             headers.insert("x-transform-quality", quality.to_string().parse().unwrap());
         }
+        if let Some(max_bytes) = params.max_bytes {
+            headers.insert(
+                "x-transform-max-bytes",
+                max_bytes.to_string().parse().unwrap(),
+            );
+        }
+        if let Some(quality) = output_quality {
+            headers.insert(
+                "x-transform-chosen-quality",
+                quality.to_string().parse().unwrap(),
+            );
+        }
 
         if params.no_alpha {
             headers.insert("x-transform-noalpha", "true".parse().unwrap());
@@ -260,12 +497,20 @@ pub async fn get_image(
             }
         }
 
-        // 生成转换参数的完整字符串
-        let params_summary = identifier.split('@').nth(1).unwrap_or("").to_string();
-        headers.insert("x-transform-params", params_summary.parse().unwrap());
-
-        // 检查是否从GIF提取了第一帧
-        if image_info.mime_type == "image/gif" && params.format.is_some() {
+        // 生成转换参数的完整字符串（路径、查询字符串和兼容地址统一为标准化写法）
+        headers.insert(
+            "x-transform-params",
+            params.to_normalized_string().parse().unwrap(),
+        );
+
+        // 检查是否从GIF提取了第一帧（输出GIF或WebP时保留动画）
+        if image_info.mime_type == "image/gif"
+            && params.frame.is_none()
+            && params
+                .format
+                .as_deref()
+                .is_some_and(|format| !matches!(format, "gif" | "webp"))
+        {
             headers.insert("x-gif-first-frame", "true".parse().unwrap());
         }
     } else {
@@ -325,15 +570,164 @@ pub async fn get_image(
     Ok((headers, final_data).into_response())
 }
 
+/// 从请求头读取客户端提示，优先使用 Sec-CH- 前缀的标准名称
+fn client_hints_from_headers(headers: &HeaderMap) -> ClientHints {
+    fn read<T: std::str::FromStr>(headers: &HeaderMap, names: [&str; 2]) -> Option<T> {
+        names.iter().find_map(|name| {
+            headers
+                .get(*name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+        })
+    }
+
+    ClientHints {
+        dpr: read::<f32>(headers, ["sec-ch-dpr", "dpr"])
+            .filter(|dpr| dpr.is_finite() && *dpr > 0.0),
+        width: read(headers, ["sec-ch-width", "width"]),
+        viewport_width: read(headers, ["sec-ch-viewport-width", "viewport-width"]),
+    }
+}
+
+/// 解析图片水印引用的图片并将标识符替换为哈希（参数不包含图片水印时返回None）
+async fn resolve_watermark_image(
+    app_state: &AppState,
+    params: &mut ImageTransformParams,
+    trusted: bool,
+) -> Result<Option<ImageInfo>, AppError> {
+    match params.watermark_params_mut() {
+        Some(watermark) => {
+            WatermarkService::resolve_watermark_image(app_state.db_pool(), watermark, trusted).await
+        }
+        None => Ok(None),
+    }
+}
+
+/// 获取响应式图片清单接口：返回各宽度/格式的链接、srcset和<picture>代码
+pub async fn get_srcset(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(query): Query<SrcsetQuery>,
+    request_headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let image_info = ImageService::get_image_info(app_state.db_pool(), &identifier)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    ImageService::ensure_available(&image_info)?;
+
+    let credentials = AccessCredentials::from_request(&request_headers, query.token.clone());
+    AccessService::authorize(&image_info, &credentials)?;
+
+    if query.pregenerate {
+        if !credentials.has_valid_api_key() {
+            return Err(AppError::Unauthorized(
+                "预生成需要有效的API密钥".to_string(),
+            ));
+        }
+        if !AppConfig::get().cache.enable_transform_cache {
+            return Err(AppError::BadRequest(
+                "转换缓存未启用，无法预生成".to_string(),
+            ));
+        }
+    }
+
+    // 生成清单不计入查看次数
+    let image_data = ImageService::read_image_file_untracked(&image_info).await?;
+    let (mut manifest, variants) = SrcsetService::build_manifest(&image_info, &image_data, &query)?;
+
+    if query.pregenerate {
+        manifest.pregenerated =
+            pregenerate_variants(&app_state, &image_info, &image_data, variants).await?;
+        info!(
+            "预生成响应式图片变体: {} 共{}个",
+            image_info.hash, manifest.pregenerated
+        );
+    }
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "生成srcset成功",
+        "data": manifest
+    })))
+}
+
+/// 将变体转换后写入缓存，已缓存、自动协商格式和占位图变体跳过，返回新生成的数量
+async fn pregenerate_variants(
+    app_state: &AppState,
+    image_info: &ImageInfo,
+    image_data: &[u8],
+    variants: Vec<ImageTransformParams>,
+) -> Result<usize, AppError> {
+    let cache_service = CacheService::new(app_state.db_pool().get_connection().clone())?;
+    cache_service.ensure_cache_dir().await?;
+
+    // 按匿名访问清单链接时命中的强制水印规则处理，保证缓存键一致
+    let path = format!(
+        "/images/{}",
+        image_info.short_id.as_deref().unwrap_or(&image_info.hash)
+    );
+    let anonymous = AccessCredentials::from_request(&HeaderMap::new(), None);
+    let forced_watermark = WatermarkService::forced_watermark(&path, &anonymous);
+    let watermark_forced = forced_watermark.is_some();
+
+    let mut generated = 0;
+    for mut params in variants {
+        // 自动格式的结果取决于请求头，占位图不写入缓存
+        if params.is_auto_format() || params.placeholder.is_some() {
+            continue;
+        }
+        if let Some(ref watermark) = forced_watermark {
+            params.force_watermark(watermark.clone());
+        }
+        let watermark_image =
+            resolve_watermark_image(app_state, &mut params, watermark_forced).await?;
+
+        let cache_key = CacheService::generate_cache_key(&image_info.hash, &params);
+        if cache_service.get_cache(&cache_key).await?.is_some() {
+            continue;
+        }
+
+        let watermark_data =
+            WatermarkService::load_watermark_image(watermark_image.as_ref()).await?;
+        let output = ImageTransformService::transform_image(
+            image_data,
+            &image_info.mime_type,
+            &params,
+            watermark_data.as_deref(),
+        )
+        .await?;
+        cache_service
+            .save_cache(
+                &image_info.hash,
+                &params,
+                &output.data,
+                &output.mime_type,
+                output.quality,
+            )
+            .await?;
+        generated += 1;
+    }
+
+    Ok(generated)
+}
+
 /// 获取图片信息接口（通过哈希值）
 pub async fn get_image_info(
     State(app_state): State<AppState>,
     Path(identifier): Path<String>,
+    Query(access): Query<AccessQuery>,
+    request_headers: HeaderMap,
 ) -> Result<impl IntoResponse, AppError> {
     let image_info = ImageService::get_image_info(app_state.db_pool(), &identifier)
         .await?
         .ok_or(AppError::FileNotFound)?;
 
+    ImageService::ensure_available(&image_info)?;
+
+    let credentials = AccessCredentials::from_request(&request_headers, access.token);
+    AccessService::authorize(&image_info, &credentials)?;
+
     Ok(Json(serde_json::json!({
         "success": true,
         "message": "获取图片信息成功",
@@ -344,10 +738,15 @@ pub async fn get_image_info(
 /// 查询图片列表 (POST - JSON请求体)
 pub async fn query_images_post(
     State(app_state): State<AppState>,
-    Json(query): Json<ImageQuery>,
+    request_headers: HeaderMap,
+    Json(mut query): Json<ImageQuery>,
 ) -> Result<impl IntoResponse, AppError> {
     info!("收到查询图片列表请求 (POST): {:?}", query);
 
+    // 非公开图片只对持有API密钥的请求列出
+    query.include_hidden =
+        AccessCredentials::from_request(&request_headers, None).has_valid_api_key();
+
     let (images, total) = ImageService::query_images(app_state.db_pool(), &query).await?;
 
     info!("返回图片列表: {} 条记录，总计 {} 条", images.len(), total);
@@ -367,10 +766,15 @@ pub async fn query_images_post(
 /// 查询图片列表 (GET - URL查询参数)
 pub async fn query_images_get(
     State(app_state): State<AppState>,
-    Query(query): Query<ImageQuery>,
+    request_headers: HeaderMap,
+    Query(mut query): Query<ImageQuery>,
 ) -> Result<impl IntoResponse, AppError> {
     info!("收到查询图片列表请求 (GET): {:?}", query);
 
+    // 非公开图片只对持有API密钥的请求列出
+    query.include_hidden =
+        AccessCredentials::from_request(&request_headers, None).has_valid_api_key();
+
     let (images, total) = ImageService::query_images(app_state.db_pool(), &query).await?;
 
     info!("返回图片列表: {} 条记录，总计 {} 条", images.len(), total);
@@ -403,13 +807,24 @@ pub async fn get_stats(State(app_state): State<AppState>) -> Result<impl IntoRes
 }
 
 /// 删除图片接口（通过哈希值）
+///
+/// 非公开图片需要API密钥或删除范围的访问令牌
 pub async fn delete_image(
     State(app_state): State<AppState>,
     Path(identifier): Path<String>,
+    Query(access): Query<AccessQuery>,
+    request_headers: HeaderMap,
 ) -> Result<impl IntoResponse, AppError> {
     info!("收到删除图片请求: {}", identifier);
 
-    ImageService::delete_image(app_state.db_pool(), &identifier).await?;
+    let image_info = ImageService::get_image_info(app_state.db_pool(), &identifier)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    let credentials = AccessCredentials::from_request(&request_headers, access.token);
+    AccessService::authorize_delete(&image_info, &credentials)?;
+
+    let image_info = ImageService::delete_image(app_state.db_pool(), &image_info.hash).await?;
 
     let config = AppConfig::get();
     let mut cache_count = 0;
@@ -419,11 +834,14 @@ pub async fn delete_image(
         let connection = app_state.db_pool().get_connection();
         let cache_service = CacheService::new(connection)?;
 
-        match cache_service.remove_by_original_hash(&identifier).await {
+        match cache_service
+            .remove_by_original_hash(&image_info.hash)
+            .await
+        {
             Ok(count) => {
                 cache_count = count;
                 if count > 0 {
-                    info!("删除图片{}的{}个相关缓存", identifier, count);
+                    info!("删除图片{}的{}个相关缓存", image_info.hash, count);
                 }
             }
             Err(e) => {
@@ -432,7 +850,7 @@ pub async fn delete_image(
         }
     }
 
-    info!("图片删除成功: {}", identifier);
+    info!("图片删除成功: {}", image_info.hash);
 
     Ok(Json(serde_json::json!({
         "success": true,
@@ -440,3 +858,117 @@ pub async fn delete_image(
         "cache_cleaned": cache_count
     })))
 }
+
+/// 密码解锁接口，校验通过后签发查看令牌并写入Cookie
+///
+/// 同一来源连续失败过多时按指数退避拒绝尝试
+pub async fn unlock_image(
+    State(app_state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(identifier): Path<String>,
+    request_headers: HeaderMap,
+    Json(request): Json<UnlockRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = AccessService::client_ip(addr.ip(), &request_headers);
+
+    let image_info = ImageService::get_image_info(app_state.db_pool(), &identifier)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    ImageService::ensure_available(&image_info)?;
+
+    let Some(password_hash) = image_info.password_hash.as_deref() else {
+        return Err(AppError::BadRequest("该图片未设置访问密码".to_string()));
+    };
+
+    AccessService::check_unlock_allowed(&image_info.hash, client)?;
+
+    if !AccessService::verify_password(password_hash, &request.password) {
+        AccessService::record_unlock_failure(&image_info.hash, client);
+        warn!("图片解锁失败，密码错误: {} ({})", image_info.hash, client);
+        return Err(AppError::Unauthorized("访问密码错误".to_string()));
+    }
+    AccessService::clear_unlock_failures(&image_info.hash, client);
+
+    let ttl = AppConfig::get().auth.unlock_token_ttl;
+    let scope = TokenScope::View;
+    let (token, expires_at) = AccessService::issue_token(&image_info.hash, scope, ttl);
+    info!("图片解锁成功: {}", image_info.hash);
+
+    let cookie = format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        AccessService::token_cookie_name(&image_info.hash),
+        token,
+        ttl.as_seconds()
+    );
+
+    Ok((
+        [(header::SET_COOKIE, cookie)],
+        Json(serde_json::json!({
+            "success": true,
+            "message": "图片解锁成功",
+            "data": AccessTokenData {
+                token,
+                scope,
+                expires_at
+            }
+        })),
+    ))
+}
+
+/// 签发分享令牌接口（需要API密钥），用于分享私有图片
+///
+/// 默认签发查看令牌，scope=delete 时签发删除令牌
+pub async fn create_share_token(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(query): Query<ShareTokenQuery>,
+    request_headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    if !AccessCredentials::from_request(&request_headers, None).has_valid_api_key() {
+        return Err(AppError::Unauthorized("需要有效的API密钥".to_string()));
+    }
+
+    let image_info = ImageService::get_image_info(app_state.db_pool(), &identifier)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    ImageService::ensure_available(&image_info)?;
+
+    let ttl = match query
+        .ttl
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        Some(ttl) => {
+            let ttl = ttl
+                .parse::<Duration>()
+                .map_err(|e| AppError::BadRequest(format!("无效的ttl: {}", e)))?;
+            if ttl.as_seconds() == 0 {
+                return Err(AppError::BadRequest("ttl必须大于0".to_string()));
+            }
+            ttl
+        }
+        None => AppConfig::get().auth.share_token_ttl,
+    };
+
+    let scope = query.scope.unwrap_or_default();
+    let (token, expires_at) = AccessService::issue_token(&image_info.hash, scope, ttl);
+    info!(
+        "签发分享令牌: {} ({}, {})",
+        image_info.hash,
+        scope.as_str(),
+        ttl
+    );
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "签发分享令牌成功",
+        "data": AccessTokenData {
+            token,
+            scope,
+            expires_at
+        }
+    })))
+}
@@ -1,4 +1,5 @@
 pub mod cache_handler;
+pub mod compat_handler;
 pub mod health_handler;
 pub mod image_handler;
 pub mod static_files;
@@ -7,9 +8,10 @@ pub use cache_handler::{
     auto_cleanup_cache, cache_management_dashboard, clear_all_cache, decay_heat_scores,
     get_cache_stats,
 };
+pub use compat_handler::{imgproxy_image, thumbor_image};
 pub use health_handler::{get_system_stats, health_check_detailed};
 pub use image_handler::{
-    delete_image, get_image, get_image_info, get_stats, query_images_get, query_images_post,
-    upload_image,
+    create_share_token, delete_image, get_image, get_image_info, get_srcset, get_stats,
+    query_images_get, query_images_post, unlock_image, upload_image,
 };
 pub use static_files::api_docs;
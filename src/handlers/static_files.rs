@@ -532,7 +532,7 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                             <span class="path">/upload</span>
                         </div>
                         <div class="endpoint-content">
-                            <div class="description">上传图片文件 (multipart/form-data, field: file)</div>
+                            <div class="description">上传图片文件 (multipart/form-data, field: file, 可选 alias 自定义别名, expires_in/expires_at 过期时间, max_views 最大查看次数, visibility 可见性 public/unlisted/private, password 访问密码)</div>
                         </div>
                     </div>
 
@@ -542,18 +542,36 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                             <span class="path">/images/{identifier}[@params]</span>
                         </div>
                         <div class="endpoint-content">
-                            <div class="description">获取图片文件 (通过哈希值)</div>
+                            <div class="description">获取图片文件 (标识符支持完整哈希、短ID、别名或唯一哈希前缀)</div>
                             <div style="margin-top: 12px;">
                                 <strong style="color: #06b6d4;">🎯 实时转换功能</strong><br>
                                 <small style="color: #94a3b8;">在文件名后添加 @ 参数即可实现实时转换</small>
                             </div>
                             <div style="margin-top: 8px; font-family: 'Monaco', 'Courier New', monospace; font-size: 0.85rem; background: rgba(6, 182, 212, 0.1); padding: 8px; border-radius: 6px;">
                                 <strong>示例:</strong> /images/abc123@w800_h600_jpeg_q90_base64raw<br>
-                                <strong>参数:</strong> w宽度_h高度_格式_na去透明_q质量_base64/base64raw
+                                <strong>查询字符串:</strong> /images/abc123?w=800&amp;h=600&amp;fmt=jpeg&amp;q=90（另支持 fit、dpr、preset）<br>
+                                <strong>参数:</strong> w宽度_h高度_格式_na去透明_q质量_base64/base64raw<br>
+                                <strong>适配:</strong> fit/fill(cover)/contain/scale，对齐 gc/gn/gse/g0.3x0.7/gsmart(智能裁剪)<br>
+                                <strong>旋转:</strong> r90/r180/r270/r任意角度，fliph/flipv 翻转，crop{x}x{y}x{宽}x{高} 裁剪<br>
+                                <strong>多阶段:</strong> 用 ~ 分隔按顺序执行，如 crop0x0x800x600~w400~blur4<br>
+                                <strong>滤镜:</strong> br亮度_ct对比度_sat饱和度_hue色相_gray_sepia_invert_blur半径_sharp半径<br>
+                                <strong>水印:</strong> wmi图片标识符 或 wmt文字base64url，wmp位置_wmm边距_wmo不透明度_wms缩放_wmfs字号_wmc颜色_wmsh阴影<br>
+                                <strong>形状:</strong> rc圆角半径 或 circle，bd边框宽度_bdc边框颜色_pad留白<br>
+                                <strong>占位图:</strong> thumbhash 或 blurhash，可加 w/h 和 svg（内联SVG）
                             </div>
                         </div>
                     </div>
 
+                    <div class="endpoint">
+                        <div class="endpoint-header">
+                            <span class="method get">GET</span>
+                            <span class="path">/imgproxy/{signature}/... · /thumbor/{signature}/...</span>
+                        </div>
+                        <div class="endpoint-content">
+                            <div class="description">imgproxy/thumbor兼容地址，如 /imgproxy/insecure/rs:fill:300:200/plain/abc123@webp、/thumbor/unsafe/300x200/smart/abc123 (签名不校验，前缀可在 [compat] 中配置)</div>
+                        </div>
+                    </div>
+
                     <div class="endpoint">
                         <div class="endpoint-header">
                             <span class="method get">GET</span>
@@ -564,6 +582,36 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                         </div>
                     </div>
 
+                    <div class="endpoint">
+                        <div class="endpoint-header">
+                            <span class="method get">GET</span>
+                            <span class="path">/images/{identifier}/srcset</span>
+                        </div>
+                        <div class="endpoint-content">
+                            <div class="description">生成响应式图片清单和&lt;picture&gt;代码 (widths、preset、presets、formats、sizes、base_url，pregenerate=true 需要 X-API-Key)</div>
+                        </div>
+                    </div>
+
+                    <div class="endpoint">
+                        <div class="endpoint-header">
+                            <span class="method post">POST</span>
+                            <span class="path">/images/{identifier}/unlock</span>
+                        </div>
+                        <div class="endpoint-content">
+                            <div class="description">使用密码解锁图片 (JSON: {"password": "..."}，返回访问令牌并写入Cookie)</div>
+                        </div>
+                    </div>
+
+                    <div class="endpoint">
+                        <div class="endpoint-header">
+                            <span class="method post">POST</span>
+                            <span class="path">/images/{identifier}/token[?ttl=1d]</span>
+                        </div>
+                        <div class="endpoint-content">
+                            <div class="description">签发私有图片的分享令牌 (需要 X-API-Key，访问时通过 ?token= 携带)</div>
+                        </div>
+                    </div>
+
                     <div class="endpoint">
                         <div class="endpoint-header">
                             <span class="method delete">DELETE</span>
@@ -633,7 +681,7 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                             <span class="path">/api/images/query</span>
                         </div>
                         <div class="endpoint-content">
-                            <div class="description">高级查询图片列表 (支持分页、过滤、排序) - GET使用URL参数，POST使用JSON请求体</div>
+                            <div class="description">高级查询图片列表 (支持分页、过滤、排序，color/color_tolerance 按主色调过滤) - GET使用URL参数，POST使用JSON请求体</div>
                         </div>
                     </div>
 
@@ -663,6 +711,8 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                             <ul style="margin-top: 8px; padding-left: 20px;">
                                 <li><code>w{数字}</code> - 设置最大宽度像素</li>
                                 <li><code>h{数字}</code> - 设置最大高度像素</li>
+                                <li><code>dpr{倍数}</code> - 按设备像素比放大宽高</li>
+                                <li><code>{预设名}</code> - 使用配置中的转换预设</li>
                                 <li>等比缩放，保持原图比例</li>
                                 <li>小于设定值的图片不会放大</li>
                             </ul>
@@ -672,6 +722,7 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                             <ul style="margin-top: 8px; padding-left: 20px;">
                                 <li><code>jpeg</code> - 转为JPEG格式（有损）</li>
                                 <li><code>png</code> - 转为PNG格式（无损）</li>
+                                <li><code>auto</code> - 按浏览器支持自动选择格式</li>
                                 <li><code>webp</code> - 转为WebP格式（无损）</li>
                                 <li><code>avif</code> - 转为AVIF格式（有损）</li>
     
@@ -683,6 +734,12 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                             <strong style="color: #f1f5f9;">🎛️ 质量控制</strong>
                             <ul style="margin-top: 8px; padding-left: 20px;">
                                 <li><code>q{1-100}</code> - 设置图片质量</li>
+                                <li><code>lossless</code> - WebP无损压缩</li>
+                                <li><code>speed{1-10}</code> - AVIF编码速度</li>
+                                <li><code>kb{数字}</code> - 输出体积上限，自动搜索质量</li>
+                                <li><code>prog</code> / <code>chroma444</code> / <code>huffman</code> - JPEG渐进式、色度抽样、霍夫曼表优化</li>
+                                <li><code>pal{2-256}</code> / <code>nodither</code> - PNG调色板量化</li>
+                                <li><code>srgb</code> / <code>keepicc</code> / <code>noicc</code> - ICC配置文件转换为sRGB/保留/去除</li>
                                 <li>仅对JPEG等有损格式有效</li>
                                 <li>数值越高质量越好</li>
                             </ul>
@@ -727,7 +784,13 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                         <strong style="color: #8b5cf6;">🎬 动画处理示例</strong>
                         <div style="margin-top: 8px; font-family: 'Monaco', 'Courier New', monospace; font-size: 0.9rem;">
                             <div>/images/animated_gif - 保持GIF动画（原尺寸）</div>
-                            <div>/images/animated_gif@w800 - 仍返回原动画（不支持动画尺寸调整）</div>
+                            <div>/images/animated_gif@w800 - 逐帧缩放，保留动画</div>
+                            <div>/images/animated_webp@w400_h400_fill_q70 - 逐帧裁剪为400x400动态WebP</div>
+                            <div>/images/animated_gif@webp_q75 - GIF转动态WebP，保留动画</div>
+                            <div>/images/animated_webp@gif - 动态WebP转GIF动图</div>
+                            <div>/images/animated_gif@frame5_png - 提取第6帧转PNG</div>
+                            <div>/images/animated_gif@at1.5s_jpeg - 提取1.5秒处的帧</div>
+                            <div>/images/animated_gif@w160_frames12x4_jpeg - 抽取12帧拼成4列帧序列图</div>
                             <div>/images/animated_gif@jpeg - 提取第一帧转JPEG</div>
                             <div>/images/animated_gif@w600_jpeg - 第一帧转JPEG，600px宽</div>
                             <div>/images/animated_gif@w600_png_naw - 第一帧转PNG，白背景，600px宽</div>
@@ -0,0 +1,101 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 短ID列（上传时生成的base62短标识）
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Images::ShortId).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // 别名列（用户自定义的标识）
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Images::Alias).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // 唯一索引保证短ID和别名不重复
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_images_short_id")
+                    .table(Images::Table)
+                    .col(Images::ShortId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_images_alias")
+                    .table(Images::Table)
+                    .col(Images::Alias)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_images_alias")
+                    .table(Images::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_images_short_id")
+                    .table(Images::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .drop_column(Images::Alias)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .drop_column(Images::ShortId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Images {
+    Table,
+    ShortId,
+    Alias,
+}
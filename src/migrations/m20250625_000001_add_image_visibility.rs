@@ -0,0 +1,84 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 可见性列（public/unlisted/private）
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Images::Visibility)
+                            .string()
+                            .not_null()
+                            .default("public"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 访问密码哈希列（为空表示无需密码）
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Images::PasswordHash).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // 列表查询按可见性过滤
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_images_visibility")
+                    .table(Images::Table)
+                    .col(Images::Visibility)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_images_visibility")
+                    .table(Images::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .drop_column(Images::PasswordHash)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .drop_column(Images::Visibility)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Images {
+    Table,
+    Visibility,
+    PasswordHash,
+}
@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 按目标体积编码时选定的质量（为空表示未进行体积搜索）
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Cache::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Cache::OutputQuality).integer().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Cache::Table)
+                    .drop_column(Cache::OutputQuality)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Cache {
+    Table,
+    OutputQuality,
+}
@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // BlurHash占位图哈希列（为空表示尚未计算）
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Images::Blurhash).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // ThumbHash占位图哈希列（base64编码）
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Images::Thumbhash).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .drop_column(Images::Thumbhash)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .drop_column(Images::Blurhash)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Images {
+    Table,
+    Blurhash,
+    Thumbhash,
+}
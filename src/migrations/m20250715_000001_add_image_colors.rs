@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 主色调按通道分列存储，便于按颜色范围查询
+        for column in [
+            Images::DominantRed,
+            Images::DominantGreen,
+            Images::DominantBlue,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Images::Table)
+                        .add_column_if_not_exists(ColumnDef::new(column).small_integer().null())
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // 调色板列（逗号分隔的 #rrggbb）
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Images::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Images::Palette).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            Images::Palette,
+            Images::DominantBlue,
+            Images::DominantGreen,
+            Images::DominantRed,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Images::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Images {
+    Table,
+    DominantRed,
+    DominantGreen,
+    DominantBlue,
+    Palette,
+}
@@ -2,6 +2,13 @@ use sea_orm_migration::prelude::*;
 
 mod m20240101_000001_create_images_table;
 mod m20241201_000001_create_cache_table;
+mod m20250601_000001_add_image_short_id_and_alias;
+mod m20250615_000001_add_image_expires_at;
+mod m20250620_000001_add_image_max_views;
+mod m20250625_000001_add_image_visibility;
+mod m20250701_000001_add_cache_output_quality;
+mod m20250710_000001_add_image_placeholders;
+mod m20250715_000001_add_image_colors;
 
 pub struct Migrator;
 
@@ -11,6 +18,13 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20240101_000001_create_images_table::Migration),
             Box::new(m20241201_000001_create_cache_table::Migration),
+            Box::new(m20250601_000001_add_image_short_id_and_alias::Migration),
+            Box::new(m20250615_000001_add_image_expires_at::Migration),
+            Box::new(m20250620_000001_add_image_max_views::Migration),
+            Box::new(m20250625_000001_add_image_visibility::Migration),
+            Box::new(m20250701_000001_add_cache_output_quality::Migration),
+            Box::new(m20250710_000001_add_image_placeholders::Migration),
+            Box::new(m20250715_000001_add_image_colors::Migration),
         ]
     }
 }
@@ -1,6 +1,22 @@
+use base64::{engine::general_purpose, Engine as _};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// 转换后图片的最大边长
+pub const MAX_DIMENSION: u32 = 8192;
+
+/// 按颜色查询图片时默认的通道容差
+const DEFAULT_COLOR_TOLERANCE: u8 = 32;
+
+/// 自动协商格式的参数值
+pub const AUTO_FORMAT: &str = "auto";
+
+/// 转换参数中分隔处理阶段的字符
+pub const STAGE_SEPARATOR: char = '~';
+
+/// 单个URL允许的最大处理阶段数
+pub const MAX_STAGES: usize = 10;
+
 /// 图片信息结构体
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageInfo {
@@ -18,6 +34,94 @@ pub struct ImageInfo {
     pub extension: String,
     /// 访问次数
     pub access_count: i64,
+    /// 短ID（base62，上传时生成）
+    pub short_id: Option<String>,
+    /// 用户自定义别名
+    pub alias: Option<String>,
+    /// 过期时间（为空表示永久保存）
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 最大查看次数（为空表示不限制）
+    pub max_views: Option<i64>,
+    /// 可见性
+    pub visibility: Visibility,
+    /// 访问密码哈希（对外只输出是否设置了密码）
+    #[serde(
+        rename = "password_protected",
+        serialize_with = "serialize_is_some",
+        skip_deserializing
+    )]
+    pub password_hash: Option<String>,
+    /// BlurHash占位图哈希
+    pub blurhash: Option<String>,
+    /// ThumbHash占位图哈希（base64编码）
+    pub thumbhash: Option<String>,
+    /// 主色调（#rrggbb）
+    pub dominant_color: Option<String>,
+    /// 调色板（#rrggbb，按占比从高到低排列）
+    pub palette: Vec<String>,
+}
+
+/// 将Option序列化为是否有值
+fn serialize_is_some<S, T>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_bool(value.is_some())
+}
+
+/// 图片可见性
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// 公开，出现在列表查询中
+    Public,
+    /// 不公开列出，但知道链接即可访问
+    Unlisted,
+    /// 私有，需要API密钥或签名令牌
+    Private,
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+        }
+    }
+}
+
+impl std::str::FromStr for Visibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "public" => Ok(Visibility::Public),
+            "unlisted" => Ok(Visibility::Unlisted),
+            "private" => Ok(Visibility::Private),
+            _ => Err(format!("无效的可见性: {}", s)),
+        }
+    }
+}
+
+/// 访问令牌的授权范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    /// 查看图片
+    #[default]
+    View,
+    /// 删除图片
+    Delete,
+}
+
+impl TokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::View => "view",
+            TokenScope::Delete => "delete",
+        }
+    }
 }
 
 impl ImageInfo {
@@ -25,6 +129,47 @@ impl ImageInfo {
     pub fn stored_name(&self) -> String {
         format!("{}.{}", self.hash, self.extension)
     }
+
+    /// 检查图片是否已过期
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    /// 检查图片是否已达到最大查看次数
+    pub fn is_view_limit_reached(&self) -> bool {
+        self.max_views
+            .is_some_and(|max_views| self.access_count >= max_views)
+    }
+
+    /// 是否需要密码解锁
+    pub fn is_password_protected(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// 剩余可查看次数（不限制时为None）
+    pub fn remaining_views(&self) -> Option<i64> {
+        self.max_views
+            .map(|max_views| (max_views - self.access_count).max(0))
+    }
+}
+
+/// 上传选项
+#[derive(Debug, Clone, Default)]
+pub struct UploadOptions {
+    /// 自定义别名
+    pub alias: Option<String>,
+    /// 过期时间
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 最大查看次数
+    pub max_views: Option<i64>,
+    /// 可见性（默认公开）
+    pub visibility: Option<Visibility>,
+    /// 访问密码
+    pub password: Option<String>,
+    /// 上传请求是否携带有效的API密钥，只有此时才允许放宽已有图片的过期时间和查看次数，
+    /// 以及修改已有图片的可见性和访问密码
+    pub trusted: bool,
 }
 
 /// 图片查询参数
@@ -48,8 +193,50 @@ pub struct ImageQuery {
     pub start_time: Option<DateTime<Utc>>,
     /// 结束时间
     pub end_time: Option<DateTime<Utc>>,
-    /// 搜索关键词（文件名）
+    /// 搜索关键词（哈希或别名）
     pub search: Option<String>,
+    /// 按主色调过滤（#rrggbb 或 rrggbb）
+    pub color: Option<String>,
+    /// 颜色过滤的容差（各通道允许的最大差值，默认32）
+    pub color_tolerance: Option<u8>,
+    /// 是否包含非公开图片（仅持有API密钥时由服务端设置）
+    #[serde(skip)]
+    pub include_hidden: bool,
+}
+
+/// 按主色调过滤的条件
+#[derive(Debug, Clone, Copy)]
+pub struct ColorFilter {
+    /// 目标颜色
+    pub rgb: (u8, u8, u8),
+    /// 各通道允许的最大差值
+    pub tolerance: u8,
+}
+
+impl ImageQuery {
+    /// 解析颜色过滤参数
+    pub fn color_filter(&self) -> Result<Option<ColorFilter>, String> {
+        let Some(color) = self
+            .color
+            .as_deref()
+            .map(str::trim)
+            .filter(|color| !color.is_empty())
+        else {
+            return Ok(None);
+        };
+
+        let hex = if color.starts_with('#') {
+            color.to_string()
+        } else {
+            format!("#{}", color)
+        };
+        let rgb = ImageTransformParams::parse_hex_color(&hex)?;
+
+        Ok(Some(ColorFilter {
+            rgb,
+            tolerance: self.color_tolerance.unwrap_or(DEFAULT_COLOR_TOLERANCE),
+        }))
+    }
 }
 
 /// 图片统计信息
@@ -83,6 +270,198 @@ pub struct TimeStat {
     pub total_size: i64,
 }
 
+/// 图片访问查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct AccessQuery {
+    /// 访问令牌（解锁或分享时签发）
+    pub token: Option<String>,
+}
+
+/// 查询字符串形式的转换参数，如 ?w=300&h=200&fmt=webp&q=80
+///
+/// 与 @ 路径语法等价；两者同时使用时查询参数排在后面，覆盖路径中的同类参数
+#[derive(Debug, Default, Deserialize)]
+pub struct TransformQuery {
+    /// 宽度
+    #[serde(alias = "width")]
+    pub w: Option<String>,
+    /// 高度
+    #[serde(alias = "height")]
+    pub h: Option<String>,
+    /// 输出格式（含auto）
+    #[serde(alias = "format")]
+    pub fmt: Option<String>,
+    /// 质量
+    #[serde(alias = "quality")]
+    pub q: Option<String>,
+    /// 尺寸适配模式
+    pub fit: Option<String>,
+    /// 设备像素比
+    pub dpr: Option<String>,
+    /// 预设名称
+    pub preset: Option<String>,
+}
+
+impl TransformQuery {
+    /// 转换为 @ 路径语法的参数字符串，未指定任何转换参数时返回None
+    pub fn to_params_string(&self) -> Result<Option<String>, String> {
+        let value = |field: &Option<String>| {
+            field
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let mut parts = Vec::new();
+
+        // 预设最先展开，其后的参数覆盖预设中的同类参数
+        if let Some(preset) = value(&self.preset) {
+            if preset.contains(['_', '@', STAGE_SEPARATOR]) {
+                return Err(format!("无效的预设名称: {}", preset));
+            }
+            parts.push(preset);
+        }
+        if let Some(width) = value(&self.w) {
+            let width = width
+                .parse::<u32>()
+                .map_err(|_| format!("无效的宽度: {}", width))?;
+            parts.push(format!("w{}", width));
+        }
+        if let Some(height) = value(&self.h) {
+            let height = height
+                .parse::<u32>()
+                .map_err(|_| format!("无效的高度: {}", height))?;
+            parts.push(format!("h{}", height));
+        }
+        if let Some(dpr) = value(&self.dpr) {
+            let dpr = dpr
+                .parse::<f32>()
+                .ok()
+                .filter(|dpr| dpr.is_finite() && *dpr > 0.0)
+                .ok_or_else(|| format!("无效的设备像素比: {}", dpr))?;
+            parts.push(format!("dpr{}", dpr));
+        }
+        if let Some(fit) = value(&self.fit) {
+            let fit = ImageTransformParams::parse_fit_mode(&fit)
+                .ok_or_else(|| format!("无效的适配模式: {}", fit))?;
+            parts.push(fit.as_str().to_string());
+        }
+        if let Some(format) = value(&self.fmt) {
+            let format = format.to_lowercase();
+            if !ImageTransformParams::is_valid_format(&format) && format != AUTO_FORMAT {
+                return Err(format!("不支持的输出格式: {}", format));
+            }
+            parts.push(format);
+        }
+        if let Some(quality) = value(&self.q) {
+            let quality = quality
+                .parse::<u8>()
+                .ok()
+                .filter(|quality| (1..=100).contains(quality))
+                .ok_or_else(|| format!("质量参数必须在1-100之间: {}", quality))?;
+            parts.push(format!("q{}", quality));
+        }
+
+        Ok((!parts.is_empty()).then(|| parts.join("_")))
+    }
+}
+
+/// 密码解锁请求
+#[derive(Debug, Deserialize)]
+pub struct UnlockRequest {
+    /// 访问密码
+    pub password: String,
+}
+
+/// 签发分享令牌的查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct ShareTokenQuery {
+    /// 令牌有效期（如 "1h"、"7d"），默认使用配置值
+    pub ttl: Option<String>,
+    /// 令牌授权范围（view/delete），默认 view
+    pub scope: Option<TokenScope>,
+}
+
+/// 响应式图片清单的查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct SrcsetQuery {
+    /// 宽度列表，逗号分隔（默认使用配置 transform.srcset_widths）
+    pub widths: Option<String>,
+    /// 作为各宽度基础参数的预设
+    pub preset: Option<String>,
+    /// 预设列表，逗号分隔，每个预设作为一个候选尺寸（不能与widths同时使用）
+    pub presets: Option<String>,
+    /// 输出格式列表，逗号分隔，每种格式生成一个<source>
+    pub formats: Option<String>,
+    /// sizes属性，默认100vw
+    pub sizes: Option<String>,
+    /// 链接前缀（如 https://img.example.com），默认生成相对路径
+    pub base_url: Option<String>,
+    /// 将所有变体预生成到转换缓存（需要API密钥）
+    #[serde(default)]
+    pub pregenerate: bool,
+    /// 访问令牌，同时附加到生成的链接中
+    pub token: Option<String>,
+}
+
+/// 响应式图片清单
+#[derive(Debug, Serialize)]
+pub struct SrcsetManifest {
+    /// 文件哈希值
+    pub hash: String,
+    /// 原图宽度（按EXIF方向摆正后）
+    pub width: u32,
+    /// 原图高度（按EXIF方向摆正后）
+    pub height: u32,
+    /// 各格式的候选集，按<source>顺序排列
+    pub sources: Vec<SrcsetSource>,
+    /// <img>使用的候选集
+    pub fallback: SrcsetSource,
+    /// sizes属性
+    pub sizes: String,
+    /// 可直接使用的<picture>/<img>代码
+    pub html: String,
+    /// 本次预生成的变体数量
+    pub pregenerated: usize,
+}
+
+/// 同一格式的一组候选图片
+#[derive(Debug, Serialize)]
+pub struct SrcsetSource {
+    /// 输出格式（为空时沿用预设或原图格式）
+    pub format: Option<String>,
+    /// 输出的MIME类型（自动协商格式时为空）
+    pub mime_type: Option<String>,
+    /// srcset属性
+    pub srcset: String,
+    /// 按宽度升序排列的候选图片
+    pub variants: Vec<SrcsetVariant>,
+}
+
+/// 单个候选图片
+#[derive(Debug, Clone, Serialize)]
+pub struct SrcsetVariant {
+    /// 输出宽度
+    pub width: u32,
+    /// 输出高度
+    pub height: u32,
+    /// 转换参数
+    pub params: String,
+    /// 访问链接
+    pub url: String,
+}
+
+/// 访问令牌响应数据
+#[derive(Debug, Serialize)]
+pub struct AccessTokenData {
+    /// 访问令牌，可通过 ?token= 或 X-Access-Token 传递
+    pub token: String,
+    /// 授权范围
+    pub scope: TokenScope,
+    /// 过期时间
+    pub expires_at: DateTime<Utc>,
+}
+
 /// 上传响应结构体
 #[derive(Debug, Serialize)]
 pub struct UploadResponse {
@@ -106,28 +485,303 @@ pub struct ErrorResponse {
 }
 
 /// 图片转换参数
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct ImageTransformParams {
     /// 目标宽度
     pub width: Option<u32>,
     /// 目标高度
     pub height: Option<u32>,
+    /// 设备像素比（宽高按此倍数放大）
+    pub dpr: Option<f32>,
     /// 目标格式
     pub format: Option<String>,
     /// 图片质量 (1-100)
     pub quality: Option<u8>,
+    /// 无损压缩（仅WebP）
+    pub lossless: bool,
+    /// 编码速度（1-10，仅AVIF）
+    pub speed: Option<u8>,
+    /// 渐进式编码（仅JPEG）
+    pub progressive: bool,
+    /// 色度抽样方式（仅JPEG）
+    pub chroma_subsampling: Option<ChromaSubsampling>,
+    /// 优化霍夫曼表（仅JPEG）
+    pub optimize_huffman: bool,
+    /// 调色板量化的颜色数（2-256，仅PNG）
+    pub palette: Option<u16>,
+    /// 调色板量化时关闭抖动
+    pub no_dither: bool,
+    /// 输出体积上限（字节），按此搜索编码质量
+    pub max_bytes: Option<u64>,
+    /// 内嵌ICC配置文件的处理方式（未指定时转换为sRGB）
+    pub icc_mode: Option<IccMode>,
     /// 是否去除透明通道
     pub no_alpha: bool,
     /// 去除透明通道后的背景色
     pub background_color: Option<BackgroundColor>,
     /// Base64输出模式
     pub base64_mode: Base64OutputMode,
+    /// 尺寸适配模式
+    pub fit_mode: FitMode,
+    /// 裁剪/填充时的对齐方式
+    pub gravity: Gravity,
+    /// 顺时针旋转角度（0-360，不含0）
+    pub rotation: Option<f32>,
+    /// 水平翻转
+    pub flip_horizontal: bool,
+    /// 垂直翻转
+    pub flip_vertical: bool,
+    /// 裁剪区域（在旋转翻转之后、缩放之前执行）
+    pub crop: Option<CropRegion>,
+    /// 像素滤镜
+    pub filters: ImageFilters,
+    /// 水印
+    pub watermark: Option<WatermarkParams>,
+    /// 圆角或圆形蒙版
+    pub mask: Option<MaskShape>,
+    /// 边框宽度（像素，向外扩展画布）
+    pub border: Option<u32>,
+    /// 边框颜色（默认黑色）
+    pub border_color: Option<(u8, u8, u8)>,
+    /// 四周留白（像素，透明或以背景色填充）
+    pub padding: Option<u32>,
+    /// 动图帧选择（单帧或帧序列图）
+    pub frame: Option<FrameSelection>,
+    /// 输出占位图（由存储的BlurHash/ThumbHash解码）
+    pub placeholder: Option<PlaceholderKind>,
+    /// 占位图输出为内联SVG
+    pub svg: bool,
+    /// 以 ~ 分隔的后续阶段解析出的操作，依次在第一阶段的结果上执行
+    pub chained: Vec<TransformOperation>,
+}
+
+/// 水印参数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WatermarkParams {
+    /// 图片水印（另一张已存储图片的标识符）
+    pub image: Option<String>,
+    /// 文字水印内容
+    pub text: Option<String>,
+    /// 位置（默认右下角）
+    pub position: Option<Gravity>,
+    /// 距边缘的像素距离
+    pub margin: Option<u32>,
+    /// 不透明度（1-100）
+    pub opacity: Option<u8>,
+    /// 图片水印宽度占目标图片宽度的百分比（1-100）
+    pub scale: Option<u8>,
+    /// 文字大小（像素）
+    pub font_size: Option<u32>,
+    /// 文字颜色
+    pub color: Option<(u8, u8, u8)>,
+    /// 文字阴影
+    pub shadow: bool,
+}
+
+impl WatermarkParams {
+    /// 解析水印参数（wm前缀），无法识别时返回false
+    ///
+    /// wmi{标识符} 图片水印；wmt{base64url} 文字水印；wmp{方位} 位置；wmm{像素} 边距；
+    /// wmo{1-100} 不透明度；wms{1-100} 图片水印相对宽度；wmfs{像素} 字号；
+    /// wmc{RRGGBB} 文字颜色；wmsh 文字阴影
+    fn parse_token(watermark: &mut Option<Self>, param: &str) -> bool {
+        let Some(value) = param.strip_prefix("wm") else {
+            return false;
+        };
+        if value.is_empty() {
+            return false;
+        }
+
+        let params = watermark.get_or_insert_with(Self::default);
+        if value == "sh" {
+            params.shadow = true;
+        } else if let Some(text) = value.strip_prefix('t') {
+            params.text = general_purpose::URL_SAFE_NO_PAD
+                .decode(text.trim_end_matches('='))
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .filter(|text| !text.trim().is_empty());
+        } else if let Some(size) = value.strip_prefix("fs") {
+            params.font_size = size.parse().ok();
+        } else if let Some(position) = value.strip_prefix('p') {
+            params.position = Gravity::parse(position);
+        } else if let Some(margin) = value.strip_prefix('m') {
+            params.margin = margin.parse().ok();
+        } else if let Some(opacity) = value.strip_prefix('o') {
+            params.opacity = opacity.parse().ok();
+        } else if let Some(scale) = value.strip_prefix('s') {
+            params.scale = scale.parse().ok();
+        } else if let Some(color) = value.strip_prefix('c') {
+            params.color = ImageTransformParams::parse_hex_color(&format!(
+                "#{}",
+                color.trim_start_matches('#')
+            ))
+            .ok();
+        } else if let Some(image) = value.strip_prefix('i') {
+            params.image = Some(image.to_string());
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// 生成标准化的参数字符串
+    pub fn to_normalized_string(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(ref image) = self.image {
+            parts.push(format!("wmi{}", image));
+        }
+        if let Some(ref text) = self.text {
+            parts.push(format!(
+                "wmt{}",
+                general_purpose::URL_SAFE_NO_PAD.encode(text.as_bytes())
+            ));
+        }
+        if let Some(position) = self.position {
+            parts.push(format!(
+                "wmp{}",
+                position.to_normalized_string().trim_start_matches('g')
+            ));
+        }
+        if let Some(margin) = self.margin {
+            parts.push(format!("wmm{}", margin));
+        }
+        if let Some(opacity) = self.opacity {
+            parts.push(format!("wmo{}", opacity));
+        }
+        if let Some(scale) = self.scale {
+            parts.push(format!("wms{}", scale));
+        }
+        if let Some(font_size) = self.font_size {
+            parts.push(format!("wmfs{}", font_size));
+        }
+        if let Some((r, g, b)) = self.color {
+            parts.push(format!("wmc{:02x}{:02x}{:02x}", r, g, b));
+        }
+        if self.shadow {
+            parts.push("wmsh".to_string());
+        }
+
+        parts.join("_")
+    }
+}
+
+/// 像素滤镜参数
+///
+/// 按固定顺序应用：亮度、对比度、饱和度、色相、灰度、复古、反色、模糊、锐化
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImageFilters {
+    /// 亮度调整（-100到100）
+    pub brightness: Option<i32>,
+    /// 对比度调整（-100到100）
+    pub contrast: Option<i32>,
+    /// 饱和度调整（-100到100，-100为完全去色）
+    pub saturation: Option<i32>,
+    /// 色相旋转角度（0-360）
+    pub hue_rotate: Option<i32>,
+    /// 灰度
+    pub grayscale: bool,
+    /// 复古色调
+    pub sepia: bool,
+    /// 反色
+    pub invert: bool,
+    /// 高斯模糊半径（sigma）
+    pub blur: Option<f32>,
+    /// 锐化（USM）半径（sigma）
+    pub sharpen: Option<f32>,
+}
+
+impl ImageFilters {
+    /// 是否未设置任何滤镜
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 解析滤镜参数，无法识别时返回false，前缀匹配但数值无效时返回错误
+    fn parse_token(&mut self, param: &str) -> Result<bool, String> {
+        let lower = param.to_lowercase();
+        let invalid = || format!("无效的滤镜参数: {}", param);
+        let number = |value: &str| {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(invalid)
+        };
+        let integer = |value: &str| value.parse::<i32>().map_err(|_| invalid());
+
+        match lower.as_str() {
+            "gray" | "grayscale" => self.grayscale = true,
+            "sepia" => self.sepia = true,
+            "invert" | "neg" => self.invert = true,
+            _ => {
+                if let Some(value) = lower.strip_prefix("blur") {
+                    self.blur = Some(number(value)?);
+                } else if let Some(value) = lower.strip_prefix("sharp") {
+                    // 省略半径时使用默认值1.0
+                    self.sharpen = Some(if value.is_empty() {
+                        1.0
+                    } else {
+                        number(value)?
+                    });
+                } else if let Some(value) = lower.strip_prefix("br") {
+                    self.brightness = Some(integer(value)?);
+                } else if let Some(value) = lower.strip_prefix("ct") {
+                    self.contrast = Some(integer(value)?);
+                } else if let Some(value) = lower.strip_prefix("sat") {
+                    self.saturation = Some(integer(value)?);
+                } else if let Some(value) = lower.strip_prefix("hue") {
+                    self.hue_rotate = Some(integer(value)?.rem_euclid(360)).filter(|v| *v != 0);
+                } else {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// 按应用顺序生成标准化的参数字符串
+    fn normalized_parts(&self) -> Vec<String> {
+        let mut parts = Vec::new();
+
+        if let Some(brightness) = self.brightness {
+            parts.push(format!("br{}", brightness));
+        }
+        if let Some(contrast) = self.contrast {
+            parts.push(format!("ct{}", contrast));
+        }
+        if let Some(saturation) = self.saturation {
+            parts.push(format!("sat{}", saturation));
+        }
+        if let Some(hue) = self.hue_rotate {
+            parts.push(format!("hue{}", hue));
+        }
+        if self.grayscale {
+            parts.push("gray".to_string());
+        }
+        if self.sepia {
+            parts.push("sepia".to_string());
+        }
+        if self.invert {
+            parts.push("invert".to_string());
+        }
+        if let Some(blur) = self.blur {
+            parts.push(format!("blur{}", blur));
+        }
+        if let Some(sharpen) = self.sharpen {
+            parts.push(format!("sharp{}", sharpen));
+        }
+
+        parts
+    }
 }
 
 /// Base64输出模式
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum Base64OutputMode {
     /// 不输出base64
+    #[default]
     None,
     /// 输出包含完整信息的JSON结构体
     Structured,
@@ -135,8 +789,524 @@ pub enum Base64OutputMode {
     Raw,
 }
 
+/// 尺寸适配模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FitMode {
+    /// 等比缩放到指定范围内（不放大）
+    #[default]
+    Fit,
+    /// 等比缩放后裁剪为精确尺寸（cover）
+    Fill,
+    /// 等比缩放到指定范围内，并用透明边填充为精确尺寸（letterbox）
+    Contain,
+    /// 拉伸为精确尺寸，不保持比例
+    Scale,
+}
+
+impl FitMode {
+    /// 是否输出精确的宽高（需要同时指定宽度和高度）
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, FitMode::Fit)
+    }
+
+    /// 参数字符串中的标识
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FitMode::Fit => "fit",
+            FitMode::Fill => "fill",
+            FitMode::Contain => "contain",
+            FitMode::Scale => "scale",
+        }
+    }
+}
+
+/// 占位图哈希类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceholderKind {
+    /// BlurHash
+    BlurHash,
+    /// ThumbHash（保留宽高比和透明度）
+    ThumbHash,
+}
+
+impl PlaceholderKind {
+    /// 解析参数值：blurhash、thumbhash
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "blurhash" => Some(PlaceholderKind::BlurHash),
+            "thumbhash" => Some(PlaceholderKind::ThumbHash),
+            _ => None,
+        }
+    }
+
+    /// 参数字符串中的标识
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlaceholderKind::BlurHash => "blurhash",
+            PlaceholderKind::ThumbHash => "thumbhash",
+        }
+    }
+}
+
+/// 内嵌ICC配置文件的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IccMode {
+    /// 将像素转换为sRGB并去除配置文件（默认）
+    #[default]
+    Srgb,
+    /// 不转换像素，在输出中保留原配置文件
+    Keep,
+    /// 不转换像素，直接去除配置文件
+    Strip,
+}
+
+impl IccMode {
+    /// 解析参数：srgb、keepicc、noicc
+    fn parse(param: &str) -> Option<Self> {
+        match param {
+            "srgb" => Some(IccMode::Srgb),
+            "keepicc" => Some(IccMode::Keep),
+            "noicc" => Some(IccMode::Strip),
+            _ => None,
+        }
+    }
+
+    /// 参数字符串中的标识
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IccMode::Srgb => "srgb",
+            IccMode::Keep => "keepicc",
+            IccMode::Strip => "noicc",
+        }
+    }
+}
+
+/// JPEG色度抽样方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// 不抽样，色彩最准确
+    Yuv444,
+    /// 水平方向减半
+    Yuv422,
+    /// 水平和垂直方向都减半，体积最小
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    /// 解析参数值：444、422、420
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "444" => Some(ChromaSubsampling::Yuv444),
+            "422" => Some(ChromaSubsampling::Yuv422),
+            "420" => Some(ChromaSubsampling::Yuv420),
+            _ => None,
+        }
+    }
+
+    /// 参数字符串中的标识
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChromaSubsampling::Yuv444 => "444",
+            ChromaSubsampling::Yuv422 => "422",
+            ChromaSubsampling::Yuv420 => "420",
+        }
+    }
+}
+
+/// 裁剪/填充对齐方式
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Gravity {
+    #[default]
+    Center,
+    North,
+    South,
+    East,
+    West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+    /// 焦点坐标（相对位置，0.0-1.0）
+    FocalPoint(f32, f32),
+    /// 根据图片内容自动选择裁剪窗口
+    Smart,
+}
+
+impl Gravity {
+    /// 解析对齐方式（不含 g 前缀），如 "n"、"se"、"0.3x0.7"
+    fn parse(value: &str) -> Option<Self> {
+        let gravity = match value.to_lowercase().as_str() {
+            "c" | "center" => Gravity::Center,
+            "n" | "north" => Gravity::North,
+            "s" | "south" => Gravity::South,
+            "e" | "east" => Gravity::East,
+            "w" | "west" => Gravity::West,
+            "ne" => Gravity::NorthEast,
+            "nw" => Gravity::NorthWest,
+            "se" => Gravity::SouthEast,
+            "sw" => Gravity::SouthWest,
+            "smart" => Gravity::Smart,
+            focal => {
+                let (x, y) = focal.split_once('x')?;
+                let x = x.parse::<f32>().ok()?;
+                let y = y.parse::<f32>().ok()?;
+                if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+                    return None;
+                }
+                Gravity::FocalPoint(x, y)
+            }
+        };
+        Some(gravity)
+    }
+
+    /// 相对位置（0.0为左/上，1.0为右/下）
+    pub fn anchor(&self) -> (f32, f32) {
+        match self {
+            // 智能裁剪在转换阶段解析为焦点，其余场景按居中处理
+            Gravity::Center | Gravity::Smart => (0.5, 0.5),
+            Gravity::North => (0.5, 0.0),
+            Gravity::South => (0.5, 1.0),
+            Gravity::East => (1.0, 0.5),
+            Gravity::West => (0.0, 0.5),
+            Gravity::NorthEast => (1.0, 0.0),
+            Gravity::NorthWest => (0.0, 0.0),
+            Gravity::SouthEast => (1.0, 1.0),
+            Gravity::SouthWest => (0.0, 1.0),
+            Gravity::FocalPoint(x, y) => (*x, *y),
+        }
+    }
+
+    /// 生成标准化的参数字符串
+    fn to_normalized_string(self) -> String {
+        match self {
+            Gravity::Center => "gc".to_string(),
+            Gravity::North => "gn".to_string(),
+            Gravity::South => "gs".to_string(),
+            Gravity::East => "ge".to_string(),
+            Gravity::West => "gw".to_string(),
+            Gravity::NorthEast => "gne".to_string(),
+            Gravity::NorthWest => "gnw".to_string(),
+            Gravity::SouthEast => "gse".to_string(),
+            Gravity::SouthWest => "gsw".to_string(),
+            Gravity::FocalPoint(x, y) => format!("g{:.3}x{:.3}", x, y),
+            Gravity::Smart => "gsmart".to_string(),
+        }
+    }
+}
+
+/// 客户端提示（Client Hints）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientHints {
+    /// 设备像素比（Sec-CH-DPR / DPR）
+    pub dpr: Option<f32>,
+    /// 图片布局宽度，物理像素（Sec-CH-Width / Width）
+    pub width: Option<u32>,
+    /// 视口宽度，CSS像素（Sec-CH-Viewport-Width / Viewport-Width）
+    pub viewport_width: Option<u32>,
+}
+
+/// 动图帧选择
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSelection {
+    /// 指定序号的帧（从0开始）
+    Index(u32),
+    /// 指定时间点（毫秒）正在播放的帧
+    Time(u32),
+    /// 均匀抽取若干帧拼接为帧序列图
+    Sprite {
+        /// 抽取帧数（不指定时使用全部帧）
+        count: Option<u32>,
+        /// 每行帧数（不指定时排成一行）
+        columns: Option<u32>,
+    },
+}
+
+impl FrameSelection {
+    /// 解析帧选择参数：frame{N}、at{毫秒} / at{秒}s、frames[{N}[x{列数}]]
+    fn parse(param: &str) -> Option<Self> {
+        if let Some(sprite) = param.strip_prefix("frames") {
+            if sprite.is_empty() {
+                return Some(FrameSelection::Sprite {
+                    count: None,
+                    columns: None,
+                });
+            }
+            let (count, columns) = match sprite.split_once('x') {
+                Some((count, columns)) => (count, Some(columns.parse::<u32>().ok()?)),
+                None => (sprite, None),
+            };
+            return Some(FrameSelection::Sprite {
+                count: Some(count.parse::<u32>().ok()?),
+                columns,
+            });
+        }
+
+        if let Some(index) = param.strip_prefix("frame") {
+            return index.parse::<u32>().ok().map(FrameSelection::Index);
+        }
+
+        let time = param.strip_prefix("at")?;
+        let millis = match time.strip_suffix('s') {
+            Some(seconds) => {
+                let seconds = seconds.parse::<f64>().ok()?;
+                if !seconds.is_finite() || seconds < 0.0 {
+                    return None;
+                }
+                (seconds * 1000.0).round().min(u32::MAX as f64) as u32
+            }
+            None => time.parse::<u32>().ok()?,
+        };
+        Some(FrameSelection::Time(millis))
+    }
+
+    /// 生成标准化的参数字符串
+    fn to_normalized_string(self) -> String {
+        match self {
+            FrameSelection::Index(index) => format!("frame{}", index),
+            FrameSelection::Time(millis) => format!("at{}", millis),
+            FrameSelection::Sprite { count, columns } => match (count, columns) {
+                (None, _) => "frames".to_string(),
+                (Some(count), None) => format!("frames{}", count),
+                (Some(count), Some(columns)) => format!("frames{}x{}", count, columns),
+            },
+        }
+    }
+}
+
+/// 裁剪区域（像素）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropRegion {
+    /// 解析裁剪参数：crop{x}x{y}x{宽}x{高}
+    fn parse(param: &str) -> Option<Self> {
+        let values = param
+            .strip_prefix("crop")?
+            .split('x')
+            .map(|value| value.parse::<u32>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        match values.as_slice() {
+            [x, y, width, height] => Some(CropRegion {
+                x: *x,
+                y: *y,
+                width: *width,
+                height: *height,
+            }),
+            _ => None,
+        }
+    }
+
+    /// 生成标准化的参数字符串
+    fn to_normalized_string(self) -> String {
+        format!("crop{}x{}x{}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// 处理流水线中的单个操作
+///
+/// 每个阶段内的操作按固定顺序排列：旋转翻转、裁剪、缩放、滤镜、水印、蒙版边框留白；
+/// 多个阶段按URL中的顺序依次执行
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransformOperation {
+    /// 顺时针旋转后翻转
+    Orient {
+        rotation: Option<f32>,
+        flip_horizontal: bool,
+        flip_vertical: bool,
+    },
+    /// 按像素区域裁剪
+    Crop(CropRegion),
+    /// 按适配模式缩放
+    Resize {
+        width: Option<u32>,
+        height: Option<u32>,
+        fit_mode: FitMode,
+        gravity: Gravity,
+    },
+    /// 像素滤镜
+    Filters(ImageFilters),
+    /// 水印
+    Watermark(WatermarkParams),
+    /// 蒙版、边框和留白
+    Decorate {
+        mask: Option<MaskShape>,
+        border: Option<u32>,
+        border_color: Option<(u8, u8, u8)>,
+        padding: Option<u32>,
+    },
+}
+
+impl TransformOperation {
+    /// 操作在单个阶段内的执行次序
+    fn order(&self) -> u8 {
+        match self {
+            TransformOperation::Orient { .. } => 0,
+            TransformOperation::Crop(_) => 1,
+            TransformOperation::Resize { .. } => 2,
+            TransformOperation::Filters(_) => 3,
+            TransformOperation::Watermark(_) => 4,
+            TransformOperation::Decorate { .. } => 5,
+        }
+    }
+
+    /// 生成标准化的参数列表
+    fn normalized_parts(&self) -> Vec<String> {
+        let mut parts = Vec::new();
+        match self {
+            TransformOperation::Orient {
+                rotation,
+                flip_horizontal,
+                flip_vertical,
+            } => {
+                if let Some(rotation) = rotation {
+                    parts.push(format!("r{}", rotation));
+                }
+                if *flip_horizontal {
+                    parts.push("fliph".to_string());
+                }
+                if *flip_vertical {
+                    parts.push("flipv".to_string());
+                }
+            }
+            TransformOperation::Crop(region) => parts.push(region.to_normalized_string()),
+            TransformOperation::Resize {
+                width,
+                height,
+                fit_mode,
+                gravity,
+            } => {
+                if let Some(width) = width {
+                    parts.push(format!("w{}", width));
+                }
+                if let Some(height) = height {
+                    parts.push(format!("h{}", height));
+                }
+                if *fit_mode != FitMode::Fit {
+                    parts.push(fit_mode.as_str().to_string());
+                }
+                if *gravity != Gravity::Center {
+                    parts.push(gravity.to_normalized_string());
+                }
+            }
+            TransformOperation::Filters(filters) => parts.extend(filters.normalized_parts()),
+            TransformOperation::Watermark(watermark) => {
+                parts.push(watermark.to_normalized_string())
+            }
+            TransformOperation::Decorate {
+                mask,
+                border,
+                border_color,
+                padding,
+            } => {
+                if let Some(mask) = mask {
+                    parts.push(mask.to_normalized_string());
+                }
+                if let Some(border) = border {
+                    parts.push(format!("bd{}", border));
+                }
+                if let Some((r, g, b)) = border_color {
+                    parts.push(format!("bdc{:02x}{:02x}{:02x}", r, g, b));
+                }
+                if let Some(padding) = padding {
+                    parts.push(format!("pad{}", padding));
+                }
+            }
+        }
+        parts
+    }
+
+    /// 估算操作后的图片尺寸
+    pub fn output_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        match *self {
+            // 旋转90度或270度时宽高互换
+            TransformOperation::Orient {
+                rotation: Some(angle),
+                ..
+            } if angle == 90.0 || angle == 270.0 => (height, width),
+            TransformOperation::Orient {
+                rotation: Some(angle),
+                ..
+            } if angle != 180.0 => {
+                // 任意角度旋转扩展画布，与旋转实现的计算方式一致
+                let (sin, cos) = angle.to_radians().sin_cos();
+                (
+                    (width as f32 * cos.abs() + height as f32 * sin.abs()).ceil() as u32,
+                    (width as f32 * sin.abs() + height as f32 * cos.abs()).ceil() as u32,
+                )
+            }
+            TransformOperation::Crop(region) => (
+                region.width.min(width.saturating_sub(region.x)).max(1),
+                region.height.min(height.saturating_sub(region.y)).max(1),
+            ),
+            TransformOperation::Resize {
+                width: Some(target_width),
+                height: Some(target_height),
+                fit_mode,
+                ..
+            } if fit_mode != FitMode::Fit => (target_width, target_height),
+            TransformOperation::Resize {
+                width: target_width,
+                height: target_height,
+                ..
+            } => {
+                let scale_x = target_width.map_or(f64::INFINITY, |w| w as f64 / width as f64);
+                let scale_y = target_height.map_or(f64::INFINITY, |h| h as f64 / height as f64);
+                let scale = scale_x.min(scale_y).min(1.0);
+                (
+                    ((width as f64 * scale).round() as u32).max(1),
+                    ((height as f64 * scale).round() as u32).max(1),
+                )
+            }
+            // 边框和留白向外扩展画布
+            TransformOperation::Decorate {
+                border, padding, ..
+            } => {
+                let extra = (border.unwrap_or(0) + padding.unwrap_or(0)) * 2;
+                (width.saturating_add(extra), height.saturating_add(extra))
+            }
+            _ => (width, height),
+        }
+    }
+}
+
+/// 蒙版形状
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskShape {
+    /// 圆角，半径为像素数
+    Rounded(u32),
+    /// 内切圆（非正方形图片为内切椭圆）
+    Circle,
+}
+
+impl MaskShape {
+    /// 解析蒙版参数：rc{半径}、circle
+    fn parse(param: &str) -> Option<Self> {
+        if param == "circle" {
+            return Some(MaskShape::Circle);
+        }
+        param
+            .strip_prefix("rc")?
+            .parse::<u32>()
+            .ok()
+            .map(MaskShape::Rounded)
+    }
+
+    /// 生成标准化的参数字符串
+    fn to_normalized_string(self) -> String {
+        match self {
+            MaskShape::Rounded(radius) => format!("rc{}", radius),
+            MaskShape::Circle => "circle".to_string(),
+        }
+    }
+}
+
 /// 背景色选项
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BackgroundColor {
     White,
     Black,
@@ -146,39 +1316,167 @@ pub enum BackgroundColor {
 impl ImageTransformParams {
     /// 从URL参数字符串解析转换参数
     /// 格式: w1200_h1200_jpeg_naw_q80
+    ///
+    /// 以 ~ 分隔多个处理阶段（如 crop0x0x800x600~w400~blur4），阶段内的操作按固定顺序执行，
+    /// 阶段之间按书写顺序执行；格式、质量等输出参数可写在任一阶段，对整体生效
     pub fn parse(params_str: &str) -> Result<Self, String> {
         // 如果参数字符串为空，返回空的转换参数
         if params_str.trim().is_empty() {
-            return Ok(Self {
-                width: None,
-                height: None,
-                format: None,
-                quality: None,
-                no_alpha: false,
-                background_color: None,
-                base64_mode: Base64OutputMode::None,
-            });
+            return Ok(Self::default());
         }
 
-        let mut params = ImageTransformParams {
-            width: None,
-            height: None,
-            format: None,
-            quality: None,
-            no_alpha: false,
-            background_color: None,
-            base64_mode: Base64OutputMode::None,
-        };
+        if params_str.split(STAGE_SEPARATOR).count() > MAX_STAGES {
+            return Err(format!("处理阶段不能超过{}个", MAX_STAGES));
+        }
+
+        let mut params = ImageTransformParams::default();
+        let mut stages = params_str.split(STAGE_SEPARATOR);
+        Self::parse_stage(&mut params, stages.next().unwrap_or_default(), false)?;
+
+        // 后续阶段的操作移入流水线，第一阶段保留在各字段中
+        let mut first_stage = params.take_stage();
+        for stage in stages {
+            Self::parse_stage(&mut params, stage, true)?;
+            let stage = params.take_stage();
+            params.chained.extend(stage.stage_operations());
+        }
+        params.swap_stage(&mut first_stage);
+
+        Ok(params)
+    }
 
-        for param in params_str.split('_') {
+    /// 将查询字符串参数合并到第一个处理阶段末尾
+    ///
+    /// 查询字符串中的尺寸、格式等参数与路径中第一阶段的参数作用相同，并覆盖其中的同类参数
+    pub fn merge_into_first_stage(params_str: &str, extra: &str) -> String {
+        match params_str.split_once(STAGE_SEPARATOR) {
+            Some((first, rest)) => format!("{}_{}{}{}", first, extra, STAGE_SEPARATOR, rest),
+            None => format!("{}_{}", params_str, extra),
+        }
+    }
+
+    /// 检查单个参数是否为转换语法中的有效参数（用于避免预设名称遮蔽语法参数）
+    pub fn is_token(param: &str) -> bool {
+        Self::parse_stage(&mut Self::default(), param, true).is_ok()
+    }
+
+    /// 解析单个阶段的参数，写入对应字段
+    ///
+    /// 后续阶段（chained）不允许无效参数；后续阶段和包含裁剪的阶段要求操作参数按执行顺序书写，
+    /// 避免 w100_crop… 这类写法被静默地按固定顺序执行
+    fn parse_stage(params: &mut Self, stage_str: &str, chained: bool) -> Result<(), String> {
+        let ordered = chained
+            || stage_str
+                .split('_')
+                .any(|param| CropRegion::parse(param).is_some());
+        let mut last_order = 0;
+
+        for param in stage_str.split('_') {
             if param.is_empty() {
                 continue;
             }
+            let before = params.clone();
 
             // 优先检查是否为有效的图片格式
             if Self::is_valid_format(param) {
                 // 图片格式
                 params.format = Some(param.to_lowercase());
+            } else if param == AUTO_FORMAT {
+                // 根据Accept请求头自动选择格式
+                params.format = Some(AUTO_FORMAT.to_string());
+            } else if let Some(kind) = PlaceholderKind::parse(param) {
+                // 占位图：blurhash、thumbhash（需在滤镜之前匹配，避免被识别为blur）
+                params.placeholder = Some(kind);
+            } else if param == "svg" {
+                // 占位图输出为SVG
+                params.svg = true;
+            } else if params.filters.parse_token(param)? {
+                // 像素滤镜
+            } else if WatermarkParams::parse_token(&mut params.watermark, param) {
+                // 水印
+            } else if let Some(mask) = MaskShape::parse(param) {
+                // 蒙版：rc16、circle（需在旋转参数之前匹配）
+                params.mask = Some(mask);
+            } else if let Some(color) = param.strip_prefix("bdc") {
+                // 边框颜色：bdcff0000
+                params.border_color =
+                    Self::parse_hex_color(&format!("#{}", color.trim_start_matches('#'))).ok();
+            } else if let Some(width_str) = param.strip_prefix("bd") {
+                // 边框宽度
+                if let Ok(width) = width_str.parse::<u32>() {
+                    params.border = Some(width);
+                }
+            } else if let Some(padding_str) = param.strip_prefix("pad") {
+                // 四周留白
+                if let Ok(padding) = padding_str.parse::<u32>() {
+                    params.padding = Some(padding);
+                }
+            } else if let Some(crop) = CropRegion::parse(param) {
+                // 裁剪区域：crop10x20x300x200
+                params.crop = Some(crop);
+            } else if param == "fliph" {
+                // 水平翻转
+                params.flip_horizontal = true;
+            } else if param == "flipv" {
+                // 垂直翻转
+                params.flip_vertical = true;
+            } else if param == "lossless" {
+                // 无损压缩
+                params.lossless = true;
+            } else if param == "prog" {
+                // 渐进式JPEG
+                params.progressive = true;
+            } else if param == "huffman" {
+                // 优化霍夫曼表
+                params.optimize_huffman = true;
+            } else if let Some(icc_mode) = IccMode::parse(param) {
+                // ICC配置文件处理：srgb、keepicc、noicc
+                params.icc_mode = Some(icc_mode);
+            } else if let Some(chroma) = param
+                .strip_prefix("chroma")
+                .and_then(ChromaSubsampling::parse)
+            {
+                // 色度抽样：chroma444、chroma422、chroma420
+                params.chroma_subsampling = Some(chroma);
+            } else if let Some(kb_str) = param.strip_prefix("kb") {
+                // 目标体积：kb100 表示不超过100KB
+                if let Ok(kb) = kb_str.parse::<u64>() {
+                    let max_bytes = kb
+                        .checked_mul(1024)
+                        .ok_or_else(|| format!("目标体积超出范围: {}", param))?;
+                    params.max_bytes = Some(max_bytes);
+                }
+            } else if param == "nodither" {
+                // 关闭调色板抖动
+                params.no_dither = true;
+            } else if let Some(colors_str) = param.strip_prefix("pal") {
+                // 调色板量化：pal（256色）、pal64
+                if colors_str.is_empty() {
+                    params.palette = Some(256);
+                } else if let Ok(colors) = colors_str.parse::<u16>() {
+                    params.palette = Some(colors);
+                }
+            } else if let Some(dpr_str) = param.strip_prefix("dpr") {
+                // 设备像素比：dpr2、dpr1.5
+                if let Ok(dpr) = dpr_str.parse::<f32>() {
+                    if dpr.is_finite() && dpr > 0.0 {
+                        params.dpr = Some(dpr);
+                    }
+                }
+            } else if let Some(speed_str) = param.strip_prefix("speed") {
+                // 编码速度
+                if let Ok(speed) = speed_str.parse::<u8>() {
+                    params.speed = Some(speed);
+                }
+            } else if let Some(frame) = FrameSelection::parse(param) {
+                // 动图帧选择：frame3、at1.5s、frames12x4
+                params.frame = Some(frame);
+            } else if let Some(fit_mode) = Self::parse_fit_mode(param) {
+                // 尺寸适配模式
+                params.fit_mode = fit_mode;
+            } else if let Some(gravity) = param.strip_prefix('g').and_then(Gravity::parse) {
+                // 对齐方式：gn/gse/g0.3x0.7
+                params.gravity = gravity;
             } else if let Some(bg_part) = param.strip_prefix("na") {
                 // 去除透明通道及背景色设置
                 params.no_alpha = true;
@@ -200,6 +1498,14 @@ impl ImageTransformParams {
                     // 只有na，使用默认白色背景
                     params.background_color = Some(BackgroundColor::White);
                 }
+            } else if let Some(angle_str) = param.strip_prefix('r') {
+                // 顺时针旋转角度，支持小数，负数表示逆时针
+                if let Ok(angle) = angle_str.parse::<f32>() {
+                    if angle.is_finite() {
+                        let angle = angle.rem_euclid(360.0);
+                        params.rotation = (angle != 0.0).then_some(angle);
+                    }
+                }
             } else if let Some(width_str) = param.strip_prefix('w') {
                 // 宽度参数
                 if let Ok(width) = width_str.parse::<u32>() {
@@ -224,9 +1530,160 @@ impl ImageTransformParams {
                 // base64纯文本输出参数
                 params.base64_mode = Base64OutputMode::Raw;
             }
+
+            if chained && *params == before {
+                return Err(format!("无效或重复的参数: {}", param));
+            }
+            if ordered {
+                let previous = before.stage_operations();
+                let order = params
+                    .stage_operations()
+                    .iter()
+                    .filter(|operation| !previous.contains(operation))
+                    .map(TransformOperation::order)
+                    .max();
+                if let Some(order) = order {
+                    if order < last_order {
+                        return Err(format!(
+                            "参数{}与同一阶段中之前参数的执行顺序不符，请按执行顺序书写或使用~分隔阶段",
+                            param
+                        ));
+                    }
+                    last_order = order;
+                }
+            }
         }
 
-        Ok(params)
+        Ok(())
+    }
+
+    /// 与另一组参数交换单个阶段的操作字段（几何变换、滤镜、水印、蒙版等）
+    fn swap_stage(&mut self, other: &mut Self) {
+        std::mem::swap(&mut self.width, &mut other.width);
+        std::mem::swap(&mut self.height, &mut other.height);
+        std::mem::swap(&mut self.fit_mode, &mut other.fit_mode);
+        std::mem::swap(&mut self.gravity, &mut other.gravity);
+        std::mem::swap(&mut self.rotation, &mut other.rotation);
+        std::mem::swap(&mut self.flip_horizontal, &mut other.flip_horizontal);
+        std::mem::swap(&mut self.flip_vertical, &mut other.flip_vertical);
+        std::mem::swap(&mut self.crop, &mut other.crop);
+        std::mem::swap(&mut self.filters, &mut other.filters);
+        std::mem::swap(&mut self.watermark, &mut other.watermark);
+        std::mem::swap(&mut self.mask, &mut other.mask);
+        std::mem::swap(&mut self.border, &mut other.border);
+        std::mem::swap(&mut self.border_color, &mut other.border_color);
+        std::mem::swap(&mut self.padding, &mut other.padding);
+    }
+
+    /// 取出单个阶段的操作字段，原字段恢复为默认值
+    fn take_stage(&mut self) -> Self {
+        let mut stage = Self::default();
+        self.swap_stage(&mut stage);
+        stage
+    }
+
+    /// 按固定顺序生成第一阶段的操作
+    fn stage_operations(&self) -> Vec<TransformOperation> {
+        let mut operations = Vec::new();
+
+        if self.rotation.is_some() || self.flip_horizontal || self.flip_vertical {
+            operations.push(TransformOperation::Orient {
+                rotation: self.rotation,
+                flip_horizontal: self.flip_horizontal,
+                flip_vertical: self.flip_vertical,
+            });
+        }
+        if let Some(crop) = self.crop {
+            operations.push(TransformOperation::Crop(crop));
+        }
+        // 精确尺寸模式缺少宽高时同样生成缩放操作，由校验报错
+        if self.width.is_some() || self.height.is_some() || self.fit_mode.is_exact() {
+            operations.push(TransformOperation::Resize {
+                width: self.width,
+                height: self.height,
+                fit_mode: self.fit_mode,
+                gravity: self.gravity,
+            });
+        }
+        if !self.filters.is_empty() {
+            operations.push(TransformOperation::Filters(self.filters.clone()));
+        }
+        if let Some(ref watermark) = self.watermark {
+            operations.push(TransformOperation::Watermark(watermark.clone()));
+        }
+        if self.mask.is_some()
+            || self.border.is_some()
+            || self.border_color.is_some()
+            || self.padding.is_some()
+        {
+            operations.push(TransformOperation::Decorate {
+                mask: self.mask,
+                border: self.border,
+                border_color: self.border_color,
+                padding: self.padding,
+            });
+        }
+
+        operations
+    }
+
+    /// 按执行顺序排列的完整操作列表
+    pub fn operations(&self) -> Vec<TransformOperation> {
+        let mut operations = self.stage_operations();
+        operations.extend(self.chained.iter().cloned());
+        operations
+    }
+
+    /// 流水线中的水印参数
+    pub fn watermark_params_mut(&mut self) -> Option<&mut WatermarkParams> {
+        if self.watermark.is_some() {
+            return self.watermark.as_mut();
+        }
+        self.chained
+            .iter_mut()
+            .find_map(|operation| match operation {
+                TransformOperation::Watermark(watermark) => Some(watermark),
+                _ => None,
+            })
+    }
+
+    /// 在所有操作之前裁剪，原有的第一阶段操作移入后续阶段
+    pub fn prepend_crop(&mut self, crop: CropRegion) {
+        let mut operations = self.take_stage().stage_operations();
+        operations.append(&mut self.chained);
+        self.chained = operations;
+        self.crop = Some(crop);
+    }
+
+    /// 包含裁剪的阶段按执行顺序输出操作参数，保证标准化结果可以重新解析
+    fn to_ordered_string(&self) -> String {
+        let mut rest = self.clone();
+        let operations = rest.take_stage().stage_operations();
+        let parts: Vec<String> = operations
+            .iter()
+            .flat_map(TransformOperation::normalized_parts)
+            .collect();
+
+        let rest = rest.to_normalized_string();
+        if rest.is_empty() || rest.starts_with(STAGE_SEPARATOR) {
+            format!("{}{}", parts.join("_"), rest)
+        } else {
+            format!("{}_{}", parts.join("_"), rest)
+        }
+    }
+
+    /// 使用强制水印替换URL中的水印参数
+    ///
+    /// 存在后续阶段时水印作为最后一个操作叠加，避免被之后的裁剪或滤镜去除
+    pub fn force_watermark(&mut self, watermark: WatermarkParams) {
+        self.chained
+            .retain(|operation| !matches!(operation, TransformOperation::Watermark(_)));
+        if self.chained.is_empty() {
+            self.watermark = Some(watermark);
+        } else {
+            self.watermark = None;
+            self.chained.push(TransformOperation::Watermark(watermark));
+        }
     }
 
     /// 检查是否为有效的图片格式
@@ -237,19 +1694,103 @@ impl ImageTransformParams {
         )
     }
 
+    /// 解析尺寸适配模式
+    fn parse_fit_mode(param: &str) -> Option<FitMode> {
+        match param.to_lowercase().as_str() {
+            "fit" => Some(FitMode::Fit),
+            "fill" | "cover" => Some(FitMode::Fill),
+            "contain" => Some(FitMode::Contain),
+            "scale" => Some(FitMode::Scale),
+            _ => None,
+        }
+    }
+
     /// 检查是否需要进行转换
     /// base64参数不影响是否需要转换，它只控制输出格式
     pub fn needs_transform(&self) -> bool {
         self.width.is_some()
             || self.height.is_some()
+            || self.rotation.is_some()
+            || self.flip_horizontal
+            || self.flip_vertical
+            || self.crop.is_some()
+            || !self.filters.is_empty()
+            || self.watermark.is_some()
+            || self.mask.is_some()
+            || self.border.is_some()
+            || self.border_color.is_some()
+            || self.padding.is_some()
+            || self.frame.is_some()
             || self.format.is_some()
             || self.quality.is_some()
+            || self.lossless
+            || self.speed.is_some()
+            || self.progressive
+            || self.chroma_subsampling.is_some()
+            || self.optimize_huffman
+            || self.palette.is_some()
+            || self.max_bytes.is_some()
+            || self.icc_mode.is_some()
             || self.no_alpha
+            || self.placeholder.is_some()
+            || self.svg
+            || !self.chained.is_empty()
+    }
+
+    /// 蒙版或留白是否会产生透明区域
+    pub fn produces_transparency(&self) -> bool {
+        self.operations().iter().any(|operation| {
+            matches!(
+                operation,
+                TransformOperation::Decorate { mask, padding, .. }
+                    if mask.is_some() || padding.is_some()
+            )
+        })
+    }
+
+    /// 是否需要根据Accept请求头协商输出格式
+    pub fn is_auto_format(&self) -> bool {
+        self.format.as_deref() == Some(AUTO_FORMAT)
+    }
+
+    /// 按设备像素比换算宽高
+    ///
+    /// URL中的dpr优先于客户端提示；URL未指定宽高时使用客户端提示的宽度（已是物理像素）
+    /// 或视口宽度。换算后清除dpr，使等效的请求共用同一个缓存
+    pub fn apply_device_pixel_ratio(&mut self, hints: &ClientHints, max_dpr: f32) {
+        let dpr = self.dpr.take().or(hints.dpr).map(|dpr| dpr.min(max_dpr));
+        let scale =
+            |value: u32, dpr: f32| ((value as f32 * dpr).round() as u32).clamp(1, MAX_DIMENSION);
+
+        if self.width.is_none() && self.height.is_none() {
+            if let Some(width) = hints.width {
+                self.width = Some(width.clamp(1, MAX_DIMENSION));
+            } else if let Some(viewport_width) = hints.viewport_width {
+                self.width = Some(scale(viewport_width, dpr.unwrap_or(1.0)));
+            }
+            return;
+        }
+
+        if let Some(dpr) = dpr {
+            self.width = self.width.map(|width| scale(width, dpr));
+            self.height = self.height.map(|height| scale(height, dpr));
+            // 后续阶段的缩放同样按像素比放大
+            for operation in &mut self.chained {
+                if let TransformOperation::Resize { width, height, .. } = operation {
+                    *width = width.map(|width| scale(width, dpr));
+                    *height = height.map(|height| scale(height, dpr));
+                }
+            }
+        }
     }
 
     /// 生成标准化的参数字符串（用于缓存键生成）
     /// 按固定顺序排列参数，确保相同功能的转换生成相同的缓存键
     pub fn to_normalized_string(&self) -> String {
+        if self.crop.is_some() {
+            return self.to_ordered_string();
+        }
+
         let mut parts = Vec::new();
 
         // 按固定顺序添加参数
@@ -261,6 +1802,64 @@ impl ImageTransformParams {
             parts.push(format!("h{}", height));
         }
 
+        if let Some(dpr) = self.dpr {
+            parts.push(format!("dpr{}", dpr));
+        }
+
+        if self.fit_mode != FitMode::Fit {
+            parts.push(self.fit_mode.as_str().to_string());
+        }
+
+        if self.gravity != Gravity::Center {
+            parts.push(self.gravity.to_normalized_string());
+        }
+
+        if let Some(rotation) = self.rotation {
+            parts.push(format!("r{}", rotation));
+        }
+
+        if self.flip_horizontal {
+            parts.push("fliph".to_string());
+        }
+
+        if self.flip_vertical {
+            parts.push("flipv".to_string());
+        }
+
+        if let Some(frame) = self.frame {
+            parts.push(frame.to_normalized_string());
+        }
+
+        if let Some(kind) = self.placeholder {
+            parts.push(kind.as_str().to_string());
+        }
+
+        if self.svg {
+            parts.push("svg".to_string());
+        }
+
+        parts.extend(self.filters.normalized_parts());
+
+        if let Some(ref watermark) = self.watermark {
+            parts.push(watermark.to_normalized_string());
+        }
+
+        if let Some(mask) = self.mask {
+            parts.push(mask.to_normalized_string());
+        }
+
+        if let Some(border) = self.border {
+            parts.push(format!("bd{}", border));
+        }
+
+        if let Some((r, g, b)) = self.border_color {
+            parts.push(format!("bdc{:02x}{:02x}{:02x}", r, g, b));
+        }
+
+        if let Some(padding) = self.padding {
+            parts.push(format!("pad{}", padding));
+        }
+
         if let Some(ref format) = self.format {
             parts.push(format.clone());
         }
@@ -269,6 +1868,42 @@ impl ImageTransformParams {
             parts.push(format!("q{}", quality));
         }
 
+        if self.lossless {
+            parts.push("lossless".to_string());
+        }
+
+        if let Some(speed) = self.speed {
+            parts.push(format!("speed{}", speed));
+        }
+
+        if self.progressive {
+            parts.push("prog".to_string());
+        }
+
+        if let Some(chroma) = self.chroma_subsampling {
+            parts.push(format!("chroma{}", chroma.as_str()));
+        }
+
+        if self.optimize_huffman {
+            parts.push("huffman".to_string());
+        }
+
+        if let Some(colors) = self.palette {
+            parts.push(format!("pal{}", colors));
+        }
+
+        if self.no_dither {
+            parts.push("nodither".to_string());
+        }
+
+        if let Some(max_bytes) = self.max_bytes {
+            parts.push(format!("kb{}", max_bytes / 1024));
+        }
+
+        if let Some(icc_mode) = self.icc_mode {
+            parts.push(icc_mode.as_str().to_string());
+        }
+
         if self.no_alpha {
             match &self.background_color {
                 Some(BackgroundColor::White) => parts.push("naw".to_string()),
@@ -286,7 +1921,13 @@ impl ImageTransformParams {
             Base64OutputMode::None => {}
         }
 
-        parts.join("_")
+        // 后续阶段的每个操作单独成为一个阶段，与原写法的执行结果相同
+        let mut normalized = parts.join("_");
+        for operation in &self.chained {
+            normalized.push(STAGE_SEPARATOR);
+            normalized.push_str(&operation.normalized_parts().join("_"));
+        }
+        normalized
     }
 
     /// 获取目标MIME类型
@@ -312,7 +1953,7 @@ impl ImageTransformParams {
     }
 
     /// 解析十六进制颜色
-    fn parse_hex_color(hex: &str) -> Result<(u8, u8, u8), String> {
+    pub(crate) fn parse_hex_color(hex: &str) -> Result<(u8, u8, u8), String> {
         if hex.len() != 7 || !hex.starts_with('#') {
             return Err("十六进制颜色格式必须为#RRGGBB".to_string());
         }
@@ -348,6 +1989,8 @@ pub struct CacheInfo {
     pub access_count: i64,
     /// 缓存热度评分
     pub heat_score: f64,
+    /// 按目标体积编码时选定的质量
+    pub output_quality: Option<u8>,
 }
 
 /// 缓存统计信息
@@ -396,3 +2039,111 @@ pub struct Base64ImageResponse {
     /// 原图信息
     pub original: ImageInfo,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(params: &str) -> String {
+        ImageTransformParams::parse(params)
+            .unwrap_or_else(|e| panic!("{}: {}", params, e))
+            .to_normalized_string()
+    }
+
+    #[test]
+    fn normalized_string_round_trips() {
+        for (params, expected) in [
+            ("q80_jpeg_h600_w1200_naw", "w1200_h600_jpeg_q80_naw"),
+            ("w300_h300_fill_gsmart_webp", "w300_h300_fill_gsmart_webp"),
+            ("w400_dpr1.5_auto", "w400_dpr1.5_auto"),
+            (
+                "r90_fliph_crop10x20x300x200_w100",
+                "r90_fliph_crop10x20x300x200_w100",
+            ),
+            ("crop0x0x800x600~w400~blur4", "crop0x0x800x600~w400~blur4"),
+            (
+                "w800_br20_ct-15_sat30_hue90_gray_sharp1.5",
+                "w800_br20_ct-15_sat30_hue90_gray_sharp1.5",
+            ),
+            (
+                "wmtwqkgUklGUw_wmpnw_wmm24_wmo60_w600",
+                "w600_wmtwqkgUklGUw_wmpnw_wmm24_wmo60",
+            ),
+            (
+                "w200_h200_fill_circle_bd4_bdcffffff_pad20",
+                "w200_h200_fill_circle_bd4_bdcffffff_pad20",
+            ),
+            (
+                "jpeg_q80_prog_chroma444_huffman",
+                "jpeg_q80_prog_chroma444_huffman",
+            ),
+            ("png_pal64_nodither", "png_pal64_nodither"),
+            ("w160_frames12x4_jpeg", "w160_frames12x4_jpeg"),
+            ("avif_q60_speed4_kb100", "avif_q60_speed4_kb100"),
+            ("thumbhash_svg", "thumbhash_svg"),
+        ] {
+            let normalized = normalize(params);
+            assert_eq!(normalized, expected, "{}", params);
+            assert_eq!(
+                ImageTransformParams::parse(&normalized).unwrap(),
+                ImageTransformParams::parse(params).unwrap(),
+                "{}",
+                params
+            );
+            assert_eq!(normalize(&normalized), normalized);
+        }
+    }
+
+    #[test]
+    fn empty_params_parse_to_default() {
+        assert_eq!(
+            ImageTransformParams::parse("").unwrap(),
+            ImageTransformParams::default()
+        );
+        assert_eq!(normalize(""), "");
+    }
+
+    #[test]
+    fn too_many_stages_rejected() {
+        let params = ["w100"; MAX_STAGES + 1].join("~");
+        assert!(ImageTransformParams::parse(&params).is_err());
+        let params = ["w100"; MAX_STAGES].join("~");
+        assert!(ImageTransformParams::parse(&params).is_ok());
+    }
+
+    #[test]
+    fn malformed_filter_values_rejected_in_every_stage() {
+        for params in ["blur", "blurx", "sharpen-3", "brx", "ct1.5", "sat", "huex"] {
+            assert!(ImageTransformParams::parse(params).is_err(), "{}", params);
+            assert!(
+                ImageTransformParams::parse(&format!("w100~{}", params)).is_err(),
+                "{}",
+                params
+            );
+        }
+        assert_eq!(normalize("sharp_blur2"), "blur2_sharp1");
+        assert_eq!(normalize("hue360_br-10"), "br-10");
+    }
+
+    #[test]
+    fn query_params_merge_into_first_stage() {
+        let merged = ImageTransformParams::merge_into_first_stage("w100~blur5", "w50");
+        assert_eq!(merged, "w100_w50~blur5");
+        assert_eq!(normalize(&merged), "w50~blur5");
+        assert_eq!(
+            ImageTransformParams::merge_into_first_stage("crop0x0x10x10~w8~gray", "webp_q80"),
+            "crop0x0x10x10_webp_q80~w8~gray"
+        );
+        assert_eq!(
+            ImageTransformParams::merge_into_first_stage("w100", "h50"),
+            "w100_h50"
+        );
+    }
+
+    #[test]
+    fn chained_stage_rejects_unknown_and_out_of_order_tokens() {
+        assert!(ImageTransformParams::parse("w400~bogus").is_err());
+        assert!(ImageTransformParams::parse("w400~blur4_w100").is_err());
+        assert!(ImageTransformParams::parse("w100_crop0x0x10x10").is_err());
+    }
+}
@@ -24,6 +24,9 @@ pub trait CacheRepositoryTrait: Repository {
     /// 删除缓存记录
     async fn delete_by_key(&self, cache_key: &str) -> Result<bool, AppError>;
 
+    /// 根据原始hash获取相关缓存列表
+    async fn find_by_original_hash(&self, original_hash: &str) -> Result<Vec<CacheInfo>, AppError>;
+
     /// 根据原始hash删除相关缓存
     async fn delete_by_original_hash(&self, original_hash: &str) -> Result<u64, AppError>;
 
@@ -194,6 +197,19 @@ impl CacheRepositoryTrait for CacheRepository {
         Ok(deleted)
     }
 
+    async fn find_by_original_hash(&self, original_hash: &str) -> Result<Vec<CacheInfo>, AppError> {
+        debug!("根据原始hash查询相关缓存: {}", original_hash);
+
+        let connection = self.get_connection();
+        let models = Cache::find()
+            .filter(cache::Column::OriginalHash.eq(original_hash))
+            .all(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("查询相关缓存失败: {}", e)))?;
+
+        Ok(models.into_iter().map(|model| model.into()).collect())
+    }
+
     async fn delete_by_original_hash(&self, original_hash: &str) -> Result<u64, AppError> {
         debug!("根据原始hash删除相关缓存: {}", original_hash);
 
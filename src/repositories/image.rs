@@ -1,14 +1,14 @@
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait,
-    PaginatorTrait, QueryFilter, QueryOrder, Statement,
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Statement,
 };
 use std::sync::Arc;
 use tracing::{debug, info};
 
 use crate::entities::{image, Image};
-use crate::models::{ImageInfo, ImageQuery, ImageStats, TimeStat, TypeStat};
+use crate::models::{ImageInfo, ImageQuery, ImageStats, TimeStat, TypeStat, Visibility};
 use crate::repositories::{BaseRepository, PageResult, Repository};
 use crate::utils::AppError;
 
@@ -21,10 +21,84 @@ pub trait ImageRepositoryTrait: Repository {
     /// 根据hash获取图片信息
     async fn find_by_hash(&self, hash: &str) -> Result<Option<ImageInfo>, AppError>;
 
+    /// 根据短ID获取图片信息
+    async fn find_by_short_id(&self, short_id: &str) -> Result<Option<ImageInfo>, AppError>;
+
+    /// 根据别名获取图片信息
+    async fn find_by_alias(&self, alias: &str) -> Result<Option<ImageInfo>, AppError>;
+
+    /// 根据hash前缀查询图片（最多返回limit条）
+    async fn find_by_hash_prefix(
+        &self,
+        prefix: &str,
+        limit: u64,
+    ) -> Result<Vec<ImageInfo>, AppError>;
+
+    /// 设置图片短ID
+    async fn update_short_id(&self, hash: &str, short_id: &str) -> Result<bool, AppError>;
+
+    /// 设置图片别名
+    async fn update_alias(&self, hash: &str, alias: &str) -> Result<bool, AppError>;
+
+    /// 设置图片过期时间
+    async fn update_expires_at(
+        &self,
+        hash: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, AppError>;
+
+    /// 设置图片最大查看次数
+    async fn update_max_views(&self, hash: &str, max_views: Option<i64>) -> Result<bool, AppError>;
+
+    /// 更新图片可见性和访问密码哈希
+    async fn update_access_control(
+        &self,
+        hash: &str,
+        visibility: Visibility,
+        password_hash: Option<String>,
+    ) -> Result<bool, AppError>;
+
+    /// 设置图片占位图哈希
+    async fn update_placeholders(
+        &self,
+        hash: &str,
+        blurhash: &str,
+        thumbhash: &str,
+    ) -> Result<bool, AppError>;
+
+    /// 设置图片主色调和调色板（逗号分隔的 #rrggbb）
+    async fn update_colors(
+        &self,
+        hash: &str,
+        dominant: [u8; 3],
+        palette: &str,
+    ) -> Result<bool, AppError>;
+
+    /// 按hash顺序获取缺少占位图哈希或颜色信息的图片列表（hash大于after，最多返回limit条）
+    async fn find_missing_features(
+        &self,
+        after: Option<&str>,
+        placeholders: bool,
+        colors: bool,
+        limit: u64,
+    ) -> Result<Vec<ImageInfo>, AppError>;
+
+    /// 获取已过期或已达到查看上限的图片列表（最多返回limit条）
+    ///
+    /// 达到查看上限的图片需在最后一次访问grace_period之后才会返回，
+    /// 避免删除仍在读取中的文件
+    async fn find_purgeable(
+        &self,
+        grace_period: chrono::Duration,
+        limit: u64,
+    ) -> Result<Vec<ImageInfo>, AppError>;
+
     /// 分页查询图片列表
     async fn find_by_query(&self, query: &ImageQuery) -> Result<PageResult<ImageInfo>, AppError>;
 
     /// 更新图片访问信息
+    ///
+    /// 访问计数在数据库中原子递增，已达到最大查看次数时不更新并返回false
     async fn update_access(&self, hash: &str) -> Result<bool, AppError>;
 
     /// 删除图片记录
@@ -71,8 +145,31 @@ impl ImageRepository {
             condition = condition.add(image::Column::CreatedAt.lte(end_time));
         }
 
+        if !query.include_hidden {
+            condition = condition.add(image::Column::Visibility.eq(Visibility::Public.as_str()));
+        }
+
+        // 主色调各通道与目标颜色的差值都不超过容差
+        if let Ok(Some(filter)) = query.color_filter() {
+            let (red, green, blue) = filter.rgb;
+            for (column, value) in [
+                (image::Column::DominantRed, red),
+                (image::Column::DominantGreen, green),
+                (image::Column::DominantBlue, blue),
+            ] {
+                condition = condition.add(column.between(
+                    value.saturating_sub(filter.tolerance) as i16,
+                    value.saturating_add(filter.tolerance) as i16,
+                ));
+            }
+        }
+
         if let Some(search) = &query.search {
-            condition = condition.add(image::Column::Hash.contains(search));
+            condition = condition.add(
+                Condition::any()
+                    .add(image::Column::Hash.contains(search))
+                    .add(image::Column::Alias.contains(search)),
+            );
         }
 
         condition
@@ -176,6 +273,238 @@ impl ImageRepositoryTrait for ImageRepository {
         Ok(result.map(|model| model.into()))
     }
 
+    async fn find_by_short_id(&self, short_id: &str) -> Result<Option<ImageInfo>, AppError> {
+        debug!("根据短ID查询图片: {}", short_id);
+
+        let connection = self.get_connection();
+        let result = Image::find()
+            .filter(image::Column::ShortId.eq(short_id))
+            .one(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("查询图片失败: {}", e)))?;
+
+        Ok(result.map(|model| model.into()))
+    }
+
+    async fn find_by_alias(&self, alias: &str) -> Result<Option<ImageInfo>, AppError> {
+        debug!("根据别名查询图片: {}", alias);
+
+        let connection = self.get_connection();
+        let result = Image::find()
+            .filter(image::Column::Alias.eq(alias))
+            .one(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("查询图片失败: {}", e)))?;
+
+        Ok(result.map(|model| model.into()))
+    }
+
+    async fn find_by_hash_prefix(
+        &self,
+        prefix: &str,
+        limit: u64,
+    ) -> Result<Vec<ImageInfo>, AppError> {
+        debug!("根据hash前缀查询图片: {}", prefix);
+
+        let connection = self.get_connection();
+        let models = Image::find()
+            .filter(image::Column::Hash.starts_with(prefix))
+            .limit(limit)
+            .all(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("查询图片失败: {}", e)))?;
+
+        Ok(models.into_iter().map(|model| model.into()).collect())
+    }
+
+    async fn update_short_id(&self, hash: &str, short_id: &str) -> Result<bool, AppError> {
+        debug!("设置图片短ID: {} -> {}", hash, short_id);
+
+        let connection = self.get_connection();
+        let result = Image::update_many()
+            .col_expr(image::Column::ShortId, Expr::value(short_id))
+            .filter(image::Column::Hash.eq(hash))
+            .exec(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("更新图片短ID失败: {}", e)))?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn update_alias(&self, hash: &str, alias: &str) -> Result<bool, AppError> {
+        debug!("设置图片别名: {} -> {}", hash, alias);
+
+        let connection = self.get_connection();
+        let result = Image::update_many()
+            .col_expr(image::Column::Alias, Expr::value(alias))
+            .filter(image::Column::Hash.eq(hash))
+            .exec(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("更新图片别名失败: {}", e)))?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn update_expires_at(
+        &self,
+        hash: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, AppError> {
+        debug!("设置图片过期时间: {} -> {:?}", hash, expires_at);
+
+        let connection = self.get_connection();
+        let result = Image::update_many()
+            .col_expr(image::Column::ExpiresAt, Expr::value(expires_at))
+            .filter(image::Column::Hash.eq(hash))
+            .exec(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("更新图片过期时间失败: {}", e)))?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn update_max_views(&self, hash: &str, max_views: Option<i64>) -> Result<bool, AppError> {
+        debug!("设置图片最大查看次数: {} -> {:?}", hash, max_views);
+
+        let connection = self.get_connection();
+        let result = Image::update_many()
+            .col_expr(image::Column::MaxViews, Expr::value(max_views))
+            .filter(image::Column::Hash.eq(hash))
+            .exec(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("更新图片最大查看次数失败: {}", e)))?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn update_access_control(
+        &self,
+        hash: &str,
+        visibility: Visibility,
+        password_hash: Option<String>,
+    ) -> Result<bool, AppError> {
+        debug!("设置图片访问控制: {} -> {}", hash, visibility.as_str());
+
+        let connection = self.get_connection();
+        let result = Image::update_many()
+            .col_expr(image::Column::Visibility, Expr::value(visibility.as_str()))
+            .col_expr(image::Column::PasswordHash, Expr::value(password_hash))
+            .filter(image::Column::Hash.eq(hash))
+            .exec(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("更新图片访问控制失败: {}", e)))?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn update_placeholders(
+        &self,
+        hash: &str,
+        blurhash: &str,
+        thumbhash: &str,
+    ) -> Result<bool, AppError> {
+        debug!("设置图片占位图哈希: {}", hash);
+
+        let connection = self.get_connection();
+        let result = Image::update_many()
+            .col_expr(image::Column::Blurhash, Expr::value(blurhash))
+            .col_expr(image::Column::Thumbhash, Expr::value(thumbhash))
+            .filter(image::Column::Hash.eq(hash))
+            .exec(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("更新图片占位图哈希失败: {}", e)))?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn update_colors(
+        &self,
+        hash: &str,
+        dominant: [u8; 3],
+        palette: &str,
+    ) -> Result<bool, AppError> {
+        debug!("设置图片颜色信息: {} -> {:?}", hash, dominant);
+
+        let [red, green, blue] = dominant.map(|value| value as i16);
+        let connection = self.get_connection();
+        let result = Image::update_many()
+            .col_expr(image::Column::DominantRed, Expr::value(red))
+            .col_expr(image::Column::DominantGreen, Expr::value(green))
+            .col_expr(image::Column::DominantBlue, Expr::value(blue))
+            .col_expr(image::Column::Palette, Expr::value(palette))
+            .filter(image::Column::Hash.eq(hash))
+            .exec(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("更新图片颜色信息失败: {}", e)))?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn find_missing_features(
+        &self,
+        after: Option<&str>,
+        placeholders: bool,
+        colors: bool,
+        limit: u64,
+    ) -> Result<Vec<ImageInfo>, AppError> {
+        debug!("查询缺少特征的图片: after={:?}", after);
+
+        let mut missing = Condition::any();
+        if placeholders {
+            missing = missing
+                .add(image::Column::Blurhash.is_null())
+                .add(image::Column::Thumbhash.is_null());
+        }
+        if colors {
+            missing = missing.add(image::Column::DominantRed.is_null());
+        }
+
+        let mut condition = Condition::all().add(missing);
+        if let Some(after) = after {
+            condition = condition.add(image::Column::Hash.gt(after));
+        }
+
+        let connection = self.get_connection();
+        let models = Image::find()
+            .filter(condition)
+            .order_by_asc(image::Column::Hash)
+            .limit(limit)
+            .all(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("查询图片失败: {}", e)))?;
+
+        Ok(models.into_iter().map(|model| model.into()).collect())
+    }
+
+    async fn find_purgeable(
+        &self,
+        grace_period: chrono::Duration,
+        limit: u64,
+    ) -> Result<Vec<ImageInfo>, AppError> {
+        debug!("查询已过期或已达到查看上限的图片");
+
+        let now = Utc::now();
+        let connection = self.get_connection();
+        let models = Image::find()
+            .filter(
+                Condition::any().add(image::Column::ExpiresAt.lte(now)).add(
+                    Condition::all()
+                        .add(image::Column::MaxViews.is_not_null())
+                        .add(
+                            Expr::col(image::Column::AccessCount)
+                                .gte(Expr::col(image::Column::MaxViews)),
+                        )
+                        .add(image::Column::LastAccessed.lte(now - grace_period)),
+                ),
+            )
+            .limit(limit)
+            .all(&*connection)
+            .await
+            .map_err(|e| AppError::Internal(format!("查询过期图片失败: {}", e)))?;
+
+        Ok(models.into_iter().map(|model| model.into()).collect())
+    }
+
     async fn find_by_query(&self, query: &ImageQuery) -> Result<PageResult<ImageInfo>, AppError> {
         debug!("分页查询图片列表: {:?}", query);
 
@@ -214,27 +543,27 @@ impl ImageRepositoryTrait for ImageRepository {
         debug!("更新图片访问信息: {}", hash);
 
         let connection = self.get_connection();
-        let image_model = Image::find()
-            .filter(image::Column::Hash.eq(hash))
-            .one(&*connection)
-            .await
-            .map_err(|e| AppError::Internal(format!("查询图片失败: {}", e)))?;
-
-        if let Some(model) = image_model {
-            let mut active_model: image::ActiveModel = model.into();
-            active_model.access_count = sea_orm::Set(active_model.access_count.unwrap() + 1);
-            active_model.last_accessed = sea_orm::Set(Some(Utc::now()));
-
-            active_model
-                .update(&*connection)
+        let result =
+            Image::update_many()
+                .col_expr(
+                    image::Column::AccessCount,
+                    Expr::col(image::Column::AccessCount).add(1),
+                )
+                .col_expr(image::Column::LastAccessed, Expr::value(Some(Utc::now())))
+                .filter(image::Column::Hash.eq(hash))
+                .filter(Condition::any().add(image::Column::MaxViews.is_null()).add(
+                    Expr::col(image::Column::AccessCount).lt(Expr::col(image::Column::MaxViews)),
+                ))
+                .exec(&*connection)
                 .await
                 .map_err(|e| AppError::Internal(format!("更新访问信息失败: {}", e)))?;
 
+        let updated = result.rows_affected > 0;
+        if updated {
             debug!("图片访问信息更新成功: {}", hash);
-            Ok(true)
-        } else {
-            Ok(false)
         }
+
+        Ok(updated)
     }
 
     async fn delete_by_hash(&self, hash: &str) -> Result<bool, AppError> {
@@ -10,15 +10,16 @@ use tower_http::cors::{Any, CorsLayer};
 use crate::app_state::AppState;
 use crate::config::AppConfig;
 use crate::handlers::{
-    api_docs, auto_cleanup_cache, cache_management_dashboard, clear_all_cache, decay_heat_scores,
-    delete_image, get_cache_stats, get_image, get_image_info, get_stats, get_system_stats,
-    health_check_detailed, query_images_get, query_images_post, upload_image,
+    api_docs, auto_cleanup_cache, cache_management_dashboard, clear_all_cache, create_share_token,
+    decay_heat_scores, delete_image, get_cache_stats, get_image, get_image_info, get_srcset,
+    get_stats, get_system_stats, health_check_detailed, imgproxy_image, query_images_get,
+    query_images_post, thumbor_image, unlock_image, upload_image,
 };
 use crate::middleware::{log_requests, request_timeout};
 
 /// 创建应用路由
 pub fn create_routes(app_state: AppState, config: &AppConfig) -> Router {
-    let mut app = Router::new()
+    let mut router = Router::new()
         // API文档根路径
         .route("/", get(api_docs))
         // 健康检查
@@ -32,6 +33,12 @@ pub fn create_routes(app_state: AppState, config: &AppConfig) -> Router {
         .route("/images/{filename}", get(get_image))
         // 获取图片信息 - 返回JSON格式的图片元数据
         .route("/images/{filename}/info", get(get_image_info))
+        // 响应式图片清单 - 返回srcset和<picture>代码，可预生成变体
+        .route("/images/{filename}/srcset", get(get_srcset))
+        // 密码解锁 - 校验密码后签发访问令牌
+        .route("/images/{filename}/unlock", post(unlock_image))
+        // 签发分享令牌 - 需要API密钥
+        .route("/images/{filename}/token", post(create_share_token))
         // 查询图片列表 - 同时支持GET和POST
         .route(
             "/api/images/query",
@@ -46,7 +53,18 @@ pub fn create_routes(app_state: AppState, config: &AppConfig) -> Router {
         .route("/api/cache/cleanup/auto", post(auto_cleanup_cache))
         .route("/api/cache/decay", post(decay_heat_scores))
         .route("/api/cache/clear", delete(clear_all_cache))
-        .route("/cache/management", get(cache_management_dashboard))
+        .route("/cache/management", get(cache_management_dashboard));
+
+    // imgproxy/thumbor兼容路由（第一段为签名，不做校验）
+    let compat = &config.compat;
+    if !compat.imgproxy_prefix.is_empty() {
+        router = router.route(&compat_route(&compat.imgproxy_prefix), get(imgproxy_image));
+    }
+    if !compat.thumbor_prefix.is_empty() {
+        router = router.route(&compat_route(&compat.thumbor_prefix), get(thumbor_image));
+    }
+
+    let mut app = router
         // 注入应用状态
         .with_state(app_state.clone())
         // 添加文件大小限制中间件
@@ -73,3 +91,8 @@ pub fn create_routes(app_state: AppState, config: &AppConfig) -> Router {
 
     app
 }
+
+/// 兼容路由的路径模式：{前缀}/{签名}/{剩余路径}
+pub fn compat_route(prefix: &str) -> String {
+    format!("{}/{{signature}}/{{*path}}", prefix.trim_end_matches('/'))
+}
@@ -5,7 +5,7 @@ use tracing::{error, info};
 use crate::app_state::AppState;
 use crate::config::AppConfig;
 use crate::logging;
-use crate::routes::create_routes;
+use crate::routes::{compat_route, create_routes};
 use crate::services;
 use crate::utils;
 
@@ -56,6 +56,59 @@ pub fn start_cache_cleanup_task(app_state: AppState, config: &AppConfig) -> Opti
     }))
 }
 
+/// 启动过期图片清理任务
+pub fn start_expired_image_cleanup_task(app_state: AppState, config: &AppConfig) -> JoinHandle<()> {
+    let cleanup_interval = config.storage.expired_cleanup_interval.as_seconds().max(1);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(cleanup_interval));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    match services::ImageService::purge_expired_images(app_state.db_pool()).await {
+                        Ok(count) => {
+                            if count > 0 {
+                                info!("过期图片清理完成: 删除{}张图片", count);
+                            }
+                        }
+                        Err(e) => {
+                            error!("过期图片清理失败: {}", e);
+                        }
+                    }
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    break;
+                }
+            }
+        }
+    })
+}
+
+/// 启动图片特征（占位图哈希、颜色信息）补算任务（启动时执行一次）
+pub fn start_feature_backfill_task(
+    app_state: AppState,
+    config: &AppConfig,
+) -> Option<JoinHandle<()>> {
+    let placeholders = config.placeholder.enabled && config.placeholder.backfill_on_startup;
+    let colors = config.colors.enabled && config.colors.backfill_on_startup;
+    if !placeholders && !colors {
+        return None;
+    }
+
+    Some(tokio::spawn(async move {
+        match services::ImageService::backfill_features(app_state.db_pool()).await {
+            Ok(count) => {
+                if count > 0 {
+                    info!("图片特征补算完成: {}张图片", count);
+                }
+            }
+            Err(e) => {
+                error!("图片特征补算失败: {}", e);
+            }
+        }
+    }))
+}
+
 /// 打印API接口信息
 pub fn print_api_info() {
     info!("API接口:");
@@ -63,7 +116,24 @@ pub fn print_api_info() {
     info!("  健康检查: GET      /health");
     info!("  上传图片: POST     /upload");
     info!("  获取图片: GET      /images/<filename>");
+    info!("  查询参数: GET      /images/<filename>?w=&h=&fmt=&q=");
+    let compat = &AppConfig::get().compat;
+    if !compat.imgproxy_prefix.is_empty() {
+        info!(
+            "  imgproxy: GET      {}",
+            compat_route(&compat.imgproxy_prefix)
+        );
+    }
+    if !compat.thumbor_prefix.is_empty() {
+        info!(
+            "  thumbor:  GET      {}",
+            compat_route(&compat.thumbor_prefix)
+        );
+    }
     info!("  图片信息: GET      /images/<filename>/info");
+    info!("  响应清单: GET      /images/<filename>/srcset");
+    info!("  密码解锁: POST     /images/<filename>/unlock");
+    info!("  分享令牌: POST     /images/<filename>/token");
     info!("  查询列表: GET/POST /api/images/query");
     info!("  统计信息: GET      /api/stats");
     info!("  删除图片: DEL      /images/<filename>");
@@ -90,6 +160,36 @@ pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
     info!("启动图床服务...");
     info!("使用配置: {:#?}", config);
 
+    // 校验水印配置（字体、强制水印规则）
+    if let Err(e) = services::WatermarkService::validate_config(config) {
+        eprintln!("水印配置错误: {}", e);
+        std::process::exit(1);
+    }
+
+    // 校验转换预设
+    if let Err(e) = services::PresetService::validate_config(config) {
+        eprintln!("预设配置错误: {}", e);
+        std::process::exit(1);
+    }
+
+    // 校验占位图配置
+    if let Err(e) = services::PlaceholderService::validate_config(config) {
+        eprintln!("占位图配置错误: {}", e);
+        std::process::exit(1);
+    }
+
+    // 校验颜色提取配置
+    if let Err(e) = services::ColorService::validate_config(config) {
+        eprintln!("颜色提取配置错误: {}", e);
+        std::process::exit(1);
+    }
+
+    // 校验兼容路由配置
+    if let Err(e) = services::CompatUrl::validate_config(config) {
+        eprintln!("兼容路由配置错误: {}", e);
+        std::process::exit(1);
+    }
+
     // 确保上传目录存在
     if let Err(e) = utils::ensure_upload_dir().await {
         eprintln!("创建上传目录失败: {}", e);
@@ -111,6 +211,12 @@ pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
     // 启动缓存自动清理任务
     let cleanup_task = start_cache_cleanup_task(app_state.clone(), config);
 
+    // 启动过期图片清理任务
+    let expired_cleanup_task = start_expired_image_cleanup_task(app_state.clone(), config);
+
+    // 启动图片特征补算任务
+    let feature_backfill_task = start_feature_backfill_task(app_state.clone(), config);
+
     // 创建路由
     let app = create_routes(app_state, config);
 
@@ -145,6 +251,10 @@ pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
     if let Some(task) = cleanup_task {
         task.abort();
     }
+    expired_cleanup_task.abort();
+    if let Some(task) = feature_backfill_task {
+        task.abort();
+    }
 
     Ok(())
 }
@@ -0,0 +1,573 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, OnceLock};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::http::HeaderMap;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::warn;
+
+use crate::config::AppConfig;
+use crate::models::{ImageInfo, TokenScope, Visibility};
+use crate::utils::{AppError, Duration};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 访问密码最小长度
+const PASSWORD_MIN_LENGTH: usize = 4;
+
+/// 访问密码最大长度
+const PASSWORD_MAX_LENGTH: usize = 128;
+
+/// 令牌Cookie名前缀，后接图片hash前16位
+const TOKEN_COOKIE_PREFIX: &str = "rifs_token_";
+
+/// 同一来源对同一图片解锁失败达到此次数后开始退避
+const UNLOCK_FREE_ATTEMPTS: u32 = 5;
+
+/// 解锁退避时长上限（秒）
+const UNLOCK_MAX_BACKOFF_SECS: i64 = 900;
+
+/// 解锁失败记录在最后一次失败后保留的时长（秒）
+const UNLOCK_FAILURE_RETENTION_SECS: i64 = 3600;
+
+/// 令牌签名密钥（未配置时启动随机生成）
+static TOKEN_SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// 解锁失败记录，键为 "hash" 或 "hash@ip"
+static UNLOCK_FAILURES: OnceLock<Mutex<HashMap<String, UnlockFailures>>> = OnceLock::new();
+
+/// 解锁失败计数
+#[derive(Debug, Clone, Copy)]
+struct UnlockFailures {
+    /// 连续失败次数
+    count: u32,
+    /// 最后一次失败时间
+    last_failure: DateTime<Utc>,
+}
+
+impl UnlockFailures {
+    /// 超过免退避次数后，下一次允许尝试的时间（每多失败一次退避时长翻倍）
+    fn retry_at(&self, free_attempts: u32) -> Option<DateTime<Utc>> {
+        if self.count < free_attempts {
+            return None;
+        }
+        let exponent = (self.count - free_attempts).min(16);
+        let backoff = (1i64 << exponent).min(UNLOCK_MAX_BACKOFF_SECS);
+        Some(self.last_failure + chrono::Duration::seconds(backoff))
+    }
+}
+
+/// 请求携带的访问凭据
+#[derive(Debug, Default)]
+pub struct AccessCredentials {
+    /// API密钥（X-API-Key 或 Authorization: Bearer）
+    api_key: Option<String>,
+    /// 显式传递的访问令牌（查询参数或 X-Access-Token）
+    token: Option<String>,
+    /// 请求Cookie
+    cookies: Vec<(String, String)>,
+}
+
+impl AccessCredentials {
+    /// 从请求头和查询参数中提取访问凭据
+    pub fn from_request(headers: &HeaderMap, query_token: Option<String>) -> Self {
+        let header_str = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+
+        let api_key = header_str("x-api-key").or_else(|| {
+            header_str("authorization").and_then(|v| {
+                v.strip_prefix("Bearer ")
+                    .map(|key| key.trim().to_string())
+                    .filter(|key| !key.is_empty())
+            })
+        });
+
+        let token = query_token
+            .filter(|t| !t.is_empty())
+            .or_else(|| header_str("x-access-token"));
+
+        let cookies = headers
+            .get_all("cookie")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|pair| {
+                let (name, value) = pair.trim().split_once('=')?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+
+        Self {
+            api_key,
+            token,
+            cookies,
+        }
+    }
+
+    /// 请求携带的API密钥（未校验）
+    pub fn api_key(&self) -> Option<&str> {
+        self.api_key.as_deref()
+    }
+
+    /// 是否携带了有效的API密钥
+    pub fn has_valid_api_key(&self) -> bool {
+        self.api_key
+            .as_deref()
+            .is_some_and(AccessService::is_valid_api_key)
+    }
+
+    /// 获取指定图片的访问令牌（显式令牌优先，其次为Cookie）
+    fn token_for(&self, hash: &str) -> Option<&str> {
+        if let Some(token) = self.token.as_deref() {
+            return Some(token);
+        }
+
+        let cookie_name = AccessService::token_cookie_name(hash);
+        self.cookies
+            .iter()
+            .find(|(name, _)| *name == cookie_name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// 访问控制服务
+pub struct AccessService;
+
+impl AccessService {
+    /// 获取令牌签名密钥
+    fn token_secret() -> &'static [u8] {
+        TOKEN_SECRET.get_or_init(|| {
+            let configured = &AppConfig::get().auth.token_secret;
+            if !configured.is_empty() {
+                return configured.as_bytes().to_vec();
+            }
+
+            warn!("未配置 auth.token_secret，使用随机密钥，重启后已签发的访问令牌将失效");
+            let mut secret = vec![0u8; 32];
+            OsRng.fill_bytes(&mut secret);
+            secret
+        })
+    }
+
+    /// 计算令牌签名，签名内容包含授权范围，查看令牌不能用于删除
+    fn sign(hash: &str, expires: i64, scope: TokenScope) -> String {
+        let mut mac =
+            HmacSha256::new_from_slice(Self::token_secret()).expect("HMAC可接受任意长度密钥");
+        mac.update(format!("{}:{}:{}", hash, expires, scope.as_str()).as_bytes());
+        general_purpose::URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    /// 校验访问密码格式
+    pub fn validate_password(password: &str) -> Result<(), AppError> {
+        let length = password.chars().count();
+        if !(PASSWORD_MIN_LENGTH..=PASSWORD_MAX_LENGTH).contains(&length) {
+            return Err(AppError::BadRequest(format!(
+                "访问密码长度需在{}到{}个字符之间",
+                PASSWORD_MIN_LENGTH, PASSWORD_MAX_LENGTH
+            )));
+        }
+        Ok(())
+    }
+
+    /// 计算访问密码的argon2哈希
+    pub fn hash_password(password: &str) -> Result<String, AppError> {
+        Self::validate_password(password)?;
+
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| AppError::Internal(format!("密码哈希失败: {}", e)))
+    }
+
+    /// 校验访问密码
+    pub fn verify_password(password_hash: &str, password: &str) -> bool {
+        match PasswordHash::new(password_hash) {
+            Ok(parsed) => Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok(),
+            Err(e) => {
+                warn!("无法解析存储的密码哈希: {}", e);
+                false
+            }
+        }
+    }
+
+    /// 为图片签发指定范围的访问令牌，返回令牌和过期时间
+    pub fn issue_token(hash: &str, scope: TokenScope, ttl: Duration) -> (String, DateTime<Utc>) {
+        let expires_at = Utc::now() + chrono::Duration::seconds(ttl.as_seconds() as i64);
+        let expires = expires_at.timestamp();
+        let token = format!("{}.{}", expires, Self::sign(hash, expires, scope));
+        (token, expires_at)
+    }
+
+    /// 校验图片访问令牌及其授权范围
+    pub fn verify_token(hash: &str, token: &str, scope: TokenScope) -> bool {
+        let Some((expires, signature)) = token.split_once('.') else {
+            return false;
+        };
+        let Ok(expires) = expires.parse::<i64>() else {
+            return false;
+        };
+        if expires < Utc::now().timestamp() {
+            return false;
+        }
+
+        constant_time_eq(
+            Self::sign(hash, expires, scope).as_bytes(),
+            signature.as_bytes(),
+        )
+    }
+
+    /// 获取解锁失败记录表
+    fn unlock_failures() -> std::sync::MutexGuard<'static, HashMap<String, UnlockFailures>> {
+        UNLOCK_FAILURES
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 识别请求的客户端IP
+    ///
+    /// 直连地址为受信任的反向代理时，从 X-Forwarded-For 中由右向左取第一个不受信任的地址
+    pub fn client_ip(peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        Self::forwarded_client_ip(peer, headers, &AppConfig::get().auth.trusted_proxies)
+    }
+
+    /// 按给定的受信任代理列表识别客户端IP
+    fn forwarded_client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpAddr]) -> IpAddr {
+        if !trusted.contains(&peer) {
+            return peer;
+        }
+
+        headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .map(|addr| addr.trim().parse::<IpAddr>())
+            .take_while(Result::is_ok)
+            .filter_map(Result::ok)
+            .find(|addr| !trusted.contains(addr))
+            .unwrap_or(peer)
+    }
+
+    /// 检查是否允许尝试解锁，连续失败过多时返回需要等待的错误
+    pub fn check_unlock_allowed(hash: &str, client: IpAddr) -> Result<(), AppError> {
+        let image_lockout = AppConfig::get().auth.unlock_image_lockout_attempts;
+        let failures = Self::unlock_failures();
+        let retry_at = [
+            failures
+                .get(&format!("{}@{}", hash, client))
+                .and_then(|f| f.retry_at(UNLOCK_FREE_ATTEMPTS)),
+            failures
+                .get(hash)
+                .filter(|_| image_lockout > 0)
+                .and_then(|f| f.retry_at(image_lockout)),
+        ]
+        .into_iter()
+        .flatten()
+        .max();
+
+        match retry_at {
+            Some(retry_at) if retry_at > Utc::now() => {
+                let wait = (retry_at - Utc::now()).num_seconds().max(1);
+                Err(AppError::TooManyRequests(format!(
+                    "解锁尝试过于频繁，请{}秒后重试",
+                    wait
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 记录一次解锁失败
+    pub fn record_unlock_failure(hash: &str, client: IpAddr) {
+        let now = Utc::now();
+        let mut failures = Self::unlock_failures();
+        failures
+            .retain(|_, f| (now - f.last_failure).num_seconds() < UNLOCK_FAILURE_RETENTION_SECS);
+
+        for key in [format!("{}@{}", hash, client), hash.to_string()] {
+            let entry = failures.entry(key).or_insert(UnlockFailures {
+                count: 0,
+                last_failure: now,
+            });
+            entry.count = entry.count.saturating_add(1);
+            entry.last_failure = now;
+        }
+    }
+
+    /// 解锁成功后清除该来源的失败记录（图片维度的累计记录保留到自然过期）
+    pub fn clear_unlock_failures(hash: &str, client: IpAddr) {
+        Self::unlock_failures().remove(&format!("{}@{}", hash, client));
+    }
+
+    /// 校验API密钥
+    pub fn is_valid_api_key(key: &str) -> bool {
+        AppConfig::get()
+            .auth
+            .api_keys
+            .iter()
+            .any(|configured| constant_time_eq(configured.as_bytes(), key.as_bytes()))
+    }
+
+    /// 图片访问令牌对应的Cookie名
+    pub fn token_cookie_name(hash: &str) -> String {
+        format!("{}{}", TOKEN_COOKIE_PREFIX, &hash[..hash.len().min(16)])
+    }
+
+    /// 检查请求是否有权访问图片
+    ///
+    /// API密钥可访问所有图片；私有或受密码保护的图片需要有效的访问令牌
+    pub fn authorize(
+        image_info: &ImageInfo,
+        credentials: &AccessCredentials,
+    ) -> Result<(), AppError> {
+        let restricted =
+            image_info.visibility == Visibility::Private || image_info.is_password_protected();
+        if !restricted || credentials.has_valid_api_key() {
+            return Ok(());
+        }
+
+        if credentials
+            .token_for(&image_info.hash)
+            .is_some_and(|token| Self::verify_token(&image_info.hash, token, TokenScope::View))
+        {
+            return Ok(());
+        }
+
+        if image_info.is_password_protected() {
+            Err(AppError::Unauthorized(
+                "图片受密码保护，请先解锁".to_string(),
+            ))
+        } else {
+            Err(AppError::Unauthorized(
+                "私有图片，需要API密钥或访问令牌".to_string(),
+            ))
+        }
+    }
+
+    /// 检查请求是否有权删除图片
+    ///
+    /// 非公开（私有、不公开列出）或受密码保护的图片需要API密钥或删除范围的访问令牌，
+    /// 密码解锁和默认分享签发的查看令牌不能用于删除
+    pub fn authorize_delete(
+        image_info: &ImageInfo,
+        credentials: &AccessCredentials,
+    ) -> Result<(), AppError> {
+        let restricted =
+            image_info.visibility != Visibility::Public || image_info.is_password_protected();
+        if !restricted || credentials.has_valid_api_key() {
+            return Ok(());
+        }
+
+        if credentials
+            .token_for(&image_info.hash)
+            .is_some_and(|token| Self::verify_token(&image_info.hash, token, TokenScope::Delete))
+        {
+            return Ok(());
+        }
+
+        Err(AppError::Unauthorized(
+            "删除非公开图片需要API密钥或删除令牌".to_string(),
+        ))
+    }
+}
+
+/// 常量时间比较，避免通过响应时间推测密钥
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(visibility: Visibility, password_hash: Option<&str>) -> ImageInfo {
+        ImageInfo {
+            hash: "a".repeat(64),
+            size: 0,
+            mime_type: "image/png".to_string(),
+            created_at: Utc::now(),
+            last_accessed: None,
+            extension: "png".to_string(),
+            access_count: 0,
+            short_id: None,
+            alias: None,
+            expires_at: None,
+            max_views: None,
+            visibility,
+            password_hash: password_hash.map(str::to_string),
+            blurhash: None,
+            thumbhash: None,
+            dominant_color: None,
+            palette: Vec::new(),
+        }
+    }
+
+    fn with_token(token: &str) -> AccessCredentials {
+        AccessCredentials {
+            token: Some(token.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn verify_token_checks_hash_scope_and_signature() {
+        AppConfig::init_for_tests();
+        let hash = "a".repeat(64);
+        let (token, _) = AccessService::issue_token(&hash, TokenScope::View, Duration::hours(1));
+
+        assert!(AccessService::verify_token(&hash, &token, TokenScope::View));
+        assert!(!AccessService::verify_token(
+            &hash,
+            &token,
+            TokenScope::Delete
+        ));
+        assert!(!AccessService::verify_token(
+            &"b".repeat(64),
+            &token,
+            TokenScope::View
+        ));
+
+        let (expires, _) = token.split_once('.').unwrap();
+        let forged = format!("{}.{}", expires, "x".repeat(43));
+        assert!(!AccessService::verify_token(
+            &hash,
+            &forged,
+            TokenScope::View
+        ));
+        assert!(!AccessService::verify_token(
+            &hash,
+            "garbage",
+            TokenScope::View
+        ));
+    }
+
+    #[test]
+    fn verify_token_rejects_expired() {
+        AppConfig::init_for_tests();
+        let hash = "a".repeat(64);
+        let expires = Utc::now().timestamp() - 1;
+        let token = format!(
+            "{}.{}",
+            expires,
+            AccessService::sign(&hash, expires, TokenScope::View)
+        );
+        assert!(!AccessService::verify_token(
+            &hash,
+            &token,
+            TokenScope::View
+        ));
+    }
+
+    #[test]
+    fn authorize_delete_requires_delete_scope() {
+        AppConfig::init_for_tests();
+        let info = image(Visibility::Private, None);
+        let (view, _) =
+            AccessService::issue_token(&info.hash, TokenScope::View, Duration::hours(1));
+        let (delete, _) =
+            AccessService::issue_token(&info.hash, TokenScope::Delete, Duration::hours(1));
+
+        assert!(AccessService::authorize(&info, &with_token(&view)).is_ok());
+        assert!(AccessService::authorize(&info, &with_token(&delete)).is_err());
+        assert!(AccessService::authorize_delete(&info, &with_token(&view)).is_err());
+        assert!(AccessService::authorize_delete(&info, &with_token(&delete)).is_ok());
+        assert!(AccessService::authorize_delete(&info, &AccessCredentials::default()).is_err());
+    }
+
+    #[test]
+    fn authorize_delete_allows_public_without_credentials() {
+        AppConfig::init_for_tests();
+        let credentials = AccessCredentials::default();
+        assert!(
+            AccessService::authorize_delete(&image(Visibility::Public, None), &credentials).is_ok()
+        );
+        assert!(
+            AccessService::authorize_delete(&image(Visibility::Unlisted, None), &credentials)
+                .is_err()
+        );
+        assert!(AccessService::authorize_delete(
+            &image(Visibility::Public, Some("$argon2id$placeholder")),
+            &credentials
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn unlock_backs_off_after_repeated_failures() {
+        AppConfig::init_for_tests();
+        let hash = "c".repeat(64);
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+
+        for _ in 0..UNLOCK_FREE_ATTEMPTS {
+            assert!(AccessService::check_unlock_allowed(&hash, client).is_ok());
+            AccessService::record_unlock_failure(&hash, client);
+        }
+        assert!(matches!(
+            AccessService::check_unlock_allowed(&hash, client),
+            Err(AppError::TooManyRequests(_))
+        ));
+        assert!(AccessService::check_unlock_allowed(&hash, other).is_ok());
+
+        AccessService::clear_unlock_failures(&hash, client);
+        assert!(AccessService::check_unlock_allowed(&hash, client).is_ok());
+    }
+
+    #[test]
+    fn per_image_lockout_is_disabled_by_default() {
+        AppConfig::init_for_tests();
+        let hash = "d".repeat(64);
+        for index in 0..200u8 {
+            let client = IpAddr::from([198, 51, 100, index]);
+            AccessService::record_unlock_failure(&hash, client);
+        }
+        let fresh: IpAddr = "203.0.113.1".parse().unwrap();
+        assert!(AccessService::check_unlock_allowed(&hash, fresh).is_ok());
+    }
+
+    #[test]
+    fn client_ip_uses_forwarded_header_only_from_trusted_proxies() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let inner_proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let trusted = [proxy, inner_proxy];
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "198.51.100.7, 203.0.113.9, 10.0.0.2".parse().unwrap(),
+        );
+
+        let client = AccessService::forwarded_client_ip(proxy, &headers, &trusted);
+        assert_eq!(client, "203.0.113.9".parse::<IpAddr>().unwrap());
+
+        let untrusted: IpAddr = "192.0.2.10".parse().unwrap();
+        assert_eq!(
+            AccessService::forwarded_client_ip(untrusted, &headers, &trusted),
+            untrusted
+        );
+        assert_eq!(
+            AccessService::forwarded_client_ip(proxy, &HeaderMap::new(), &trusted),
+            proxy
+        );
+    }
+}
@@ -0,0 +1,724 @@
+use image::{DynamicImage, GenericImageView, ImageFormat, RgbaImage};
+use std::io::Cursor;
+use tracing::{error, info};
+
+use crate::config::AppConfig;
+use crate::models::{FrameSelection, ImageTransformParams};
+use crate::utils::AppError;
+
+/// 浏览器对过短帧间隔的处理：不超过该值的帧延迟按默认延迟播放
+const MIN_FRAME_DELAY_MS: u32 = 10;
+
+/// 默认帧延迟（毫秒）
+const DEFAULT_FRAME_DELAY_MS: u32 = 100;
+
+/// GIF调色板量化速度（1-30，越大越快、质量越低）
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+/// GIF中低于该值的透明度视为全透明
+const GIF_ALPHA_THRESHOLD: u8 = 128;
+
+/// 帧序列图的最大边长
+const MAX_SPRITE_SIDE: u32 = 16384;
+
+/// 解码后的动图帧（已按处置方式合成为完整画布）
+pub struct AnimatedFrame {
+    /// 帧画面
+    pub image: RgbaImage,
+    /// 帧持续时间（毫秒）
+    pub delay_ms: u32,
+}
+
+/// 解码后的动图
+pub struct AnimatedImage {
+    /// 全部帧
+    pub frames: Vec<AnimatedFrame>,
+    /// 播放次数（0表示无限循环，1表示只播放一次）
+    pub loop_count: u16,
+}
+
+/// 动图转换服务
+pub struct AnimatedImageTransform;
+
+impl AnimatedImageTransform {
+    /// 解码GIF或动态WebP的全部帧
+    pub fn decode(data: &[u8], mime_type: &str) -> Result<AnimatedImage, AppError> {
+        match mime_type {
+            "image/gif" => Self::decode_gif(data),
+            "image/webp" => Self::decode_webp(data),
+            _ => Err(AppError::BadRequest(format!(
+                "仅支持GIF和WebP动图，当前格式: {}",
+                mime_type
+            ))),
+        }
+    }
+
+    /// 检查动图是否超出处理预算
+    fn check_budget(width: u32, height: u32, frame_count: usize) -> Result<(), AppError> {
+        let config = &AppConfig::get().transform;
+
+        if frame_count > config.max_animation_frames as usize {
+            return Err(AppError::BadRequest(format!(
+                "动图帧数超出限制: 最多{}帧",
+                config.max_animation_frames
+            )));
+        }
+
+        let pixels = width as u64 * height as u64 * frame_count as u64;
+        if pixels > config.max_animation_pixels {
+            return Err(AppError::BadRequest(format!(
+                "动图总像素数超出限制: {}x{}x{}帧 > {}",
+                width, height, frame_count, config.max_animation_pixels
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// 规范化帧延迟
+    fn normalize_delay(delay_ms: u32) -> u32 {
+        if delay_ms <= MIN_FRAME_DELAY_MS {
+            DEFAULT_FRAME_DELAY_MS
+        } else {
+            delay_ms
+        }
+    }
+
+    /// 解码GIF，按处置方式将各帧合成到完整画布
+    fn decode_gif(data: &[u8]) -> Result<AnimatedImage, AppError> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+
+        let decode_error = |e: gif::DecodingError| {
+            error!("GIF解码失败: {}", e);
+            AppError::BadRequest("无法解析GIF文件".to_string())
+        };
+
+        let mut reader = options.read_info(Cursor::new(data)).map_err(decode_error)?;
+        let (width, height) = (reader.width() as u32, reader.height() as u32);
+
+        // 逻辑屏幕尺寸来自文件头，分配画布前先检查预算
+        Self::check_budget(width, height, 1)?;
+
+        let mut canvas = RgbaImage::new(width, height);
+        let mut frames = Vec::new();
+        let mut buffer = Vec::new();
+
+        while let Some(frame) = reader.next_frame_info().map_err(decode_error)? {
+            Self::check_budget(width, height, frames.len() + 1)?;
+
+            let dispose = frame.dispose;
+            let delay = frame.delay;
+            let (left, top) = (frame.left as u32, frame.top as u32);
+            let (frame_width, frame_height) = (frame.width as u32, frame.height as u32);
+
+            // 帧尺寸可以超出逻辑屏幕，读取像素前同样检查预算
+            Self::check_budget(frame_width, frame_height, 1)?;
+            buffer.resize(reader.buffer_size(), 0);
+            reader.read_into_buffer(&mut buffer).map_err(decode_error)?;
+
+            let previous = (dispose == gif::DisposalMethod::Previous).then(|| canvas.clone());
+
+            // 绘制当前帧（透明像素保留下层画面）
+            for (index, pixel) in buffer.chunks_exact(4).enumerate() {
+                if pixel[3] == 0 {
+                    continue;
+                }
+                let x = left + index as u32 % frame_width;
+                let y = top + index as u32 / frame_width;
+                if x < width && y < height {
+                    canvas.put_pixel(x, y, image::Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]));
+                }
+            }
+
+            frames.push(AnimatedFrame {
+                image: canvas.clone(),
+                delay_ms: Self::normalize_delay(delay as u32 * 10),
+            });
+
+            // 按处置方式准备下一帧的画布
+            match dispose {
+                gif::DisposalMethod::Background => {
+                    for y in top..(top + frame_height).min(height) {
+                        for x in left..(left + frame_width).min(width) {
+                            canvas.put_pixel(x, y, image::Rgba([0, 0, 0, 0]));
+                        }
+                    }
+                }
+                gif::DisposalMethod::Previous => {
+                    if let Some(previous) = previous {
+                        canvas = previous;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if frames.is_empty() {
+            return Err(AppError::BadRequest("GIF不包含任何帧".to_string()));
+        }
+
+        // NETSCAPE扩展中的次数不含首次播放；没有该扩展时只播放一次
+        let loop_count = match reader.repeat() {
+            gif::Repeat::Infinite => 0,
+            gif::Repeat::Finite(count) => count.saturating_add(1),
+        };
+
+        info!(
+            "GIF动图解码完成: {}x{}, {}帧, 循环次数: {}",
+            width,
+            height,
+            frames.len(),
+            loop_count
+        );
+
+        Ok(AnimatedImage { frames, loop_count })
+    }
+
+    /// 解码动态WebP
+    fn decode_webp(data: &[u8]) -> Result<AnimatedImage, AppError> {
+        // libwebp一次性解码全部帧，先从容器头部检查预算，无法读取尺寸时拒绝解码
+        let (width, height, frame_count) = Self::probe_webp(data)
+            .ok_or_else(|| AppError::BadRequest("无法读取WebP尺寸".to_string()))?;
+        Self::check_budget(width, height, frame_count)?;
+
+        let decoded = webp::AnimDecoder::new(data).decode().map_err(|e| {
+            error!("WebP动图解码失败: {}", e);
+            AppError::BadRequest("无法解析WebP动图".to_string())
+        })?;
+
+        // 解码器给出的时间戳为各帧的结束时间
+        let mut frames = Vec::with_capacity(decoded.len());
+        let mut previous_timestamp = 0;
+        for frame in &decoded {
+            let timestamp = frame.get_time_ms();
+            let image = match frame.get_layout() {
+                webp::PixelLayout::Rgba => {
+                    RgbaImage::from_raw(frame.width(), frame.height(), frame.get_image().to_vec())
+                }
+                webp::PixelLayout::Rgb => image::RgbImage::from_raw(
+                    frame.width(),
+                    frame.height(),
+                    frame.get_image().to_vec(),
+                )
+                .map(|rgb| image::DynamicImage::ImageRgb8(rgb).to_rgba8()),
+            }
+            .ok_or_else(|| AppError::Internal("构建WebP帧图像失败".to_string()))?;
+
+            frames.push(AnimatedFrame {
+                image,
+                delay_ms: Self::normalize_delay((timestamp - previous_timestamp).max(0) as u32),
+            });
+            previous_timestamp = timestamp;
+        }
+
+        if frames.is_empty() {
+            return Err(AppError::BadRequest("WebP不包含任何帧".to_string()));
+        }
+
+        let loop_count = decoded.loop_count.min(u16::MAX as u32) as u16;
+
+        info!(
+            "WebP动图解码完成: {}x{}, {}帧, 循环次数: {}",
+            frames[0].image.width(),
+            frames[0].image.height(),
+            frames.len(),
+            loop_count
+        );
+
+        Ok(AnimatedImage { frames, loop_count })
+    }
+
+    /// 按序号或时间点选取单帧
+    ///
+    /// 时间点超出动画总时长时返回最后一帧
+    pub fn select_frame(
+        animation: AnimatedImage,
+        selection: FrameSelection,
+    ) -> Result<RgbaImage, AppError> {
+        let frame_count = animation.frames.len();
+        let index = match selection {
+            FrameSelection::Index(index) => {
+                if index as usize >= frame_count {
+                    return Err(AppError::BadRequest(format!(
+                        "帧序号超出范围: 共{}帧（从0开始）",
+                        frame_count
+                    )));
+                }
+                index as usize
+            }
+            FrameSelection::Time(millis) => {
+                let mut elapsed = 0u64;
+                animation
+                    .frames
+                    .iter()
+                    .position(|frame| {
+                        elapsed += frame.delay_ms as u64;
+                        elapsed > millis as u64
+                    })
+                    .unwrap_or(frame_count - 1)
+            }
+            FrameSelection::Sprite { .. } => {
+                return Err(AppError::Internal("帧序列图不能作为单帧选取".to_string()));
+            }
+        };
+
+        info!("选取动图第{}帧（共{}帧）", index, frame_count);
+        Ok(animation
+            .frames
+            .into_iter()
+            .nth(index)
+            .map(|frame| frame.image)
+            .unwrap_or_default())
+    }
+
+    /// 均匀抽取若干帧（不指定数量或数量超过总帧数时返回全部帧）
+    pub fn sample_frames(animation: AnimatedImage, count: Option<u32>) -> Vec<RgbaImage> {
+        let frame_count = animation.frames.len();
+        let count = count.map_or(frame_count, |count| (count as usize).min(frame_count));
+
+        let mut frames: Vec<Option<RgbaImage>> = animation
+            .frames
+            .into_iter()
+            .map(|frame| Some(frame.image))
+            .collect();
+        (0..count)
+            .filter_map(|i| frames[i * frame_count / count].take())
+            .collect()
+    }
+
+    /// 将帧按行拼接为帧序列图（不指定列数时排成一行）
+    pub fn compose_sprite(
+        tiles: Vec<DynamicImage>,
+        columns: Option<u32>,
+    ) -> Result<DynamicImage, AppError> {
+        let Some(first) = tiles.first() else {
+            return Err(AppError::Internal("帧序列图没有可用的帧".to_string()));
+        };
+
+        let (tile_width, tile_height) = first.dimensions();
+        let count = tiles.len() as u32;
+        let columns = columns.unwrap_or(count).clamp(1, count);
+        let rows = count.div_ceil(columns);
+
+        let (width, height) = (
+            tile_width as u64 * columns as u64,
+            tile_height as u64 * rows as u64,
+        );
+        if width > MAX_SPRITE_SIDE as u64 || height > MAX_SPRITE_SIDE as u64 {
+            return Err(AppError::BadRequest(format!(
+                "帧序列图尺寸超出限制: {}x{}，最大边长{}像素",
+                width, height, MAX_SPRITE_SIDE
+            )));
+        }
+
+        let has_alpha = tiles.iter().any(|tile| tile.color().has_alpha());
+        let mut sprite = RgbaImage::new(width as u32, height as u32);
+        for (i, tile) in tiles.iter().enumerate() {
+            let (column, row) = (i as u32 % columns, i as u32 / columns);
+            image::imageops::replace(
+                &mut sprite,
+                &tile.to_rgba8(),
+                (column * tile_width) as i64,
+                (row * tile_height) as i64,
+            );
+        }
+
+        info!(
+            "帧序列图拼接完成: {}帧, {}列x{}行, 单帧{}x{}",
+            count, columns, rows, tile_width, tile_height
+        );
+
+        // 各帧都不透明时输出RGB，与静图转换保持一致
+        Ok(if has_alpha {
+            DynamicImage::ImageRgba8(sprite)
+        } else {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(sprite).to_rgb8())
+        })
+    }
+
+    /// 列出WebP容器中的块（fourcc，数据起始偏移，数据长度）
+    fn webp_chunks(data: &[u8]) -> Option<Vec<([u8; 4], usize, usize)>> {
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+            return None;
+        }
+
+        let mut chunks = Vec::new();
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let fourcc: [u8; 4] = data[offset..offset + 4].try_into().ok()?;
+            let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().ok()?) as usize;
+            if offset + 8 + size > data.len() {
+                return None;
+            }
+            chunks.push((fourcc, offset + 8, size));
+
+            // 块按偶数字节对齐
+            offset += 8 + size + (size & 1);
+        }
+
+        Some(chunks)
+    }
+
+    /// 读取WebP容器中的画布尺寸和帧数，不解码像素
+    fn probe_webp(data: &[u8]) -> Option<(u32, u32, usize)> {
+        let mut canvas = None;
+        let mut frame_count = 0;
+        for (fourcc, offset, size) in Self::webp_chunks(data)? {
+            match &fourcc {
+                b"VP8X" if size >= 10 => {
+                    canvas = Some((
+                        read_u24(&data[offset + 4..]) + 1,
+                        read_u24(&data[offset + 7..]) + 1,
+                    ));
+                }
+                // 简单格式的WebP没有VP8X块，从位流头部读取尺寸
+                b"VP8 " if size >= 10 && canvas.is_none() => {
+                    let read_u14 = |at: usize| {
+                        u16::from_le_bytes([data[offset + at], data[offset + at + 1]]) as u32
+                            & 0x3fff
+                    };
+                    canvas = Some((read_u14(6), read_u14(8)));
+                }
+                b"VP8L" if size >= 5 && data[offset] == 0x2f && canvas.is_none() => {
+                    let bits = u32::from_le_bytes(data[offset + 1..offset + 5].try_into().ok()?);
+                    canvas = Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1));
+                }
+                b"ANMF" => frame_count += 1,
+                _ => {}
+            }
+        }
+
+        let (width, height) = canvas?;
+        Some((width, height, frame_count.max(1)))
+    }
+
+    /// 修正动态WebP最后一帧的持续时间
+    ///
+    /// webp库结束编码时传入的结束时间戳为0，libwebp会改用平均帧间隔作为最后一帧的时长，
+    /// 这里按总时长减去其余帧时长写回（libwebp可能合并相同的相邻帧，不能直接使用原帧延迟）
+    fn fix_last_webp_frame_duration(data: &mut [u8], total_ms: u32) {
+        let Some(frames) = Self::webp_chunks(data).map(|chunks| {
+            chunks
+                .into_iter()
+                .filter(|(fourcc, _, size)| fourcc == b"ANMF" && *size >= 16)
+                .map(|(_, offset, _)| offset)
+                .collect::<Vec<_>>()
+        }) else {
+            return;
+        };
+        let Some((&last, others)) = frames.split_last() else {
+            return;
+        };
+
+        let elapsed: u32 = others
+            .iter()
+            .map(|&offset| read_u24(&data[offset + 12..]))
+            .sum();
+        let duration = total_ms.saturating_sub(elapsed).clamp(1, 0xFF_FFFF);
+        data[last + 12..last + 15].copy_from_slice(&duration.to_le_bytes()[..3]);
+    }
+
+    /// 将动图编码为GIF或动态WebP
+    pub fn encode(
+        animation: &AnimatedImage,
+        format: ImageFormat,
+        params: &ImageTransformParams,
+    ) -> Result<Vec<u8>, AppError> {
+        match format {
+            ImageFormat::Gif => Self::encode_gif(animation),
+            ImageFormat::WebP => {
+                Self::encode_webp(animation, params.quality.unwrap_or(85), params.lossless)
+            }
+            _ => Err(AppError::BadRequest(format!(
+                "目标格式不支持动画: {:?}",
+                format
+            ))),
+        }
+    }
+
+    /// 编码GIF动图，每帧独立量化调色板
+    ///
+    /// 相同的相邻帧合并为一帧；其余帧只写入与上一帧不同的矩形区域，
+    /// 区域内未变化的像素设为透明以提高压缩率
+    fn encode_gif(animation: &AnimatedImage) -> Result<Vec<u8>, AppError> {
+        let (width, height) = animation.frames[0].image.dimensions();
+        if width > u16::MAX as u32 || height > u16::MAX as u32 {
+            return Err(AppError::BadRequest(format!(
+                "GIF尺寸超出限制: {}x{}",
+                width, height
+            )));
+        }
+
+        // GIF只有1位透明度，统一透明像素的颜色以便共用一个透明索引
+        let mut frames: Vec<(Vec<u8>, u32)> = Vec::with_capacity(animation.frames.len());
+        for frame in &animation.frames {
+            let mut pixels = frame.image.as_raw().clone();
+            for pixel in pixels.chunks_exact_mut(4) {
+                if pixel[3] < GIF_ALPHA_THRESHOLD {
+                    pixel.copy_from_slice(&[0, 0, 0, 0]);
+                } else {
+                    pixel[3] = 255;
+                }
+            }
+
+            match frames.last_mut() {
+                Some((previous, delay)) if *previous == pixels => *delay += frame.delay_ms,
+                _ => frames.push((pixels, frame.delay_ms)),
+            }
+        }
+
+        // 当前帧在上一帧不透明处变为透明时，上一帧必须以清除背景的方式处置
+        let needs_clear: Vec<bool> = (0..frames.len())
+            .map(|i| {
+                i > 0
+                    && frames[i]
+                        .0
+                        .chunks_exact(4)
+                        .zip(frames[i - 1].0.chunks_exact(4))
+                        .any(|(current, previous)| current[3] == 0 && previous[3] != 0)
+            })
+            .collect();
+
+        let encode_error = |e: gif::EncodingError| {
+            error!("GIF编码失败: {}", e);
+            AppError::Internal("GIF编码失败".to_string())
+        };
+
+        let mut buffer = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut buffer, width as u16, height as u16, &[])
+                .map_err(encode_error)?;
+            let repeat = match animation.loop_count {
+                0 => Some(gif::Repeat::Infinite),
+                1 => None,
+                count => Some(gif::Repeat::Finite(count - 1)),
+            };
+            if let Some(repeat) = repeat {
+                encoder.set_repeat(repeat).map_err(encode_error)?;
+            }
+
+            for (i, (pixels, delay_ms)) in frames.iter().enumerate() {
+                let clear_next = needs_clear.get(i + 1).copied().unwrap_or(false);
+                let full_frame = i == 0 || needs_clear[i] || clear_next;
+
+                let (left, top, mut region, region_width, region_height) = if full_frame {
+                    (0, 0, pixels.clone(), width, height)
+                } else {
+                    Self::changed_region(&frames[i - 1].0, pixels, width, height)
+                };
+
+                let mut gif_frame = gif::Frame::from_rgba_speed(
+                    region_width as u16,
+                    region_height as u16,
+                    &mut region,
+                    GIF_QUANTIZE_SPEED,
+                );
+                gif_frame.left = left as u16;
+                gif_frame.top = top as u16;
+                gif_frame.delay = ((delay_ms + 5) / 10).min(u16::MAX as u32) as u16;
+                gif_frame.dispose = if clear_next {
+                    gif::DisposalMethod::Background
+                } else {
+                    gif::DisposalMethod::Keep
+                };
+                encoder.write_frame(&gif_frame).map_err(encode_error)?;
+            }
+        }
+
+        info!(
+            "GIF动图编码完成: {}x{}, {}帧（合并后{}帧）, {}字节",
+            width,
+            height,
+            animation.frames.len(),
+            frames.len(),
+            buffer.len()
+        );
+
+        Ok(buffer)
+    }
+
+    /// 计算与上一帧不同的矩形区域，返回（左，上，区域像素，宽，高）
+    ///
+    /// 区域内未变化的像素设为透明，叠加在上一帧上时保持原样
+    fn changed_region(
+        previous: &[u8],
+        current: &[u8],
+        width: u32,
+        height: u32,
+    ) -> (u32, u32, Vec<u8>, u32, u32) {
+        let pixel_at = |data: &[u8], x: u32, y: u32| {
+            let offset = ((y * width + x) * 4) as usize;
+            [
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ]
+        };
+
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
+        for y in 0..height {
+            for x in 0..width {
+                if pixel_at(previous, x, y) != pixel_at(current, x, y) {
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
+                }
+            }
+        }
+
+        // 相邻帧已去重，保险起见无变化时输出单个透明像素
+        if min_x > max_x || min_y > max_y {
+            return (0, 0, vec![0, 0, 0, 0], 1, 1);
+        }
+
+        let (region_width, region_height) = (max_x - min_x + 1, max_y - min_y + 1);
+        let mut region = Vec::with_capacity((region_width * region_height * 4) as usize);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let pixel = pixel_at(current, x, y);
+                if pixel == pixel_at(previous, x, y) {
+                    region.extend_from_slice(&[0, 0, 0, 0]);
+                } else {
+                    region.extend_from_slice(&pixel);
+                }
+            }
+        }
+
+        (min_x, min_y, region, region_width, region_height)
+    }
+
+    /// 编码动态WebP（显式要求或质量不低于95时使用无损压缩）
+    ///
+    /// libwebp会自动合并相同帧并只编码变化区域
+    fn encode_webp(
+        animation: &AnimatedImage,
+        quality: u8,
+        lossless: bool,
+    ) -> Result<Vec<u8>, AppError> {
+        let (width, height) = animation.frames[0].image.dimensions();
+
+        let mut config = webp::WebPConfig::new()
+            .map_err(|_| AppError::Internal("WebP编码配置初始化失败".to_string()))?;
+        if lossless || quality >= 95 {
+            config.lossless = 1;
+        } else {
+            config.quality = quality as f32;
+        }
+
+        let mut encoder = webp::AnimEncoder::new(width, height, &config);
+        encoder.set_loop_count(animation.loop_count as i32);
+
+        let mut timestamp = 0i32;
+        for frame in &animation.frames {
+            encoder.add_frame(webp::AnimFrame::from_rgba(
+                frame.image.as_raw(),
+                width,
+                height,
+                timestamp,
+            ));
+            timestamp = timestamp.saturating_add(frame.delay_ms as i32);
+        }
+
+        let mut data = encoder
+            .try_encode()
+            .map_err(|e| {
+                error!("WebP动图编码失败: {:?}", e);
+                AppError::Internal("WebP动图编码失败".to_string())
+            })?
+            .to_vec();
+        Self::fix_last_webp_frame_duration(&mut data, timestamp as u32);
+
+        info!(
+            "WebP动图编码完成: {}x{}, {}帧, 质量: {}, {}字节",
+            width,
+            height,
+            animation.frames.len(),
+            quality,
+            data.len()
+        );
+
+        Ok(data)
+    }
+}
+
+/// 读取小端序24位整数
+fn read_u24(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 编码一帧1x1的GIF，逻辑屏幕尺寸可任意指定
+    fn gif_with_screen(width: u16, height: u16) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut data, width, height, &[0, 0, 0]).unwrap();
+            let mut frame = gif::Frame::from_rgb(1, 1, &[255, 0, 0]);
+            frame.delay = 5;
+            encoder.write_frame(&frame).unwrap();
+        }
+        data
+    }
+
+    fn lossless_webp(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::new(width, height))
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::WebP)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn gif_within_budget_decodes() {
+        AppConfig::init_for_tests();
+        let animation =
+            AnimatedImageTransform::decode(&gif_with_screen(4, 3), "image/gif").unwrap();
+        assert_eq!(animation.frames.len(), 1);
+        assert_eq!(animation.frames[0].image.dimensions(), (4, 3));
+        assert_eq!(animation.frames[0].delay_ms, 50);
+    }
+
+    #[test]
+    fn gif_oversized_screen_rejected_before_allocation() {
+        AppConfig::init_for_tests();
+        let result = AnimatedImageTransform::decode(&gif_with_screen(65535, 65535), "image/gif");
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn gif_too_many_frames_rejected() {
+        AppConfig::init_for_tests();
+        let mut data = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut data, 1, 1, &[0, 0, 0]).unwrap();
+            let frames = AppConfig::get().transform.max_animation_frames + 1;
+            for _ in 0..frames {
+                encoder
+                    .write_frame(&gif::Frame::from_rgb(1, 1, &[255, 0, 0]))
+                    .unwrap();
+            }
+        }
+        let result = AnimatedImageTransform::decode(&data, "image/gif");
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn probe_webp_reads_simple_format_dimensions() {
+        assert_eq!(
+            AnimatedImageTransform::probe_webp(&lossless_webp(5, 7)),
+            Some((5, 7, 1))
+        );
+    }
+
+    #[test]
+    fn webp_without_readable_dimensions_rejected() {
+        AppConfig::init_for_tests();
+        let result = AnimatedImageTransform::decode(b"RIFF\x04\x00\x00\x00WEBP", "image/webp");
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+}
@@ -109,6 +109,7 @@ impl CacheService {
         transform_params: &ImageTransformParams,
         data: &[u8],
         mime_type: &str,
+        output_quality: Option<u8>,
     ) -> Result<CacheInfo, AppError> {
         let config = AppConfig::get();
 
@@ -146,6 +147,7 @@ impl CacheService {
             last_accessed: now,
             access_count: 1,
             heat_score: 1.0, // 新缓存初始热度为1.0
+            output_quality,
         };
 
         // 保存到数据库
@@ -388,9 +390,18 @@ impl CacheService {
         self.cache_repo.get_stats().await
     }
 
-    /// 根据原始哈希删除相关缓存
+    /// 根据原始哈希删除相关缓存（包括缓存文件）
     pub async fn remove_by_original_hash(&self, original_hash: &str) -> Result<u64, AppError> {
-        self.cache_repo.delete_by_original_hash(original_hash).await
+        let caches = self.cache_repo.find_by_original_hash(original_hash).await?;
+        let (cleaned_count, _) = self.cleanup_candidates(caches).await?;
+
+        // 兜底清理可能残留的记录
+        let remaining = self
+            .cache_repo
+            .delete_by_original_hash(original_hash)
+            .await?;
+
+        Ok(cleaned_count + remaining)
     }
 
     /// 执行热度衰减
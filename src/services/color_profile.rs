@@ -0,0 +1,312 @@
+use flate2::{write::ZlibEncoder, Compression};
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use std::io::{Cursor, Write};
+use tracing::{info, warn};
+
+use crate::utils::AppError;
+
+/// JPEG单个APP2段可容纳的ICC数据字节数（65535 - 长度字段 - 标识 - 序号）
+const JPEG_ICC_CHUNK_SIZE: usize = 65519;
+
+/// JPEG APP2段中ICC数据的标识
+const JPEG_ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+
+/// WebP VP8X块中ICC配置文件标志位
+const WEBP_ICC_FLAG: u8 = 0x20;
+
+/// WebP VP8X块中透明通道标志位
+const WEBP_ALPHA_FLAG: u8 = 0x10;
+
+/// ICC配置文件处理：读取、转换为sRGB和嵌入输出
+pub struct ColorProfile;
+
+impl ColorProfile {
+    /// 读取图片内嵌的ICC配置文件，没有或无法解析时返回None
+    pub fn read(data: &[u8]) -> Option<Vec<u8>> {
+        let mut decoder = ImageReader::new(Cursor::new(data))
+            .with_guessed_format()
+            .ok()?
+            .into_decoder()
+            .ok()?;
+        decoder.icc_profile().ok().flatten()
+    }
+
+    /// 将像素从配置文件的色彩空间转换为sRGB
+    ///
+    /// 非RGB配置文件（如CMYK、灰度）、无法解析或本身即为sRGB时原样返回
+    pub fn convert_to_srgb(img: DynamicImage, icc: &[u8]) -> DynamicImage {
+        // ICC头部第16-19字节为数据色彩空间
+        if icc.get(16..20) != Some(b"RGB ") {
+            return img;
+        }
+        let Some(input) = qcms::Profile::new_from_slice(icc, false) else {
+            warn!("ICC配置文件解析失败，跳过色彩转换");
+            return img;
+        };
+        if input.is_sRGB() {
+            return img;
+        }
+
+        let mut output = qcms::Profile::new_sRGB();
+        output.precache_output_transform();
+
+        let has_alpha = img.color().has_alpha();
+        let data_type = if has_alpha {
+            qcms::DataType::RGBA8
+        } else {
+            qcms::DataType::RGB8
+        };
+        let Some(transform) =
+            qcms::Transform::new(&input, &output, data_type, qcms::Intent::Perceptual)
+        else {
+            warn!("无法创建ICC色彩转换，跳过色彩转换");
+            return img;
+        };
+
+        info!("将内嵌ICC配置文件的色彩转换为sRGB");
+        if has_alpha {
+            let mut rgba = img.to_rgba8();
+            transform.apply(&mut rgba);
+            DynamicImage::ImageRgba8(rgba)
+        } else {
+            let mut rgb = img.to_rgb8();
+            transform.apply(&mut rgb);
+            DynamicImage::ImageRgb8(rgb)
+        }
+    }
+
+    /// 嵌入后增加的最大字节数，用于从目标体积中预留空间
+    pub fn embedded_size(icc: &[u8]) -> u64 {
+        let segments = icc.len().div_ceil(JPEG_ICC_CHUNK_SIZE).max(1) as u64;
+        // JPEG每段18字节开销；WebP最多增加VP8X块18字节、ICCP块头8字节和1字节填充
+        icc.len() as u64 + (segments * 18).max(27)
+    }
+
+    /// 在编码结果中嵌入ICC配置文件，仅支持JPEG、PNG和WebP
+    pub fn embed(data: Vec<u8>, format: ImageFormat, icc: &[u8]) -> Result<Vec<u8>, AppError> {
+        match format {
+            ImageFormat::Jpeg => Self::embed_jpeg(data, icc),
+            ImageFormat::Png => Self::embed_png(data, icc),
+            ImageFormat::WebP => Self::embed_webp(data, icc),
+            _ => Err(AppError::BadRequest(
+                "保留ICC配置文件仅支持JPEG、PNG和WebP格式".to_string(),
+            )),
+        }
+    }
+
+    /// JPEG：在SOI和APP0之后插入APP2段，配置文件过大时分段存放
+    fn embed_jpeg(data: Vec<u8>, icc: &[u8]) -> Result<Vec<u8>, AppError> {
+        if !data.starts_with(&[0xFF, 0xD8]) {
+            return Err(AppError::Internal("JPEG数据无效".to_string()));
+        }
+        let count = icc.len().div_ceil(JPEG_ICC_CHUNK_SIZE);
+        if count > u8::MAX as usize {
+            return Err(AppError::BadRequest("ICC配置文件过大".to_string()));
+        }
+
+        // JFIF要求APP0紧跟SOI
+        let mut offset = 2;
+        if data.get(2..4) == Some(&[0xFF, 0xE0]) {
+            let length = data
+                .get(4..6)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+                .ok_or_else(|| AppError::Internal("JPEG数据无效".to_string()))?;
+            offset = 4 + length;
+        }
+        if offset > data.len() {
+            return Err(AppError::Internal("JPEG数据无效".to_string()));
+        }
+
+        let mut output = Vec::with_capacity(data.len() + icc.len() + count * 18);
+        output.extend_from_slice(&data[..offset]);
+        for (index, chunk) in icc.chunks(JPEG_ICC_CHUNK_SIZE).enumerate() {
+            let length = (2 + JPEG_ICC_MARKER.len() + 2 + chunk.len()) as u16;
+            output.extend_from_slice(&[0xFF, 0xE2]);
+            output.extend_from_slice(&length.to_be_bytes());
+            output.extend_from_slice(JPEG_ICC_MARKER);
+            output.push(index as u8 + 1);
+            output.push(count as u8);
+            output.extend_from_slice(chunk);
+        }
+        output.extend_from_slice(&data[offset..]);
+        Ok(output)
+    }
+
+    /// PNG：在IHDR块之后插入zlib压缩的iCCP块
+    fn embed_png(data: Vec<u8>, icc: &[u8]) -> Result<Vec<u8>, AppError> {
+        // 8字节签名 + IHDR块（长度4 + 类型4 + 数据13 + CRC4）
+        const IHDR_END: usize = 8 + 25;
+        if data.len() < IHDR_END || data.get(12..16) != Some(b"IHDR") {
+            return Err(AppError::Internal("PNG数据无效".to_string()));
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(icc)
+            .map_err(|e| AppError::Internal(format!("ICC配置文件压缩失败: {}", e)))?;
+        let compressed = encoder
+            .finish()
+            .map_err(|e| AppError::Internal(format!("ICC配置文件压缩失败: {}", e)))?;
+
+        // 配置文件名称、NUL分隔符和压缩方法（0为zlib）
+        let mut chunk = b"iCCPICC Profile\0\0".to_vec();
+        chunk.extend_from_slice(&compressed);
+        let length = (chunk.len() - 4) as u32;
+        let crc = crc32fast::hash(&chunk);
+
+        let mut output = Vec::with_capacity(data.len() + chunk.len() + 8);
+        output.extend_from_slice(&data[..IHDR_END]);
+        output.extend_from_slice(&length.to_be_bytes());
+        output.extend_from_slice(&chunk);
+        output.extend_from_slice(&crc.to_be_bytes());
+        output.extend_from_slice(&data[IHDR_END..]);
+        Ok(output)
+    }
+
+    /// WebP：使用扩展格式（VP8X）并在其后插入ICCP块
+    fn embed_webp(data: Vec<u8>, icc: &[u8]) -> Result<Vec<u8>, AppError> {
+        let invalid = || AppError::Internal("WebP数据无效".to_string());
+        if data.len() < 20 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+            return Err(invalid());
+        }
+
+        let mut output = Vec::with_capacity(data.len() + icc.len() + 27);
+        output.extend_from_slice(&data[..12]);
+
+        let chunks = match &data[12..16] {
+            b"VP8X" => {
+                // 已是扩展格式，设置ICC标志位后原样保留VP8X块
+                let mut vp8x = data.get(12..30).ok_or_else(invalid)?.to_vec();
+                vp8x[8] |= WEBP_ICC_FLAG;
+                output.extend_from_slice(&vp8x);
+                &data[30..]
+            }
+            kind => {
+                let payload = &data[20..];
+                let (width, height, alpha) = match kind {
+                    // 有损：帧头第6字节起为14位宽高
+                    b"VP8 " => {
+                        let size = payload.get(6..10).ok_or_else(invalid)?;
+                        let width = u16::from_le_bytes([size[0], size[1]]) as u32 & 0x3FFF;
+                        let height = u16::from_le_bytes([size[2], size[3]]) as u32 & 0x3FFF;
+                        (width, height, false)
+                    }
+                    // 无损：签名字节后依次为14位宽减1、14位高减1和透明标志位
+                    b"VP8L" => {
+                        let header = payload.get(1..5).ok_or_else(invalid)?;
+                        let bits = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+                        (
+                            (bits & 0x3FFF) + 1,
+                            ((bits >> 14) & 0x3FFF) + 1,
+                            (bits >> 28) & 1 == 1,
+                        )
+                    }
+                    _ => return Err(invalid()),
+                };
+                if width == 0 || height == 0 {
+                    return Err(invalid());
+                }
+
+                let flags = WEBP_ICC_FLAG | if alpha { WEBP_ALPHA_FLAG } else { 0 };
+                output.extend_from_slice(b"VP8X");
+                output.extend_from_slice(&10u32.to_le_bytes());
+                output.extend_from_slice(&[flags, 0, 0, 0]);
+                output.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+                output.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+                &data[12..]
+            }
+        };
+
+        // ICCP块必须紧跟VP8X块，奇数长度需补齐一个字节
+        output.extend_from_slice(b"ICCP");
+        output.extend_from_slice(&(icc.len() as u32).to_le_bytes());
+        output.extend_from_slice(icc);
+        if icc.len() % 2 == 1 {
+            output.push(0);
+        }
+        output.extend_from_slice(chunks);
+
+        let riff_size = (output.len() - 8) as u32;
+        output[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    /// 指定长度的伪ICC数据，奇数长度可检查WebP的补齐字节
+    fn fake_icc(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn encode(img: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut data = Vec::new();
+        img.write_to(&mut Cursor::new(&mut data), format).unwrap();
+        data
+    }
+
+    fn assert_round_trip(data: Vec<u8>, format: ImageFormat, icc: &[u8]) {
+        let embedded = ColorProfile::embed(data, format, icc).unwrap();
+        assert_eq!(ColorProfile::read(&embedded).as_deref(), Some(icc));
+
+        let decoded = image::load_from_memory_with_format(&embedded, format).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (5, 3));
+    }
+
+    #[test]
+    fn jpeg_embed_round_trips() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(5, 3, Rgb([200, 40, 40])));
+        assert_round_trip(
+            encode(&img, ImageFormat::Jpeg),
+            ImageFormat::Jpeg,
+            &fake_icc(301),
+        );
+        // 超出单个APP2段容量时分段存放
+        assert_round_trip(
+            encode(&img, ImageFormat::Jpeg),
+            ImageFormat::Jpeg,
+            &fake_icc(JPEG_ICC_CHUNK_SIZE * 2 + 7),
+        );
+    }
+
+    #[test]
+    fn png_embed_round_trips() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(5, 3, Rgba([0, 120, 255, 128])));
+        assert_round_trip(
+            encode(&img, ImageFormat::Png),
+            ImageFormat::Png,
+            &fake_icc(301),
+        );
+    }
+
+    #[test]
+    fn webp_embed_round_trips() {
+        let icc = fake_icc(301);
+
+        // 无损（VP8L）
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(5, 3, Rgba([0, 120, 255, 128])));
+        let lossless = encode(&img, ImageFormat::WebP);
+        assert_eq!(&lossless[12..16], b"VP8L");
+        assert_round_trip(lossless, ImageFormat::WebP, &icc);
+
+        // 有损（VP8）
+        let rgb = RgbImage::from_pixel(5, 3, Rgb([200, 40, 40]));
+        let lossy = webp::Encoder::from_rgb(rgb.as_raw(), 5, 3)
+            .encode(80.0)
+            .to_vec();
+        assert_eq!(&lossy[12..16], b"VP8 ");
+        assert_round_trip(lossy, ImageFormat::WebP, &icc);
+    }
+
+    #[test]
+    fn embed_rejects_unsupported_or_invalid_data() {
+        let icc = fake_icc(16);
+        assert!(ColorProfile::embed(vec![0; 64], ImageFormat::Gif, &icc).is_err());
+        assert!(ColorProfile::embed(vec![0; 64], ImageFormat::Jpeg, &icc).is_err());
+        assert!(ColorProfile::embed(vec![0; 64], ImageFormat::Png, &icc).is_err());
+        assert!(ColorProfile::embed(vec![0; 64], ImageFormat::WebP, &icc).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use image::RgbaImage;
+
+use crate::config::AppConfig;
+use crate::utils::AppError;
+
+/// 参与颜色统计的最低不透明度，更透明的像素忽略
+const MIN_OPAQUE_ALPHA: u8 = 128;
+
+/// 调色板颜色数上限
+const MAX_PALETTE_SIZE: usize = 16;
+
+/// 图片的主色调和调色板
+pub struct ColorSummary {
+    /// 主色调（像素最多的颜色簇）
+    pub dominant: [u8; 3],
+    /// 调色板，按像素数从多到少排列
+    pub palette: Vec<[u8; 3]>,
+}
+
+/// 颜色簇
+struct ColorBucket {
+    pixels: Vec<[u8; 3]>,
+}
+
+impl ColorBucket {
+    /// 返回取值范围最大的通道及其范围
+    fn widest_channel(&self) -> (usize, u8) {
+        (0..3)
+            .map(|channel| {
+                let (min, max) = self
+                    .pixels
+                    .iter()
+                    .fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                        (min.min(pixel[channel]), max.max(pixel[channel]))
+                    });
+                (channel, max.saturating_sub(min))
+            })
+            .max_by_key(|(_, range)| *range)
+            .unwrap_or((0, 0))
+    }
+
+    /// 簇内像素的平均颜色
+    fn average(&self) -> [u8; 3] {
+        let count = self.pixels.len().max(1) as u64;
+        let mut sum = [0u64; 3];
+        for pixel in &self.pixels {
+            for channel in 0..3 {
+                sum[channel] += pixel[channel] as u64;
+            }
+        }
+        sum.map(|value| ((value + count / 2) / count) as u8)
+    }
+}
+
+/// 颜色提取服务
+pub struct ColorService;
+
+impl ColorService {
+    /// 启动时校验颜色提取配置
+    pub fn validate_config(config: &AppConfig) -> Result<(), AppError> {
+        if !(1..=MAX_PALETTE_SIZE).contains(&config.colors.palette_size) {
+            return Err(AppError::Internal(format!(
+                "调色板颜色数必须在1-{}之间",
+                MAX_PALETTE_SIZE
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// 使用中位切分法提取主色调和调色板
+    ///
+    /// 输入应为缩小后的图片；完全透明的图片返回None
+    pub fn extract(img: &RgbaImage, palette_size: usize) -> Option<ColorSummary> {
+        let pixels: Vec<[u8; 3]> = img
+            .pixels()
+            .filter(|pixel| pixel[3] >= MIN_OPAQUE_ALPHA)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect();
+        if pixels.is_empty() {
+            return None;
+        }
+
+        // 反复切分取值范围最大的簇，直到达到目标数量或无法再切分
+        let mut buckets = vec![ColorBucket { pixels }];
+        while buckets.len() < palette_size {
+            let Some((index, channel)) = buckets
+                .iter()
+                .enumerate()
+                .filter(|(_, bucket)| bucket.pixels.len() >= 2)
+                .map(|(index, bucket)| (index, bucket.widest_channel()))
+                .filter(|(_, (_, range))| *range > 0)
+                .max_by_key(|(_, (_, range))| *range)
+                .map(|(index, (channel, _))| (index, channel))
+            else {
+                break;
+            };
+
+            let mut pixels = std::mem::take(&mut buckets[index].pixels);
+            pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+            let upper = pixels.split_off(pixels.len() / 2);
+            buckets[index].pixels = pixels;
+            buckets.push(ColorBucket { pixels: upper });
+        }
+
+        buckets.sort_by_key(|bucket| std::cmp::Reverse(bucket.pixels.len()));
+        let mut palette: Vec<[u8; 3]> = Vec::with_capacity(buckets.len());
+        for color in buckets.iter().map(ColorBucket::average) {
+            if !palette.contains(&color) {
+                palette.push(color);
+            }
+        }
+
+        Some(ColorSummary {
+            dominant: palette[0],
+            palette,
+        })
+    }
+
+    /// 格式化为 #rrggbb
+    pub fn to_hex(color: [u8; 3]) -> String {
+        format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+    }
+}
@@ -0,0 +1,631 @@
+use base64::{engine::general_purpose, Engine as _};
+
+use crate::config::AppConfig;
+use crate::models::{CropRegion, ImageTransformParams};
+use crate::services::PresetService;
+use crate::utils::AppError;
+
+/// 源地址中可去除的图片扩展名
+const IMAGE_EXTENSIONS: [&str; 8] = ["jpg", "jpeg", "png", "gif", "webp", "avif", "ico", "bmp"];
+
+/// imgproxy/thumbor兼容URL解析的中间结果
+#[derive(Default)]
+struct CompatParams {
+    /// 预设名称，最先展开以便其他参数覆盖
+    presets: Vec<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    /// 适配模式，只在同时指定宽高时生效
+    fit: Option<&'static str>,
+    /// 其他参数，按出现顺序排列
+    tokens: Vec<String>,
+    /// 手动裁剪区域，在其他操作之前执行
+    crop: Option<CropRegion>,
+}
+
+impl CompatParams {
+    /// 生成 @ 路径语法的参数字符串
+    fn into_params_string(self) -> Result<String, AppError> {
+        let mut parts = self.presets;
+        if let Some(width) = self.width {
+            parts.push(format!("w{}", width));
+        }
+        if let Some(height) = self.height {
+            parts.push(format!("h{}", height));
+        }
+        // 只指定一边时按比例缩放，与imgproxy/thumbor的行为一致
+        if let (Some(fit), Some(_), Some(_)) = (self.fit, self.width, self.height) {
+            parts.push(fit.to_string());
+        }
+        parts.extend(self.tokens);
+        let params = parts.join("_");
+        let Some(crop) = self.crop else {
+            return Ok(params);
+        };
+
+        // 其余操作移入裁剪之后的阶段，预设需要先展开，否则解析时会被当作未知参数
+        let params = PresetService::expand(&params)?;
+        let mut parsed = ImageTransformParams::parse(&params).map_err(AppError::BadRequest)?;
+        parsed.prepend_crop(crop);
+        Ok(parsed.to_normalized_string())
+    }
+}
+
+/// imgproxy/thumbor风格URL的兼容解析
+///
+/// 将URL中的处理选项映射为 @ 路径语法的参数字符串，签名段不做校验，
+/// 访问控制与 /images 接口相同
+pub struct CompatUrl;
+
+impl CompatUrl {
+    /// 启动时校验兼容路由配置
+    pub fn validate_config(config: &AppConfig) -> Result<(), AppError> {
+        let compat = &config.compat;
+        for prefix in [&compat.imgproxy_prefix, &compat.thumbor_prefix] {
+            if !prefix.is_empty() && !prefix.starts_with('/') {
+                return Err(AppError::Internal(format!(
+                    "兼容路由前缀必须以/开头: {}",
+                    prefix
+                )));
+            }
+        }
+        if !compat.imgproxy_prefix.is_empty() && compat.imgproxy_prefix == compat.thumbor_prefix {
+            return Err(AppError::Internal(
+                "imgproxy和thumbor兼容路由不能使用相同的前缀".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 解析imgproxy风格的路径（签名段之后的部分），返回图片标识符和参数字符串
+    ///
+    /// 支持 {选项}/plain/{源地址}[@格式] 和 {选项}/{base64url源地址}[.格式] 两种写法
+    pub fn imgproxy(path: &str) -> Result<(String, String), AppError> {
+        let mut params = CompatParams::default();
+        let segments: Vec<&str> = path.split('/').collect();
+
+        let mut index = 0;
+        while index < segments.len() && segments[index].contains(':') {
+            Self::imgproxy_option(&mut params, segments[index])?;
+            index += 1;
+        }
+
+        let rest = &segments[index..];
+        let source = match rest.first() {
+            Some(&"plain") => {
+                let source = rest[1..].join("/");
+                match source.rsplit_once('@') {
+                    Some((source, format)) => {
+                        params.tokens.push(Self::format_token(format)?);
+                        source.to_string()
+                    }
+                    None => source,
+                }
+            }
+            Some(_) => {
+                let encoded = rest.concat();
+                let encoded = match encoded.rsplit_once('.') {
+                    Some((encoded, format)) => {
+                        params.tokens.push(Self::format_token(format)?);
+                        encoded.to_string()
+                    }
+                    None => encoded,
+                };
+                general_purpose::URL_SAFE_NO_PAD
+                    .decode(encoded.trim_end_matches('='))
+                    .ok()
+                    .and_then(|bytes| String::from_utf8(bytes).ok())
+                    .ok_or_else(|| AppError::BadRequest("无效的base64源地址".to_string()))?
+            }
+            None => return Err(AppError::BadRequest("缺少图片源地址".to_string())),
+        };
+
+        Ok((Self::identifier(&source)?, params.into_params_string()?))
+    }
+
+    /// 解析单个imgproxy处理选项（名称:参数1:参数2...）
+    fn imgproxy_option(params: &mut CompatParams, option: &str) -> Result<(), AppError> {
+        let mut args = option.split(':');
+        let name = args.next().unwrap_or_default();
+        let args: Vec<&str> = args.collect();
+        let arg = |index: usize| args.get(index).copied().filter(|arg| !arg.is_empty());
+
+        match name {
+            "resize" | "rs" => {
+                if let Some(kind) = arg(0) {
+                    params.fit = Some(Self::imgproxy_resizing_type(kind)?);
+                }
+                params.width = Self::dimension(arg(1))?.or(params.width);
+                params.height = Self::dimension(arg(2))?.or(params.height);
+            }
+            "size" | "s" => {
+                params.width = Self::dimension(arg(0))?.or(params.width);
+                params.height = Self::dimension(arg(1))?.or(params.height);
+            }
+            "resizing_type" | "rt" => {
+                params.fit = Some(Self::imgproxy_resizing_type(arg(0).unwrap_or_default())?);
+            }
+            "width" | "w" => params.width = Self::dimension(arg(0))?,
+            "height" | "h" => params.height = Self::dimension(arg(0))?,
+            "gravity" | "g" => {
+                let gravity = match arg(0).unwrap_or_default() {
+                    "ce" => "gc".to_string(),
+                    "no" => "gn".to_string(),
+                    "so" => "gs".to_string(),
+                    "ea" => "ge".to_string(),
+                    "we" => "gw".to_string(),
+                    "noea" => "gne".to_string(),
+                    "nowe" => "gnw".to_string(),
+                    "soea" => "gse".to_string(),
+                    "sowe" => "gsw".to_string(),
+                    "sm" => "gsmart".to_string(),
+                    "fp" => format!(
+                        "g{}x{}",
+                        Self::number(arg(1), "焦点坐标")?,
+                        Self::number(arg(2), "焦点坐标")?
+                    ),
+                    other => {
+                        return Err(AppError::BadRequest(format!(
+                            "不支持的imgproxy对齐方式: {}",
+                            other
+                        )))
+                    }
+                };
+                params.tokens.push(gravity);
+            }
+            "quality" | "q" => {
+                let quality = Self::number(arg(0), "质量")?;
+                // imgproxy中0表示使用默认质量
+                if quality != 0.0 {
+                    params.tokens.push(format!("q{}", quality));
+                }
+            }
+            "format" | "f" | "ext" => {
+                params
+                    .tokens
+                    .push(Self::format_token(arg(0).unwrap_or_default())?);
+            }
+            "blur" | "bl" => {
+                params
+                    .tokens
+                    .push(format!("blur{}", Self::number(arg(0), "模糊半径")?));
+            }
+            "sharpen" | "sh" => {
+                params
+                    .tokens
+                    .push(format!("sharp{}", Self::number(arg(0), "锐化半径")?));
+            }
+            "rotate" | "rot" => {
+                params
+                    .tokens
+                    .push(format!("r{}", Self::number(arg(0), "旋转角度")?));
+            }
+            "background" | "bg" => {
+                let color = match args.as_slice() {
+                    [hex] => hex.trim_start_matches('#').to_string(),
+                    [r, g, b] => {
+                        let channel = |value: &str| {
+                            value.parse::<u8>().map_err(|_| {
+                                AppError::BadRequest(format!("无效的背景色: {}", option))
+                            })
+                        };
+                        format!("{:02x}{:02x}{:02x}", channel(r)?, channel(g)?, channel(b)?)
+                    }
+                    _ => return Err(AppError::BadRequest(format!("无效的背景色: {}", option))),
+                };
+                params.tokens.push(format!("na#{}", color));
+            }
+            "dpr" => params
+                .tokens
+                .push(format!("dpr{}", Self::number(arg(0), "设备像素比")?)),
+            "preset" | "pr" => params.presets.extend(
+                args.iter()
+                    .filter(|name| !name.is_empty())
+                    .map(|name| name.to_string()),
+            ),
+            "padding" | "pd" => {
+                let values: Vec<&str> = args.iter().copied().filter(|v| !v.is_empty()).collect();
+                if values.windows(2).any(|pair| pair[0] != pair[1]) {
+                    return Err(AppError::BadRequest("仅支持四边相同的留白".to_string()));
+                }
+                if let Some(padding) = values.first() {
+                    params.tokens.push(format!("pad{}", padding));
+                }
+            }
+            // 不影响输出内容的选项直接忽略
+            "cachebuster"
+            | "cb"
+            | "strip_metadata"
+            | "sm"
+            | "strip_color_profile"
+            | "scp"
+            | "keep_copyright"
+            | "kcr"
+            | "filename"
+            | "fn"
+            | "enlarge"
+            | "el"
+            | "extend"
+            | "ex"
+            | "return_attachment"
+            | "att" => {}
+            _ => {
+                return Err(AppError::BadRequest(format!(
+                    "不支持的imgproxy参数: {}",
+                    name
+                )))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// imgproxy缩放类型映射为适配模式
+    fn imgproxy_resizing_type(kind: &str) -> Result<&'static str, AppError> {
+        match kind {
+            "fit" => Ok("fit"),
+            "fill" | "fill-down" | "auto" => Ok("fill"),
+            "force" => Ok("scale"),
+            _ => Err(AppError::BadRequest(format!(
+                "不支持的imgproxy缩放类型: {}",
+                kind
+            ))),
+        }
+    }
+
+    /// 解析thumbor风格的路径（签名段之后的部分），返回图片标识符和参数字符串
+    ///
+    /// 格式：[fit-in/][-]宽x[-]高/[水平对齐/][垂直对齐/][smart/][filters:名称(参数):.../]图片
+    pub fn thumbor(path: &str) -> Result<(String, String), AppError> {
+        let mut params = CompatParams::default();
+        let mut fit_in = false;
+        let mut smart = false;
+        let mut halign = None;
+        let mut valign = None;
+
+        let segments: Vec<&str> = path.split('/').collect();
+        let mut index = 0;
+        while let Some(&segment) = segments.get(index) {
+            match segment {
+                "fit-in" | "full-fit-in" | "adaptive-fit-in" | "adaptive-full-fit-in" => {
+                    fit_in = true
+                }
+                "left" | "right" | "center" => halign = Some(segment),
+                "top" | "bottom" | "middle" => valign = Some(segment),
+                "smart" => smart = true,
+                "trim" | "meta" => {
+                    return Err(AppError::BadRequest(format!(
+                        "不支持的thumbor参数: {}",
+                        segment
+                    )))
+                }
+                _ if segment.starts_with("trim:") => {
+                    return Err(AppError::BadRequest(
+                        "不支持的thumbor参数: trim".to_string(),
+                    ))
+                }
+                _ if segment.starts_with("filters:") => {
+                    Self::thumbor_filters(&mut params, &segment["filters:".len()..])?
+                }
+                _ if segment.contains(':') => match Self::thumbor_crop(segment)? {
+                    Some(crop) => params.crop = Some(crop),
+                    // 剩余部分为图片地址
+                    None => break,
+                },
+                _ => match Self::thumbor_size(segment) {
+                    Some((width, height, flip_horizontal, flip_vertical)) => {
+                        params.width = width;
+                        params.height = height;
+                        if flip_horizontal {
+                            params.tokens.push("fliph".to_string());
+                        }
+                        if flip_vertical {
+                            params.tokens.push("flipv".to_string());
+                        }
+                    }
+                    // 剩余部分为图片地址
+                    None => break,
+                },
+            }
+            index += 1;
+        }
+
+        let source = segments[index.min(segments.len())..].join("/");
+        if source.is_empty() {
+            return Err(AppError::BadRequest("缺少图片源地址".to_string()));
+        }
+
+        // 不使用fit-in时thumbor按宽高裁剪
+        if fit_in {
+            params.fit = Some("fit");
+        } else {
+            params.fit = Some("fill");
+            let gravity = if smart {
+                "smart".to_string()
+            } else {
+                let vertical = match valign {
+                    Some("top") => "n",
+                    Some("bottom") => "s",
+                    _ => "",
+                };
+                let horizontal = match halign {
+                    Some("left") => "w",
+                    Some("right") => "e",
+                    _ => "",
+                };
+                format!("{}{}", vertical, horizontal)
+            };
+            if !gravity.is_empty() && params.width.is_some() && params.height.is_some() {
+                params.tokens.push(format!("g{}", gravity));
+            }
+        }
+
+        Ok((Self::identifier(&source)?, params.into_params_string()?))
+    }
+
+    /// 解析thumbor尺寸段（如 300x200、-300x0、orig），返回宽、高和翻转标志
+    fn thumbor_size(segment: &str) -> Option<(Option<u32>, Option<u32>, bool, bool)> {
+        let (width, height) = segment.split_once('x')?;
+        let parse = |value: &str| -> Option<(Option<u32>, bool)> {
+            let (value, flip) = match value.strip_prefix('-') {
+                Some(value) => (value, true),
+                None => (value, false),
+            };
+            // 0、空值或orig表示按比例或保持原尺寸
+            let size = match value {
+                "" | "orig" => None,
+                value => Some(value.parse::<u32>().ok()?).filter(|size| *size > 0),
+            };
+            Some((size, flip))
+        };
+        let (width, flip_horizontal) = parse(width)?;
+        let (height, flip_vertical) = parse(height)?;
+        Some((width, height, flip_horizontal, flip_vertical))
+    }
+
+    /// 解析thumbor手动裁剪段（左x上:右x下，如 10x20:300x400），返回裁剪区域
+    ///
+    /// 不是裁剪段时返回 None，右下角不在左上角右下方时返回错误
+    fn thumbor_crop(segment: &str) -> Result<Option<CropRegion>, AppError> {
+        let point = |part: &str| {
+            let (x, y) = part.split_once('x')?;
+            Some((x.parse::<u32>().ok()?, y.parse::<u32>().ok()?))
+        };
+        let Some(((left, top), (right, bottom))) = segment
+            .split_once(':')
+            .and_then(|(left, right)| Some((point(left)?, point(right)?)))
+        else {
+            return Ok(None);
+        };
+        if right <= left || bottom <= top {
+            return Err(AppError::BadRequest(format!(
+                "无效的thumbor裁剪区域: {}",
+                segment
+            )));
+        }
+
+        Ok(Some(CropRegion {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }))
+    }
+
+    /// 解析thumbor滤镜（名称(参数):名称(参数)）
+    fn thumbor_filters(params: &mut CompatParams, filters: &str) -> Result<(), AppError> {
+        for filter in filters.split(':').filter(|filter| !filter.is_empty()) {
+            let (name, args) = filter
+                .strip_suffix(')')
+                .and_then(|filter| filter.split_once('('))
+                .ok_or_else(|| AppError::BadRequest(format!("无效的thumbor滤镜: {}", filter)))?;
+            let args: Vec<&str> = args.split(',').map(str::trim).collect();
+            let arg = |index: usize| args.get(index).copied().filter(|arg| !arg.is_empty());
+
+            let token = match name {
+                "quality" => format!("q{}", Self::number(arg(0), "质量")?),
+                "format" => Self::format_token(arg(0).unwrap_or_default())?,
+                // 第二个参数为sigma，省略时与半径相同
+                "blur" => format!("blur{}", Self::number(arg(1).or(arg(0)), "模糊半径")?),
+                "sharpen" => format!("sharp{}", Self::number(arg(1), "锐化半径")?),
+                "grayscale" => "gray".to_string(),
+                "brightness" => format!("br{}", Self::number(arg(0), "亮度")?),
+                "contrast" => format!("ct{}", Self::number(arg(0), "对比度")?),
+                // thumbor按逆时针旋转
+                "rotate" => format!("r{}", -Self::number(arg(0), "旋转角度")?),
+                "fill" | "background_color" => match arg(0).unwrap_or_default() {
+                    "white" => "naw".to_string(),
+                    "black" => "nab".to_string(),
+                    // 透明或自动填充不需要处理
+                    "transparent" | "auto" | "blur" => continue,
+                    color => format!("na#{}", color.trim_start_matches('#')),
+                },
+                "round_corner" => {
+                    let radius = arg(0).unwrap_or_default();
+                    let radius = radius.split('|').next().unwrap_or_default();
+                    format!("rc{}", Self::number(Some(radius), "圆角半径")?)
+                }
+                "strip_icc" => "noicc".to_string(),
+                // 不影响输出内容的滤镜直接忽略
+                "no_upscale" | "upscale" | "strip_exif" | "cover" => continue,
+                _ => {
+                    return Err(AppError::BadRequest(format!(
+                        "不支持的thumbor滤镜: {}",
+                        name
+                    )))
+                }
+            };
+            params.tokens.push(token);
+        }
+
+        Ok(())
+    }
+
+    /// 校验格式名称并生成格式参数
+    fn format_token(format: &str) -> Result<String, AppError> {
+        match format.to_lowercase().as_str() {
+            format @ ("jpeg" | "jpg" | "png" | "gif" | "webp" | "avif" | "ico") => {
+                Ok(format.to_string())
+            }
+            _ => Err(AppError::BadRequest(format!(
+                "不支持的输出格式: {}",
+                format
+            ))),
+        }
+    }
+
+    /// 解析尺寸，0或省略表示按比例计算
+    fn dimension(value: Option<&str>) -> Result<Option<u32>, AppError> {
+        value
+            .map(|value| {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| AppError::BadRequest(format!("无效的尺寸: {}", value)))
+            })
+            .transpose()
+            .map(|size| size.filter(|size| *size > 0))
+    }
+
+    /// 解析数值参数
+    fn number(value: Option<&str>, name: &str) -> Result<f32, AppError> {
+        value
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| AppError::BadRequest(format!("无效的{}参数", name)))
+    }
+
+    /// 从源地址中取出图片标识符，去除查询字符串和图片扩展名
+    ///
+    /// 只接受本服务的图片：裸标识符、/images/{标识符} 或 local:///{标识符}，
+    /// 其他主机或路径的源地址直接拒绝，避免误返回本地同名图片
+    fn identifier(source: &str) -> Result<String, AppError> {
+        let source = source.split(['?', '#']).next().unwrap_or_default();
+        let path = source.strip_prefix("local://").unwrap_or(source);
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let name = path.strip_prefix("images/").unwrap_or(path);
+        if name.contains(['/', ':']) {
+            return Err(AppError::BadRequest(format!(
+                "仅支持本服务的图片源地址（标识符、/images/标识符 或 local:///标识符）: {}",
+                source
+            )));
+        }
+
+        let name = match name.rsplit_once('.') {
+            Some((stem, extension))
+                if IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()) =>
+            {
+                stem
+            }
+            _ => name,
+        };
+
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(AppError::BadRequest(format!("无效的图片标识符: {}", name)));
+        }
+        Ok(name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn imgproxy(path: &str) -> (String, String) {
+        CompatUrl::imgproxy(path).unwrap()
+    }
+
+    fn thumbor(path: &str) -> (String, String) {
+        CompatUrl::thumbor(path).unwrap()
+    }
+
+    #[test]
+    fn imgproxy_plain_source_with_format() {
+        let (identifier, params) = imgproxy("rs:fill:300:200/g:sm/plain/l8NFwfZ@webp");
+        assert_eq!(identifier, "l8NFwfZ");
+        assert_eq!(params, "w300_h200_fill_gsmart_webp");
+    }
+
+    #[test]
+    fn imgproxy_base64_source() {
+        let (identifier, params) = imgproxy("rs:fit:600:0/bG9jYWw6Ly8vbDhORndmWg.jpg");
+        assert_eq!(identifier, "l8NFwfZ");
+        assert_eq!(params, "w600_jpg");
+    }
+
+    #[test]
+    fn imgproxy_accepts_local_image_paths() {
+        assert_eq!(imgproxy("w:100/plain/l8NFwfZ.png").0, "l8NFwfZ");
+        assert_eq!(imgproxy("w:100/plain/images/l8NFwfZ").0, "l8NFwfZ");
+        assert_eq!(imgproxy("w:100/plain//images/l8NFwfZ.jpg?v=2").0, "l8NFwfZ");
+        assert_eq!(imgproxy("w:100/plain/local:///l8NFwfZ").0, "l8NFwfZ");
+    }
+
+    #[test]
+    fn imgproxy_rejects_external_sources() {
+        for path in [
+            "w:100/plain/https://other-host/x/l8NFwfZ.jpg",
+            "w:100/plain/other/l8NFwfZ",
+            "w:100/plain/",
+            "w:100",
+        ] {
+            assert!(
+                matches!(CompatUrl::imgproxy(path), Err(AppError::BadRequest(_))),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn imgproxy_rejects_unknown_options() {
+        assert!(CompatUrl::imgproxy("trim:10/plain/l8NFwfZ").is_err());
+        assert!(CompatUrl::imgproxy("rs:crop:1:1/plain/l8NFwfZ").is_err());
+        assert!(CompatUrl::imgproxy("f:tiff/plain/l8NFwfZ").is_err());
+    }
+
+    #[test]
+    fn thumbor_size_flip_and_gravity() {
+        let (identifier, params) = thumbor("-300x200/top/left/l8NFwfZ");
+        assert_eq!(identifier, "l8NFwfZ");
+        assert_eq!(params, "w300_h200_fill_fliph_gnw");
+
+        let (_, params) = thumbor("fit-in/800x0/filters:quality(80):format(webp)/l8NFwfZ");
+        assert_eq!(params, "w800_q80_webp");
+    }
+
+    #[test]
+    fn thumbor_manual_crop() {
+        AppConfig::init_for_tests();
+        let (_, params) = thumbor("10x20:110x70/50x0/l8NFwfZ");
+        let parsed = ImageTransformParams::parse(&params).unwrap();
+        assert_eq!(parsed.to_normalized_string(), params);
+        assert_eq!(params, "crop10x20x100x50~w50");
+    }
+
+    #[test]
+    fn thumbor_rejects_inverted_crop() {
+        for path in [
+            "110x20:10x70/l8NFwfZ",
+            "10x70:110x20/l8NFwfZ",
+            "10x20:10x70/l8NFwfZ",
+        ] {
+            assert!(
+                matches!(CompatUrl::thumbor(path), Err(AppError::BadRequest(_))),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn thumbor_rejects_external_sources() {
+        assert!(CompatUrl::thumbor("300x200/https://other-host/x/l8NFwfZ.jpg").is_err());
+        assert!(CompatUrl::thumbor("300x200/other-host/l8NFwfZ").is_err());
+        assert_eq!(thumbor("300x200/images/l8NFwfZ.jpg").0, "l8NFwfZ");
+    }
+}
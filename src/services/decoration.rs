@@ -0,0 +1,195 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::models::MaskShape;
+
+/// 未指定时的边框颜色
+const DEFAULT_BORDER_COLOR: (u8, u8, u8) = (0, 0, 0);
+
+/// 蒙版、边框和留白
+///
+/// 依次执行：按蒙版裁出形状，沿形状外侧绘制边框，再在四周补充透明留白。
+/// 边缘按像素覆盖率做抗锯齿
+pub struct Decoration;
+
+impl Decoration {
+    /// 对单帧应用蒙版、边框和留白，未指定时原样返回
+    pub fn apply(
+        img: DynamicImage,
+        mask: Option<MaskShape>,
+        border: Option<u32>,
+        border_color: Option<(u8, u8, u8)>,
+        padding: Option<u32>,
+    ) -> DynamicImage {
+        let border = border.unwrap_or(0);
+        let padding = padding.unwrap_or(0);
+        if mask.is_none() && border == 0 && padding == 0 {
+            return img;
+        }
+
+        let (width, height) = img.dimensions();
+        let source = img.to_rgba8();
+        let (r, g, b) = border_color.unwrap_or(DEFAULT_BORDER_COLOR);
+        let border_color = [r as f32, g as f32, b as f32];
+
+        let outer_width = width + border * 2;
+        let outer_height = height + border * 2;
+        let mut canvas = RgbaImage::new(outer_width + padding * 2, outer_height + padding * 2);
+
+        for y in 0..outer_height {
+            for x in 0..outer_width {
+                // 以像素中心计算覆盖率
+                let px = x as f32 + 0.5;
+                let py = y as f32 + 0.5;
+                let inner = coverage(
+                    mask,
+                    px - border as f32,
+                    py - border as f32,
+                    width as f32,
+                    height as f32,
+                    0.0,
+                );
+                let outer = if border > 0 {
+                    coverage(
+                        mask,
+                        px,
+                        py,
+                        outer_width as f32,
+                        outer_height as f32,
+                        border as f32,
+                    )
+                } else {
+                    0.0
+                };
+
+                let pixel = match (x.checked_sub(border), y.checked_sub(border)) {
+                    (Some(sx), Some(sy)) if inner > 0.0 && sx < width && sy < height => {
+                        *source.get_pixel(sx, sy)
+                    }
+                    _ => Rgba([0, 0, 0, 0]),
+                };
+
+                // 图片与边框的覆盖区域互不重叠，按覆盖率相加合成
+                let image_alpha = pixel[3] as f32 / 255.0 * inner;
+                let border_alpha = (outer * (1.0 - inner)).max(0.0);
+                let alpha = (image_alpha + border_alpha).min(1.0);
+                if alpha <= 0.0 {
+                    continue;
+                }
+
+                let mut blended = [0u8; 4];
+                for channel in 0..3 {
+                    let value = (pixel[channel] as f32 * image_alpha
+                        + border_color[channel] * border_alpha)
+                        / alpha;
+                    blended[channel] = value.round().clamp(0.0, 255.0) as u8;
+                }
+                blended[3] = (alpha * 255.0).round() as u8;
+                canvas.put_pixel(x + padding, y + padding, Rgba(blended));
+            }
+        }
+
+        DynamicImage::ImageRgba8(canvas)
+    }
+}
+
+/// 点(x, y)在宽高为width×height的形状内的覆盖率（0-1）
+///
+/// expand 为边框宽度：外轮廓的圆角半径相应增大，使边框宽度处处一致
+fn coverage(mask: Option<MaskShape>, x: f32, y: f32, width: f32, height: f32, expand: f32) -> f32 {
+    let half_width = width / 2.0;
+    let half_height = height / 2.0;
+    let dx = x - half_width;
+    let dy = y - half_height;
+
+    let distance = match mask {
+        Some(MaskShape::Circle) => ellipse_distance(dx, dy, half_width, half_height),
+        Some(MaskShape::Rounded(radius)) => {
+            rounded_rect_distance(dx, dy, half_width, half_height, radius as f32 + expand)
+        }
+        None => rounded_rect_distance(dx, dy, half_width, half_height, 0.0),
+    };
+
+    (0.5 - distance).clamp(0.0, 1.0)
+}
+
+/// 到圆角矩形边缘的有向距离（内部为负）
+fn rounded_rect_distance(x: f32, y: f32, half_width: f32, half_height: f32, radius: f32) -> f32 {
+    let radius = radius.min(half_width).min(half_height);
+    let qx = x.abs() - (half_width - radius);
+    let qy = y.abs() - (half_height - radius);
+    let outside = (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt();
+    outside + qx.max(qy).min(0.0) - radius
+}
+
+/// 到椭圆边缘的近似有向距离（内部为负）
+fn ellipse_distance(x: f32, y: f32, a: f32, b: f32) -> f32 {
+    let k0 = ((x / a).powi(2) + (y / b).powi(2)).sqrt();
+    if k0 == 0.0 {
+        return -a.min(b);
+    }
+    let k1 = ((x / (a * a)).powi(2) + (y / (b * b)).powi(2)).sqrt();
+    k0 * (k0 - 1.0) / k1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+    fn red(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, RED))
+    }
+
+    #[test]
+    fn no_decoration_returns_input() {
+        let img = DynamicImage::ImageRgb8(red(4, 3).to_rgb8());
+        let output = Decoration::apply(img.clone(), None, Some(0), None, None);
+        assert_eq!(output, img);
+    }
+
+    #[test]
+    fn border_and_padding_extend_canvas() {
+        let output =
+            Decoration::apply(red(10, 6), None, Some(2), Some((0, 0, 255)), Some(3)).to_rgba8();
+        assert_eq!(output.dimensions(), (10 + 2 * 2 + 3 * 2, 6 + 2 * 2 + 3 * 2));
+
+        // 留白透明，边框为指定颜色，中间保留原图
+        assert_eq!(output.get_pixel(0, 0)[3], 0);
+        assert_eq!(output.get_pixel(19, 15)[3], 0);
+        assert_eq!(*output.get_pixel(3, 3), Rgba([0, 0, 255, 255]));
+        assert_eq!(*output.get_pixel(4, 10), Rgba([0, 0, 255, 255]));
+        assert_eq!(*output.get_pixel(5, 5), RED);
+        assert_eq!(*output.get_pixel(14, 10), RED);
+    }
+
+    #[test]
+    fn circle_mask_clears_corners() {
+        let output =
+            Decoration::apply(red(20, 20), Some(MaskShape::Circle), None, None, None).to_rgba8();
+        assert_eq!(output.dimensions(), (20, 20));
+        for (x, y) in [(0, 0), (19, 0), (0, 19), (19, 19)] {
+            assert_eq!(output.get_pixel(x, y)[3], 0);
+        }
+        assert_eq!(*output.get_pixel(10, 10), RED);
+        // 边缘像素按覆盖率抗锯齿
+        assert!((1..255).contains(&output.get_pixel(0, 10)[3]));
+    }
+
+    #[test]
+    fn rounded_border_follows_mask() {
+        let output = Decoration::apply(
+            red(20, 20),
+            Some(MaskShape::Rounded(6)),
+            Some(2),
+            None,
+            None,
+        )
+        .to_rgba8();
+        assert_eq!(output.dimensions(), (24, 24));
+        // 外轮廓同样为圆角，边框沿直边为默认黑色
+        assert_eq!(output.get_pixel(0, 0)[3], 0);
+        assert_eq!(*output.get_pixel(0, 12), Rgba([0, 0, 0, 255]));
+        assert_eq!(*output.get_pixel(12, 12), RED);
+    }
+}
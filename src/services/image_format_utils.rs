@@ -1,6 +1,21 @@
-use crate::models::ImageTransformParams;
+use crate::models::{
+    FrameSelection, IccMode, ImageFilters, ImageTransformParams, MaskShape, TransformOperation,
+    WatermarkParams,
+};
 use crate::utils::AppError;
-use image::ImageFormat;
+use image::{ImageDecoder, ImageFormat};
+
+/// 帧序列图最多包含的帧数
+const MAX_SPRITE_FRAMES: u32 = 100;
+
+/// 目标体积参数的上限（KB）
+const MAX_TARGET_KB: u64 = 100 * 1024;
+
+/// 占位图的最大边长
+const MAX_PLACEHOLDER_SIZE: u32 = 256;
+
+/// 边框宽度和留白的上限（像素）
+const MAX_DECORATION_SIZE: u32 = 1000;
 
 /// 图片格式工具函数
 pub struct ImageFormatUtils;
@@ -21,6 +36,11 @@ impl ImageFormatUtils {
         }
     }
 
+    /// 目标格式是否可以输出动画
+    pub fn supports_animation(format: &ImageFormat) -> bool {
+        matches!(format, ImageFormat::Gif | ImageFormat::WebP)
+    }
+
     /// 检测GIF是否为动画（包含多帧）
     fn is_gif_animated(data: &[u8]) -> bool {
         // 使用gif库解析检测
@@ -78,6 +98,95 @@ impl ImageFormatUtils {
         }
     }
 
+    /// 根据Accept请求头和源图特征协商输出格式
+    ///
+    /// 依次选择AVIF、WebP，都不支持时有透明通道的图片输出PNG，否则输出JPEG；
+    /// 保留动画时只在WebP和GIF之间选择，要求无损压缩时优先WebP，限制体积时不输出PNG
+    pub fn negotiate_format(
+        accept: &str,
+        original_mime: &str,
+        data: &[u8],
+        params: &ImageTransformParams,
+    ) -> &'static str {
+        let accepts = |mime: &str| {
+            accept.split(',').any(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                parts.next().is_some_and(|m| m.eq_ignore_ascii_case(mime))
+                    && !parts.any(|p| matches!(p, "q=0" | "q=0.0" | "q=0.00" | "q=0.000"))
+            })
+        };
+
+        if params.frame.is_none() && Self::is_animated_format(original_mime, data) {
+            return if accepts("image/webp") { "webp" } else { "gif" };
+        }
+
+        if params.lossless && accepts("image/webp") {
+            "webp"
+        } else if accepts("image/avif") && params.icc_mode != Some(IccMode::Keep) {
+            "avif"
+        } else if accepts("image/webp") {
+            "webp"
+        } else if !params.no_alpha
+            && params.max_bytes.is_none()
+            && (params.produces_transparency() || Self::has_alpha(data))
+        {
+            "png"
+        } else {
+            "jpeg"
+        }
+    }
+
+    /// 丢弃协商结果不支持的编码参数
+    ///
+    /// auto 格式下的质量、目标体积等参数只对支持它们的候选格式生效，协商回退到其他格式时忽略，
+    /// 而不是让请求失败（如不支持WebP的浏览器请求动图 auto_q80 时输出GIF）
+    pub fn drop_unsupported_encoding_params(
+        format: &ImageFormat,
+        params: &mut ImageTransformParams,
+    ) {
+        if matches!(format, ImageFormat::Gif) {
+            params.quality = None;
+        }
+        if !matches!(format, ImageFormat::WebP) {
+            params.lossless = false;
+        }
+        if !matches!(format, ImageFormat::Avif) {
+            params.speed = None;
+        }
+        if !matches!(format, ImageFormat::Jpeg) {
+            params.progressive = false;
+            params.chroma_subsampling = None;
+            params.optimize_huffman = false;
+        }
+        if !matches!(
+            format,
+            ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Avif
+        ) {
+            params.max_bytes = None;
+        }
+        if !matches!(format, ImageFormat::Png) {
+            params.palette = None;
+            params.no_dither = false;
+        }
+        if params.icc_mode == Some(IccMode::Keep)
+            && !matches!(
+                format,
+                ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP
+            )
+        {
+            params.icc_mode = None;
+        }
+    }
+
+    /// 检测图片是否包含透明通道（只读取文件头）
+    fn has_alpha(data: &[u8]) -> bool {
+        image::ImageReader::new(std::io::Cursor::new(data))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_decoder().ok())
+            .is_some_and(|decoder| decoder.color_type().has_alpha())
+    }
+
     /// 检测目标格式是否不支持透明通道
     pub fn format_requires_no_alpha(format: &ImageFormat) -> bool {
         matches!(format, ImageFormat::Jpeg)
@@ -123,31 +232,159 @@ impl ImageFormatUtils {
         params: &ImageTransformParams,
         _is_target_format_specified: bool,
     ) -> Result<(), AppError> {
-        // AVIF格式特殊检查
-        if matches!(format, ImageFormat::Avif) && params.quality.is_some() {
-            return Err(AppError::BadRequest("AVIF格式暂不支持质量参数".to_string()));
-        }
-
         // GIF格式检查
         if matches!(format, ImageFormat::Gif) && params.quality.is_some() {
             return Err(AppError::BadRequest("GIF格式不支持质量参数".to_string()));
         }
 
+        if params.lossless && !matches!(format, ImageFormat::WebP) {
+            return Err(AppError::BadRequest(
+                "无损压缩参数仅支持WebP格式".to_string(),
+            ));
+        }
+
+        if params.speed.is_some() && !matches!(format, ImageFormat::Avif) {
+            return Err(AppError::BadRequest(
+                "编码速度参数仅支持AVIF格式".to_string(),
+            ));
+        }
+
+        if (params.progressive || params.chroma_subsampling.is_some() || params.optimize_huffman)
+            && !matches!(format, ImageFormat::Jpeg)
+        {
+            return Err(AppError::BadRequest(
+                "渐进式、色度抽样和霍夫曼表参数仅支持JPEG格式".to_string(),
+            ));
+        }
+
+        if params.max_bytes.is_some()
+            && !matches!(
+                format,
+                ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Avif
+            )
+        {
+            return Err(AppError::BadRequest(
+                "目标体积参数仅支持JPEG、WebP和AVIF格式".to_string(),
+            ));
+        }
+
+        if params.palette.is_some() && !matches!(format, ImageFormat::Png) {
+            return Err(AppError::BadRequest(
+                "调色板量化参数仅支持PNG格式".to_string(),
+            ));
+        }
+
+        if params.icc_mode == Some(IccMode::Keep)
+            && !matches!(
+                format,
+                ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP
+            )
+        {
+            return Err(AppError::BadRequest(
+                "保留ICC配置文件仅支持JPEG、PNG和WebP格式".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 验证滤镜参数范围
+    fn validate_filters(filters: &ImageFilters) -> Result<(), AppError> {
+        for (name, value) in [
+            ("亮度", filters.brightness),
+            ("对比度", filters.contrast),
+            ("饱和度", filters.saturation),
+        ] {
+            if value.is_some_and(|v| !(-100..=100).contains(&v)) {
+                return Err(AppError::BadRequest(format!(
+                    "{}参数必须在-100到100之间",
+                    name
+                )));
+            }
+        }
+
+        if filters.blur.is_some_and(|v| v <= 0.0 || v > 100.0) {
+            return Err(AppError::BadRequest("模糊半径必须在0到100之间".to_string()));
+        }
+
+        if filters.sharpen.is_some_and(|v| v <= 0.0 || v > 20.0) {
+            return Err(AppError::BadRequest("锐化半径必须在0到20之间".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// 验证水印参数
+    pub fn validate_watermark(watermark: &WatermarkParams) -> Result<(), AppError> {
+        match (&watermark.image, &watermark.text) {
+            (None, None) => {
+                return Err(AppError::BadRequest(
+                    "水印需要指定图片(wmi)或文字(wmt)".to_string(),
+                ))
+            }
+            (Some(_), Some(_)) => {
+                return Err(AppError::BadRequest(
+                    "图片水印和文字水印不能同时使用".to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        if watermark
+            .text
+            .as_ref()
+            .is_some_and(|text| text.chars().count() > 200)
+        {
+            return Err(AppError::BadRequest(
+                "水印文字不能超过200个字符".to_string(),
+            ));
+        }
+        if watermark.opacity.is_some_and(|v| v == 0 || v > 100) {
+            return Err(AppError::BadRequest(
+                "水印不透明度必须在1-100之间".to_string(),
+            ));
+        }
+        if watermark.scale.is_some_and(|v| v == 0 || v > 100) {
+            return Err(AppError::BadRequest(
+                "水印缩放比例必须在1-100之间".to_string(),
+            ));
+        }
+        if watermark.font_size.is_some_and(|v| !(6..=512).contains(&v)) {
+            return Err(AppError::BadRequest(
+                "水印字号必须在6-512像素之间".to_string(),
+            ));
+        }
+        if watermark.margin.is_some_and(|v| v > 2000) {
+            return Err(AppError::BadRequest("水印边距不能超过2000像素".to_string()));
+        }
+
         Ok(())
     }
 
     /// 验证转换参数
     pub fn validate_params(params: &ImageTransformParams) -> Result<(), AppError> {
-        // 检查尺寸限制（提升到8K支持）
-        if let Some(width) = params.width {
-            if width == 0 || width > 8192 {
-                return Err(AppError::BadRequest("宽度必须在1-8192像素之间".to_string()));
-            }
+        // 按执行顺序逐个检查流水线中的操作
+        let operations = params.operations();
+        for operation in &operations {
+            Self::validate_operation(operation)?;
+        }
+        let watermarks = operations
+            .iter()
+            .filter(|operation| matches!(operation, TransformOperation::Watermark(_)))
+            .count();
+        if watermarks > 1 {
+            return Err(AppError::BadRequest("水印只能使用一次".to_string()));
         }
 
-        if let Some(height) = params.height {
-            if height == 0 || height > 8192 {
-                return Err(AppError::BadRequest("高度必须在1-8192像素之间".to_string()));
+        if let Some(FrameSelection::Sprite { count, columns }) = params.frame {
+            if count.is_some_and(|count| count == 0 || count > MAX_SPRITE_FRAMES) {
+                return Err(AppError::BadRequest(format!(
+                    "帧序列图的帧数必须在1-{}之间",
+                    MAX_SPRITE_FRAMES
+                )));
+            }
+            if columns == Some(0) {
+                return Err(AppError::BadRequest("帧序列图的列数必须大于0".to_string()));
             }
         }
 
@@ -158,6 +395,250 @@ impl ImageFormatUtils {
             }
         }
 
+        if let Some(speed) = params.speed {
+            if !(1..=10).contains(&speed) {
+                return Err(AppError::BadRequest("编码速度必须在1-10之间".to_string()));
+            }
+        }
+
+        if let Some(colors) = params.palette {
+            if !(2..=256).contains(&colors) {
+                return Err(AppError::BadRequest(
+                    "调色板颜色数必须在2-256之间".to_string(),
+                ));
+            }
+        }
+
+        if let Some(max_bytes) = params.max_bytes {
+            if max_bytes == 0 || max_bytes > MAX_TARGET_KB * 1024 {
+                return Err(AppError::BadRequest(format!(
+                    "目标体积必须在1-{}KB之间",
+                    MAX_TARGET_KB
+                )));
+            }
+            if params.lossless {
+                return Err(AppError::BadRequest(
+                    "目标体积参数不能与无损压缩同时使用".to_string(),
+                ));
+            }
+        }
+
+        if params.no_dither && params.palette.is_none() {
+            return Err(AppError::BadRequest(
+                "nodither参数需要配合pal参数使用".to_string(),
+            ));
+        }
+
+        if params.placeholder.is_some() {
+            Self::validate_placeholder(params)?;
+        } else if params.svg {
+            return Err(AppError::BadRequest(
+                "svg参数需要配合blurhash或thumbhash参数使用".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 验证流水线中的单个操作
+    fn validate_operation(operation: &TransformOperation) -> Result<(), AppError> {
+        match operation {
+            TransformOperation::Orient { .. } => {}
+            TransformOperation::Crop(region) => {
+                if region.width == 0 || region.height == 0 {
+                    return Err(AppError::BadRequest("裁剪区域的宽高必须大于0".to_string()));
+                }
+            }
+            TransformOperation::Resize {
+                width,
+                height,
+                fit_mode,
+                ..
+            } => {
+                // 检查尺寸限制（提升到8K支持）
+                if width.is_some_and(|width| width == 0 || width > 8192) {
+                    return Err(AppError::BadRequest("宽度必须在1-8192像素之间".to_string()));
+                }
+                if height.is_some_and(|height| height == 0 || height > 8192) {
+                    return Err(AppError::BadRequest("高度必须在1-8192像素之间".to_string()));
+                }
+
+                // 精确尺寸模式需要同时指定宽高
+                if fit_mode.is_exact() && (width.is_none() || height.is_none()) {
+                    return Err(AppError::BadRequest(format!(
+                        "{}模式需要同时指定宽度和高度",
+                        fit_mode.as_str()
+                    )));
+                }
+            }
+            TransformOperation::Filters(filters) => Self::validate_filters(filters)?,
+            TransformOperation::Watermark(watermark) => Self::validate_watermark(watermark)?,
+            TransformOperation::Decorate {
+                mask,
+                border,
+                border_color,
+                padding,
+            } => {
+                if *mask == Some(MaskShape::Rounded(0)) {
+                    return Err(AppError::BadRequest("圆角半径必须大于0".to_string()));
+                }
+                for (name, value) in [("边框宽度", border), ("留白", padding)] {
+                    if value.is_some_and(|v| v == 0 || v > MAX_DECORATION_SIZE) {
+                        return Err(AppError::BadRequest(format!(
+                            "{}必须在1-{}像素之间",
+                            name, MAX_DECORATION_SIZE
+                        )));
+                    }
+                }
+                if border_color.is_some() && border.is_none() {
+                    return Err(AppError::BadRequest(
+                        "bdc参数需要配合bd参数使用".to_string(),
+                    ));
+                }
+            }
+        }
+
         Ok(())
     }
+
+    /// 验证占位图参数：只能与宽高、svg和base64参数组合
+    fn validate_placeholder(params: &ImageTransformParams) -> Result<(), AppError> {
+        let allowed = ImageTransformParams {
+            width: params.width,
+            height: params.height,
+            placeholder: params.placeholder,
+            svg: params.svg,
+            base64_mode: params.base64_mode.clone(),
+            ..Default::default()
+        };
+        if allowed.to_normalized_string() != params.to_normalized_string() {
+            return Err(AppError::BadRequest(
+                "占位图参数只能与w、h、svg和base64参数组合使用".to_string(),
+            ));
+        }
+
+        if params
+            .width
+            .into_iter()
+            .chain(params.height)
+            .any(|size| size > MAX_PLACEHOLDER_SIZE)
+        {
+            return Err(AppError::BadRequest(format!(
+                "占位图尺寸不能超过{}像素",
+                MAX_PLACEHOLDER_SIZE
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{DynamicImage, Frame, Rgba, RgbaImage};
+    use std::io::Cursor;
+
+    const ALL: &str = "image/avif,image/webp,image/apng,image/*,*/*;q=0.8";
+    const LEGACY: &str = "image/png,image/jpeg,*/*;q=0.8";
+
+    fn encode(img: RgbaImage, format: ImageFormat) -> Vec<u8> {
+        let img = match format {
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(img).to_rgb8()),
+            _ => DynamicImage::ImageRgba8(img),
+        };
+        let mut data = Vec::new();
+        img.write_to(&mut Cursor::new(&mut data), format).unwrap();
+        data
+    }
+
+    fn animated_gif() -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut data);
+            for value in [0, 255] {
+                let frame = RgbaImage::from_pixel(4, 4, Rgba([value, value, value, 255]));
+                encoder.encode_frame(Frame::new(frame)).unwrap();
+            }
+        }
+        data
+    }
+
+    fn negotiate(accept: &str, mime: &str, data: &[u8], params: &str) -> &'static str {
+        let params = ImageTransformParams::parse(params).unwrap();
+        ImageFormatUtils::negotiate_format(accept, mime, data, &params)
+    }
+
+    #[test]
+    fn negotiate_prefers_modern_formats() {
+        let opaque = encode(
+            RgbaImage::from_pixel(4, 4, Rgba([9, 9, 9, 255])),
+            ImageFormat::Jpeg,
+        );
+        let transparent = encode(RgbaImage::new(4, 4), ImageFormat::Png);
+
+        assert_eq!(negotiate(ALL, "image/jpeg", &opaque, "auto"), "avif");
+        assert_eq!(
+            negotiate("image/webp", "image/jpeg", &opaque, "auto"),
+            "webp"
+        );
+        assert_eq!(negotiate(LEGACY, "image/jpeg", &opaque, "auto"), "jpeg");
+        assert_eq!(negotiate(LEGACY, "image/png", &transparent, "auto"), "png");
+        // 透明来自蒙版或留白时同样需要PNG
+        assert_eq!(
+            negotiate(LEGACY, "image/jpeg", &opaque, "circle_auto"),
+            "png"
+        );
+    }
+
+    #[test]
+    fn negotiate_respects_encoding_params() {
+        let opaque = encode(
+            RgbaImage::from_pixel(4, 4, Rgba([9, 9, 9, 255])),
+            ImageFormat::Jpeg,
+        );
+        let transparent = encode(RgbaImage::new(4, 4), ImageFormat::Png);
+
+        assert_eq!(
+            negotiate(ALL, "image/jpeg", &opaque, "lossless_auto"),
+            "webp"
+        );
+        assert_eq!(
+            negotiate(ALL, "image/jpeg", &opaque, "keepicc_auto"),
+            "webp"
+        );
+        assert_eq!(
+            negotiate(LEGACY, "image/png", &transparent, "na_auto"),
+            "jpeg"
+        );
+        assert_eq!(
+            negotiate(LEGACY, "image/png", &transparent, "kb50_auto"),
+            "jpeg"
+        );
+    }
+
+    #[test]
+    fn negotiate_ignores_zero_quality_entries() {
+        let opaque = encode(
+            RgbaImage::from_pixel(4, 4, Rgba([9, 9, 9, 255])),
+            ImageFormat::Jpeg,
+        );
+
+        let accept = "image/avif;q=0,image/webp;q=0.0,image/jpeg";
+        assert_eq!(negotiate(accept, "image/jpeg", &opaque, "auto"), "jpeg");
+        assert_eq!(
+            negotiate("IMAGE/WEBP; q=0.5", "image/jpeg", &opaque, "auto"),
+            "webp"
+        );
+    }
+
+    #[test]
+    fn negotiate_keeps_animation_unless_frame_selected() {
+        let gif = animated_gif();
+
+        assert_eq!(negotiate(ALL, "image/gif", &gif, "auto"), "webp");
+        assert_eq!(negotiate(LEGACY, "image/gif", &gif, "auto"), "gif");
+        assert_eq!(negotiate(ALL, "image/gif", &gif, "frame1_auto"), "avif");
+    }
 }
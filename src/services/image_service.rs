@@ -2,15 +2,46 @@ use chrono::Utc;
 use sha2::{Digest, Sha256};
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
+use tracing::{error, info, warn};
 
+use crate::config::AppConfig;
 use crate::database::DatabasePool;
-use crate::models::{ImageInfo, ImageQuery, ImageStats};
+use crate::models::{ImageInfo, ImageQuery, ImageStats, UploadOptions, Visibility};
 use crate::repositories::{ImageRepository, ImageRepositoryTrait};
+use crate::services::color_service::ColorSummary;
+use crate::services::placeholder_service::{PlaceholderHashes, MAX_HASH_INPUT_SIZE};
+use crate::services::static_image_transform::StaticImageTransform;
+use crate::services::{AccessService, CacheService, ColorService, PlaceholderService};
 use crate::utils::{
     detect_file_type, ensure_image_dir, ensure_upload_dir, get_extension_from_mime, get_file_path,
     get_upload_dir, validate_file_size, AppError,
 };
 
+/// 短ID的初始长度，冲突时逐位加长
+const SHORT_ID_MIN_LENGTH: usize = 7;
+
+/// 按hash前缀查找时要求的最小前缀长度
+const HASH_PREFIX_MIN_LENGTH: usize = 8;
+
+/// 每批清理的过期图片数量
+const EXPIRED_PURGE_BATCH_SIZE: u64 = 100;
+
+/// 达到查看上限的图片在最后一次访问后保留的秒数，避免删除仍在读取中的文件
+const VIEW_LIMIT_PURGE_GRACE_SECONDS: i64 = 60;
+
+/// 每批补算图片特征的图片数量
+const FEATURE_BACKFILL_BATCH_SIZE: u64 = 50;
+
+/// base62字符表
+const BASE62_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// 从图片内容计算的特征（占位图哈希和颜色信息）
+#[derive(Default)]
+struct ImageFeatures {
+    placeholders: Option<PlaceholderHashes>,
+    colors: Option<ColorSummary>,
+}
+
 /// 图片服务结构体
 pub struct ImageService;
 
@@ -22,8 +53,220 @@ impl ImageService {
         format!("{:x}", hasher.finalize())
     }
 
+    /// 将十六进制hash编码为base62字符串
+    fn encode_base62(hex_hash: &str) -> String {
+        // 把hash视为大整数，反复除以62取余
+        let mut digits: Vec<u32> = hex_hash.chars().filter_map(|c| c.to_digit(16)).collect();
+        let mut output = Vec::new();
+
+        while !digits.is_empty() {
+            let mut remainder = 0u32;
+            let mut quotient = Vec::with_capacity(digits.len());
+            for digit in &digits {
+                let acc = remainder * 16 + digit;
+                let q = acc / 62;
+                remainder = acc % 62;
+                if !quotient.is_empty() || q > 0 {
+                    quotient.push(q);
+                }
+            }
+            output.push(BASE62_ALPHABET[remainder as usize]);
+            digits = quotient;
+        }
+
+        output.reverse();
+        String::from_utf8(output).unwrap_or_default()
+    }
+
+    /// 为图片分配不冲突的短ID
+    async fn assign_short_id(image_repo: &ImageRepository, hash: &str) -> Result<String, AppError> {
+        let encoded = Self::encode_base62(hash);
+
+        for length in SHORT_ID_MIN_LENGTH..=encoded.len() {
+            let candidate = &encoded[..length];
+
+            // 短ID与别名共享查找空间，两者都不能冲突
+            if image_repo.find_by_short_id(candidate).await?.is_some()
+                || image_repo.find_by_alias(candidate).await?.is_some()
+            {
+                continue;
+            }
+
+            image_repo.update_short_id(hash, candidate).await?;
+            info!("分配短ID: {} -> {}", hash, candidate);
+            return Ok(candidate.to_string());
+        }
+
+        Err(AppError::Internal("无法生成唯一的短ID".to_string()))
+    }
+
+    /// 验证别名格式
+    fn validate_alias(alias: &str) -> Result<(), AppError> {
+        if alias.len() < 3 || alias.len() > 64 {
+            return Err(AppError::BadRequest(
+                "别名长度必须在3-64个字符之间".to_string(),
+            ));
+        }
+
+        if !alias
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(AppError::BadRequest(
+                "别名只能包含字母、数字、短横线和下划线".to_string(),
+            ));
+        }
+
+        // 纯十六进制的别名会与hash前缀查找产生歧义
+        if alias.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AppError::BadRequest(
+                "别名不能为纯十六进制字符串".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 检查别名是否可用（格式合法且未被占用）
+    async fn check_alias_available(
+        image_repo: &ImageRepository,
+        alias: &str,
+    ) -> Result<(), AppError> {
+        Self::validate_alias(alias)?;
+
+        if image_repo.find_by_alias(alias).await?.is_some()
+            || image_repo.find_by_short_id(alias).await?.is_some()
+        {
+            return Err(AppError::BadRequest(format!("别名已被占用: {}", alias)));
+        }
+
+        Ok(())
+    }
+
+    /// 为图片设置别名
+    async fn assign_alias(
+        image_repo: &ImageRepository,
+        image_info: &mut ImageInfo,
+        alias: &str,
+    ) -> Result<(), AppError> {
+        if image_info.alias.as_deref() == Some(alias) {
+            return Ok(());
+        }
+
+        Self::check_alias_available(image_repo, alias).await?;
+
+        image_repo.update_alias(&image_info.hash, alias).await?;
+        image_info.alias = Some(alias.to_string());
+        info!("设置图片别名: {} -> {}", image_info.hash, alias);
+
+        Ok(())
+    }
+
+    /// 返回图片缺少且已启用的特征：(占位图哈希, 颜色信息)
+    fn missing_features(image_info: &ImageInfo, backfill: bool) -> (bool, bool) {
+        let config = AppConfig::get();
+        let placeholders = config.placeholder.enabled
+            && (!backfill || config.placeholder.backfill_on_startup)
+            && (image_info.blurhash.is_none() || image_info.thumbhash.is_none());
+        let colors = config.colors.enabled
+            && (!backfill || config.colors.backfill_on_startup)
+            && image_info.dominant_color.is_none();
+        (placeholders, colors)
+    }
+
+    /// 计算图片特征，无法解码的图片记录警告后跳过
+    ///
+    /// 图片只解码一次，占位图哈希和颜色信息共用同一张缩略图
+    fn compute_features(
+        data: &[u8],
+        mime_type: &str,
+        hash: &str,
+        (placeholders, colors): (bool, bool),
+    ) -> ImageFeatures {
+        if !placeholders && !colors {
+            return ImageFeatures::default();
+        }
+
+        let thumbnail =
+            match StaticImageTransform::load_thumbnail(data, mime_type, MAX_HASH_INPUT_SIZE) {
+                Ok(thumbnail) => thumbnail,
+                Err(e) => {
+                    warn!("解码图片失败，跳过特征计算: {} - {}", hash, e);
+                    return ImageFeatures::default();
+                }
+            };
+
+        let placeholders = if placeholders {
+            PlaceholderService::compute(&thumbnail)
+                .inspect_err(|e| warn!("计算占位图哈希失败: {} - {}", hash, e))
+                .ok()
+        } else {
+            None
+        };
+        let colors = if colors {
+            ColorService::extract(&thumbnail, AppConfig::get().colors.palette_size)
+        } else {
+            None
+        };
+
+        ImageFeatures {
+            placeholders,
+            colors,
+        }
+    }
+
+    /// 将特征写入图片信息
+    fn apply_features(image_info: &mut ImageInfo, features: ImageFeatures) {
+        if let Some(hashes) = features.placeholders {
+            image_info.blurhash = Some(hashes.blurhash);
+            image_info.thumbhash = Some(hashes.thumbhash);
+        }
+        if let Some(colors) = features.colors {
+            image_info.dominant_color = Some(ColorService::to_hex(colors.dominant));
+            image_info.palette = colors
+                .palette
+                .into_iter()
+                .map(ColorService::to_hex)
+                .collect();
+        }
+    }
+
+    /// 保存特征并写回图片信息，返回是否有新的特征
+    async fn store_features(
+        image_repo: &ImageRepository,
+        image_info: &mut ImageInfo,
+        features: ImageFeatures,
+    ) -> Result<bool, AppError> {
+        if let Some(ref hashes) = features.placeholders {
+            image_repo
+                .update_placeholders(&image_info.hash, &hashes.blurhash, &hashes.thumbhash)
+                .await?;
+        }
+        if let Some(ref colors) = features.colors {
+            let palette = colors
+                .palette
+                .iter()
+                .map(|color| ColorService::to_hex(*color))
+                .collect::<Vec<_>>()
+                .join(",");
+            image_repo
+                .update_colors(&image_info.hash, colors.dominant, &palette)
+                .await?;
+        }
+
+        let stored = features.placeholders.is_some() || features.colors.is_some();
+        Self::apply_features(image_info, features);
+        Ok(stored)
+    }
+
     /// 保存上传的图片文件
-    pub async fn save_image(pool: &DatabasePool, data: &[u8]) -> Result<ImageInfo, AppError> {
+    pub async fn save_image(
+        pool: &DatabasePool,
+        data: &[u8],
+        options: &UploadOptions,
+    ) -> Result<ImageInfo, AppError> {
+        let alias = options.alias.as_deref();
+
         // 验证文件是否为空
         if data.is_empty() {
             return Err(AppError::InvalidFile);
@@ -41,10 +284,105 @@ impl ImageService {
         // 检查是否已存在相同文件
         let connection = pool.get_connection();
         let image_repo = ImageRepository::new(connection.clone());
-        if let Some(existing_image) = image_repo.find_by_hash(&file_hash).await? {
+        if let Some(mut existing_image) = image_repo.find_by_hash(&file_hash).await? {
+            // 旧数据可能没有短ID，重复上传时补齐
+            if existing_image.short_id.is_none() {
+                existing_image.short_id =
+                    Some(Self::assign_short_id(&image_repo, &file_hash).await?);
+            }
+            // 匿名重复上传只能为尚无别名的图片设置别名，不能替换已有别名
+            if let Some(alias) = alias {
+                if existing_image.alias.is_none() || options.trusted {
+                    Self::assign_alias(&image_repo, &mut existing_image, alias).await?;
+                } else if existing_image.alias.as_deref() != Some(alias) {
+                    info!(
+                        "图片已有别名，忽略匿名重复上传指定的别名: {} ({})",
+                        existing_image.hash, alias
+                    );
+                }
+            }
+
+            // 旧数据可能没有占位图哈希和颜色信息，重复上传时补齐
+            let missing = Self::missing_features(&existing_image, false);
+            let features = Self::compute_features(data, &mime_type, &file_hash, missing);
+            Self::store_features(&image_repo, &mut existing_image, features).await?;
+
+            // 重复上传不能放宽已有限制，避免通过再次上传复活已过期或阅后即焚的图片；
+            // 仅当携带API密钥且限制尚未生效时，取较晚的过期时间，任意一次为永久则永久保存
+            let relax_expiry = options.trusted && !existing_image.is_expired();
+            let merged_expires_at = match (existing_image.expires_at, options.expires_at) {
+                (existing, _) if !relax_expiry => existing,
+                (Some(existing), Some(requested)) => Some(existing.max(requested)),
+                _ => None,
+            };
+            if merged_expires_at != existing_image.expires_at {
+                image_repo
+                    .update_expires_at(&file_hash, merged_expires_at)
+                    .await?;
+                existing_image.expires_at = merged_expires_at;
+            }
+
+            // 查看次数上限同理：仅在未达到上限时取较大值，任意一次不限制则不限制
+            let relax_views = options.trusted && !existing_image.is_view_limit_reached();
+            let merged_max_views = match (existing_image.max_views, options.max_views) {
+                (existing, _) if !relax_views => existing,
+                (Some(existing), Some(requested)) => Some(existing.max(requested)),
+                _ => None,
+            };
+            if merged_max_views != existing_image.max_views {
+                image_repo
+                    .update_max_views(&file_hash, merged_max_views)
+                    .await?;
+                existing_image.max_views = merged_max_views;
+            }
+
+            // 访问控制仅在携带API密钥时取更严格的设置；匿名上传者即使持有相同文件，
+            // 也不能为已有图片设置密码或收紧可见性，避免锁住其他使用者
+            let (merged_visibility, merged_password_hash) = if options.trusted {
+                let requested_visibility = options.visibility.unwrap_or(Visibility::Public);
+                let merged_password_hash = match (&existing_image.password_hash, &options.password)
+                {
+                    (None, Some(password)) => Some(AccessService::hash_password(password)?),
+                    (existing, _) => existing.clone(),
+                };
+                (
+                    existing_image.visibility.max(requested_visibility),
+                    merged_password_hash,
+                )
+            } else {
+                (
+                    existing_image.visibility,
+                    existing_image.password_hash.clone(),
+                )
+            };
+            if merged_visibility != existing_image.visibility
+                || merged_password_hash != existing_image.password_hash
+            {
+                image_repo
+                    .update_access_control(
+                        &file_hash,
+                        merged_visibility,
+                        merged_password_hash.clone(),
+                    )
+                    .await?;
+                existing_image.visibility = merged_visibility;
+                existing_image.password_hash = merged_password_hash;
+            }
+
             return Ok(existing_image);
         }
 
+        // 提前校验别名，避免写入文件后才失败
+        if let Some(alias) = alias {
+            Self::check_alias_available(&image_repo, alias).await?;
+        }
+
+        let password_hash = options
+            .password
+            .as_deref()
+            .map(AccessService::hash_password)
+            .transpose()?;
+
         // 确保基础上传目录存在
         ensure_upload_dir().await?;
 
@@ -52,7 +390,7 @@ impl ImageService {
         let extension = get_extension_from_mime(&mime_type)?;
 
         // 创建图片信息
-        let image_info = ImageInfo {
+        let mut image_info = ImageInfo {
             hash: file_hash.clone(),
             size: data.len() as u64,
             mime_type,
@@ -60,8 +398,23 @@ impl ImageService {
             last_accessed: None,
             extension,
             access_count: 0,
+            short_id: None,
+            alias: None,
+            expires_at: options.expires_at,
+            max_views: options.max_views,
+            visibility: options.visibility.unwrap_or(Visibility::Public),
+            password_hash,
+            blurhash: None,
+            thumbhash: None,
+            dominant_color: None,
+            palette: Vec::new(),
         };
 
+        // 计算占位图哈希和颜色信息，随图片记录一起写入
+        let missing = Self::missing_features(&image_info, false);
+        let features = Self::compute_features(data, &image_info.mime_type, &file_hash, missing);
+        Self::apply_features(&mut image_info, features);
+
         // 计算文件路径
         let stored_name = image_info.stored_name();
         let relative_path = format!("{}/{}/{}", &file_hash[0..2], &file_hash[2..4], stored_name);
@@ -80,10 +433,18 @@ impl ImageService {
         // 保存到数据库
         image_repo.insert(&image_info).await?;
 
+        // 分配短ID和别名
+        image_info.short_id = Some(Self::assign_short_id(&image_repo, &file_hash).await?);
+        if let Some(alias) = alias {
+            Self::assign_alias(&image_repo, &mut image_info, alias).await?;
+        }
+
         Ok(image_info)
     }
 
-    /// 根据哈希值获取图片信息
+    /// 根据标识符获取图片信息
+    ///
+    /// 标识符依次按完整hash、别名、短ID、唯一hash前缀解析
     pub async fn get_image_info(
         pool: &DatabasePool,
         identifier: &str,
@@ -96,64 +457,250 @@ impl ImageService {
         let connection = pool.get_connection();
         let image_repo = ImageRepository::new(connection);
 
-        // 从数据库查询
-        image_repo.find_by_hash(identifier).await
+        // 完整hash
+        let is_hex = identifier.chars().all(|c| c.is_ascii_hexdigit());
+        if is_hex && identifier.len() == 64 {
+            return image_repo.find_by_hash(&identifier.to_lowercase()).await;
+        }
+
+        // 别名
+        if let Some(image_info) = image_repo.find_by_alias(identifier).await? {
+            return Ok(Some(image_info));
+        }
+
+        // 短ID
+        if let Some(image_info) = image_repo.find_by_short_id(identifier).await? {
+            return Ok(Some(image_info));
+        }
+
+        // 唯一hash前缀
+        if is_hex && identifier.len() >= HASH_PREFIX_MIN_LENGTH {
+            let mut matches = image_repo
+                .find_by_hash_prefix(&identifier.to_lowercase(), 2)
+                .await?;
+            return match matches.len() {
+                0 => Ok(None),
+                1 => Ok(matches.pop()),
+                _ => Err(AppError::BadRequest(format!(
+                    "hash前缀不唯一，请提供更长的前缀: {}",
+                    identifier
+                ))),
+            };
+        }
+
+        Ok(None)
     }
 
-    /// 读取图片文件内容
-    pub async fn read_image_file(
+    /// 记录一次查看，达到最大查看次数的图片返回410
+    ///
+    /// 在响应内容生成成功后调用，转换失败的请求不消耗查看次数
+    pub async fn record_view(pool: &DatabasePool, image_info: &ImageInfo) -> Result<(), AppError> {
+        let connection = pool.get_connection();
+        let image_repo = ImageRepository::new(connection);
+        match image_repo.update_access(&image_info.hash).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AppError::Gone("图片已达到最大查看次数".to_string())),
+            // 有查看次数上限的图片无法计数时拒绝提供，避免绕过阅后即焚
+            Err(e) if image_info.max_views.is_some() => {
+                error!("更新限次图片访问信息失败: {} - {}", image_info.hash, e);
+                Err(e)
+            }
+            Err(e) => {
+                warn!("更新图片访问信息失败: {} - {}", image_info.hash, e);
+                Ok(())
+            }
+        }
+    }
+
+    /// 删除图片文件，返回被删除的图片信息
+    pub async fn delete_image(
         pool: &DatabasePool,
         identifier: &str,
-    ) -> Result<Vec<u8>, AppError> {
+    ) -> Result<ImageInfo, AppError> {
         // 获取图片信息
         let image_info = Self::get_image_info(pool, identifier)
             .await?
             .ok_or(AppError::FileNotFound)?;
 
-        // 更新访问信息
+        // 从数据库删除记录
         let connection = pool.get_connection();
         let image_repo = ImageRepository::new(connection);
-        let _ = image_repo.update_access(identifier).await;
+        image_repo.delete_by_hash(&image_info.hash).await?;
 
-        // 构建文件路径
-        let stored_name = image_info.stored_name();
-        let relative_path = format!(
-            "{}/{}/{}",
-            &image_info.hash[0..2],
-            &image_info.hash[2..4],
-            stored_name
-        );
-        let file_path = get_upload_dir().join(&relative_path);
+        // 删除文件
+        let file_path = Self::get_image_path(&image_info);
+
+        tokio::fs::remove_file(&file_path).await?;
 
-        let data = tokio::fs::read(&file_path).await?;
-        Ok(data)
+        Ok(image_info)
     }
 
-    /// 删除图片文件
-    pub async fn delete_image(pool: &DatabasePool, identifier: &str) -> Result<(), AppError> {
-        // 获取图片信息
-        let image_info = Self::get_image_info(pool, identifier)
-            .await?
-            .ok_or(AppError::FileNotFound)?;
+    /// 检查图片当前是否可访问
+    pub fn ensure_available(image_info: &ImageInfo) -> Result<(), AppError> {
+        if image_info.is_expired() {
+            return Err(AppError::Gone("图片已过期".to_string()));
+        }
 
-        // 从数据库删除记录
+        if image_info.is_view_limit_reached() {
+            return Err(AppError::Gone("图片已达到最大查看次数".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// 清理已过期或已达到查看上限的图片，返回清理的图片数量
+    pub async fn purge_expired_images(pool: &DatabasePool) -> Result<u64, AppError> {
         let connection = pool.get_connection();
-        let image_repo = ImageRepository::new(connection);
-        image_repo.delete_by_hash(identifier).await?;
+        let image_repo = ImageRepository::new(connection.clone());
+        let cache_service = CacheService::new(connection)?;
 
-        // 删除文件
-        let stored_name = image_info.stored_name();
+        let mut purged_count = 0;
+
+        loop {
+            let expired_images = image_repo
+                .find_purgeable(
+                    chrono::Duration::seconds(VIEW_LIMIT_PURGE_GRACE_SECONDS),
+                    EXPIRED_PURGE_BATCH_SIZE,
+                )
+                .await?;
+            if expired_images.is_empty() {
+                break;
+            }
+
+            let batch_len = expired_images.len() as u64;
+
+            for image_info in expired_images {
+                // 先删除数据库记录，避免文件删除失败导致反复处理
+                image_repo.delete_by_hash(&image_info.hash).await?;
+
+                let file_path = Self::get_image_path(&image_info);
+                if let Err(e) = tokio::fs::remove_file(&file_path).await {
+                    warn!("删除失效图片文件失败: {} - {}", file_path.display(), e);
+                }
+
+                match cache_service
+                    .remove_by_original_hash(&image_info.hash)
+                    .await
+                {
+                    Ok(count) if count > 0 => {
+                        info!("删除失效图片{}的{}个相关缓存", image_info.hash, count);
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("删除失效图片缓存失败: {} - {}", image_info.hash, e),
+                }
+
+                purged_count += 1;
+            }
+
+            if batch_len < EXPIRED_PURGE_BATCH_SIZE {
+                break;
+            }
+        }
+
+        Ok(purged_count)
+    }
+
+    /// 确保图片有占位图哈希，缺少时读取原图计算并保存
+    pub async fn ensure_placeholders(
+        pool: &DatabasePool,
+        image_info: &mut ImageInfo,
+    ) -> Result<(), AppError> {
+        if image_info.blurhash.is_some() && image_info.thumbhash.is_some() {
+            return Ok(());
+        }
+
+        let data = Self::read_image_file_untracked(image_info).await?;
+        let thumbnail = StaticImageTransform::load_thumbnail(
+            &data,
+            &image_info.mime_type,
+            MAX_HASH_INPUT_SIZE,
+        )?;
+        let features = ImageFeatures {
+            placeholders: Some(PlaceholderService::compute(&thumbnail)?),
+            colors: None,
+        };
+
+        let image_repo = ImageRepository::new(pool.get_connection());
+        Self::store_features(&image_repo, image_info, features).await?;
+        Ok(())
+    }
+
+    /// 为缺少占位图哈希或颜色信息的已有图片补算，返回补算成功的图片数量
+    pub async fn backfill_features(pool: &DatabasePool) -> Result<u64, AppError> {
+        let config = AppConfig::get();
+        let placeholders = config.placeholder.enabled && config.placeholder.backfill_on_startup;
+        let colors = config.colors.enabled && config.colors.backfill_on_startup;
+        if !placeholders && !colors {
+            return Ok(0);
+        }
+
+        let image_repo = ImageRepository::new(pool.get_connection());
+
+        let mut filled_count = 0;
+        // 按hash顺序推进游标，无法解码的图片不会被反复处理
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let images = image_repo
+                .find_missing_features(
+                    cursor.as_deref(),
+                    placeholders,
+                    colors,
+                    FEATURE_BACKFILL_BATCH_SIZE,
+                )
+                .await?;
+            let Some(last) = images.last() else {
+                break;
+            };
+            cursor = Some(last.hash.clone());
+            let batch_len = images.len() as u64;
+
+            for mut image_info in images {
+                let data = match Self::read_image_file_untracked(&image_info).await {
+                    Ok(data) => data,
+                    Err(e) => {
+                        warn!(
+                            "读取图片文件失败，跳过特征补算: {} - {}",
+                            image_info.hash, e
+                        );
+                        continue;
+                    }
+                };
+
+                let missing = Self::missing_features(&image_info, true);
+                let features =
+                    Self::compute_features(&data, &image_info.mime_type, &image_info.hash, missing);
+                if Self::store_features(&image_repo, &mut image_info, features).await? {
+                    filled_count += 1;
+                }
+            }
+
+            if batch_len < FEATURE_BACKFILL_BATCH_SIZE {
+                break;
+            }
+        }
+
+        Ok(filled_count)
+    }
+
+    /// 读取图片文件内容（不计入访问次数），用于水印等内部引用
+    pub async fn read_image_file_untracked(image_info: &ImageInfo) -> Result<Vec<u8>, AppError> {
+        let file_path = Self::get_image_path(image_info);
+        if !file_path.exists() {
+            return Err(AppError::FileNotFound);
+        }
+        Ok(tokio::fs::read(file_path).await?)
+    }
+
+    /// 获取图片文件的完整路径
+    fn get_image_path(image_info: &ImageInfo) -> std::path::PathBuf {
         let relative_path = format!(
             "{}/{}/{}",
             &image_info.hash[0..2],
             &image_info.hash[2..4],
-            stored_name
+            image_info.stored_name()
         );
-        let file_path = get_upload_dir().join(&relative_path);
-
-        tokio::fs::remove_file(&file_path).await?;
-
-        Ok(())
+        get_upload_dir().join(relative_path)
     }
 
     /// 查询图片列表
@@ -161,6 +708,10 @@ impl ImageService {
         pool: &DatabasePool,
         query: &ImageQuery,
     ) -> Result<(Vec<ImageInfo>, u64), AppError> {
+        query
+            .color_filter()
+            .map_err(|e| AppError::BadRequest(format!("无效的颜色参数: {}", e)))?;
+
         let connection = pool.get_connection();
         let image_repo = ImageRepository::new(connection);
         let page_result = image_repo.find_by_query(query).await?;
@@ -1,64 +1,139 @@
+use image::{DynamicImage, ImageFormat, RgbaImage};
 use tracing::info;
 
-use super::{image_format_utils::ImageFormatUtils, static_image_transform::StaticImageTransform};
-use crate::models::ImageTransformParams;
+use super::{
+    animated_image_transform::{AnimatedFrame, AnimatedImageTransform},
+    color_profile::ColorProfile,
+    image_format_utils::ImageFormatUtils,
+    static_image_transform::StaticImageTransform,
+    transform_pipeline::{PipelineState, TransformPipeline},
+};
+use crate::config::AppConfig;
+use crate::models::{ClientHints, FrameSelection, IccMode, ImageTransformParams, AUTO_FORMAT};
+use crate::services::PresetService;
 use crate::utils::AppError;
 
 /// 图片转换服务 - 支持所有image库编解码器
 pub struct ImageTransformService;
 
+/// 图片转换结果
+pub struct TransformOutput {
+    /// 编码后的数据
+    pub data: Vec<u8>,
+    /// 输出的MIME类型
+    pub mime_type: String,
+    /// 按目标体积搜索得到的编码质量
+    pub quality: Option<u8>,
+}
+
 impl ImageTransformService {
     /// 高级图片转换 - 使用专用编码器和优化参数
+    ///
+    /// watermark_image 为图片水印的源数据（参数包含图片水印时由调用方预先读取）
     pub async fn transform_image(
         image_data: &[u8],
         original_mime: &str,
         params: &ImageTransformParams,
-    ) -> Result<(Vec<u8>, String), AppError> {
+        watermark_image: Option<&[u8]>,
+    ) -> Result<TransformOutput, AppError> {
         // 如果不需要转换，直接返回原始数据
         if !params.needs_transform() {
-            return Ok((image_data.to_vec(), original_mime.to_string()));
+            return Ok(TransformOutput {
+                data: image_data.to_vec(),
+                mime_type: original_mime.to_string(),
+                quality: None,
+            });
         }
 
         info!("开始高级图片转换: {:?}", params);
 
+        // 蒙版和留白会产生透明区域，未指定格式且原图格式不支持透明时改为输出PNG
+        let alpha_params;
+        let params = if params.format.is_none()
+            && params.produces_transparency()
+            && !params.no_alpha
+            && ImageFormatUtils::format_requires_no_alpha(
+                &ImageFormatUtils::determine_target_format(original_mime, &None)?,
+            ) {
+            alpha_params = ImageTransformParams {
+                format: Some("png".to_string()),
+                ..params.clone()
+            };
+            &alpha_params
+        } else {
+            params
+        };
+
         // 检测是否为动图格式（需要实际解析数据）
         let is_animated_format = ImageFormatUtils::is_animated_format(original_mime, image_data);
 
-        // 如果是动图格式且用户没有要求格式转换，直接返回原图
-        if is_animated_format && params.format.is_none() {
-            info!(
-                "检测到多帧动图且无格式转换要求，直接返回原图: {}",
-                original_mime
-            );
-            return Ok((image_data.to_vec(), original_mime.to_string()));
-        }
+        // 动图输出为GIF或WebP时逐帧转换，保留动画（支持GIF与WebP互转）
+        if is_animated_format && params.frame.is_none() {
+            let target_format =
+                ImageFormatUtils::determine_target_format(original_mime, &params.format)?;
+            if ImageFormatUtils::supports_animation(&target_format) {
+                return Self::transform_animated(
+                    image_data,
+                    original_mime,
+                    target_format,
+                    params,
+                    watermark_image,
+                );
+            }
 
-        // 如果是动图但用户明确要求转换格式，提取第一帧进行静图转换
-        if is_animated_format && params.format.is_some() {
             info!(
-                "检测到多帧动图但用户要求格式转换，将提取第一帧: {} -> {:?}",
+                "检测到多帧动图但目标格式不支持动画，将提取第一帧: {} -> {:?}",
                 original_mime, params.format
             );
         }
 
-        // 加载静图（如果是动图但要求转换，提取第一帧）
-        let mut img = if original_mime == "image/gif" {
-            // GIF格式使用专门的第一帧提取函数，无论单帧还是多帧
-            info!("使用GIF第一帧提取器");
-            StaticImageTransform::load_gif_first_frame(image_data)?
-        } else if original_mime == "image/webp" && is_animated_format {
-            // 多帧WebP要求转换时，也提取第一帧（暂时使用通用加载器）
-            info!("多帧WebP转换，使用通用加载器提取第一帧");
-            StaticImageTransform::load_image_with_color_info(image_data)?
-        } else {
-            // 其他格式使用通用加载器
-            StaticImageTransform::load_image_with_color_info(image_data)?
+        // 静图默认将内嵌ICC配置文件的色彩转换为sRGB，keepicc时原样嵌入输出
+        let icc_mode = params.icc_mode.unwrap_or_default();
+        let icc_profile = match icc_mode {
+            IccMode::Strip => None,
+            IccMode::Srgb | IccMode::Keep => ColorProfile::read(image_data),
         };
 
-        // 调整尺寸（使用高质量重采样）
-        if params.width.is_some() || params.height.is_some() {
-            img = StaticImageTransform::resize_image_hq(img, params.width, params.height)?;
-        }
+        let mut img = match params.frame {
+            // 帧序列图：各帧分别转换后拼接
+            Some(FrameSelection::Sprite { count, columns }) => {
+                let animation = AnimatedImageTransform::decode(image_data, original_mime)?;
+                let frames = AnimatedImageTransform::sample_frames(animation, count);
+                let tiles = Self::transform_frames(frames, params, watermark_image)?;
+                AnimatedImageTransform::compose_sprite(tiles, columns)?
+            }
+            selection => {
+                // 加载静图（动图按帧选择参数取帧，未指定时提取第一帧）
+                let img = if let Some(selection) = selection {
+                    let animation = AnimatedImageTransform::decode(image_data, original_mime)?;
+                    DynamicImage::ImageRgba8(AnimatedImageTransform::select_frame(
+                        animation, selection,
+                    )?)
+                } else if original_mime == "image/gif" {
+                    // GIF格式使用专门的第一帧提取函数，无论单帧还是多帧
+                    info!("使用GIF第一帧提取器");
+                    StaticImageTransform::load_gif_first_frame(image_data)?
+                } else if original_mime == "image/webp" && is_animated_format {
+                    // 多帧WebP要求转换时，也提取第一帧（暂时使用通用加载器）
+                    info!("多帧WebP转换，使用通用加载器提取第一帧");
+                    StaticImageTransform::load_image_with_color_info(image_data)?
+                } else {
+                    // 其他格式使用通用加载器
+                    StaticImageTransform::load_image_with_color_info(image_data)?
+                };
+                let img = match (icc_mode, &icc_profile) {
+                    (IccMode::Srgb, Some(icc)) => ColorProfile::convert_to_srgb(img, icc),
+                    _ => img,
+                };
+
+                TransformPipeline::execute(
+                    img,
+                    &params.operations(),
+                    watermark_image,
+                    &mut PipelineState::default(),
+                )?
+            }
+        };
 
         // 确定目标格式
         let target_format =
@@ -80,10 +155,28 @@ impl ImageTransformService {
             params.format.is_some(),
         )?;
 
-        // 使用专用编码器编码静图
-        let encoded_data =
-            StaticImageTransform::encode_with_specialized_encoder(img, target_format, params)
-                .await?;
+        // 使用专用编码器编码静图，指定体积上限时搜索编码质量
+        // 保留ICC配置文件时从目标体积中预留嵌入所需空间
+        let embedded_icc = match icc_mode {
+            IccMode::Keep => icc_profile.as_deref(),
+            IccMode::Srgb | IccMode::Strip => None,
+        };
+        let (encoded_data, quality) = if let Some(max_bytes) = params.max_bytes {
+            let max_bytes =
+                max_bytes.saturating_sub(embedded_icc.map_or(0, ColorProfile::embedded_size));
+            let (data, quality) =
+                StaticImageTransform::encode_within_size(img, target_format, params, max_bytes)
+                    .await?;
+            (data, Some(quality))
+        } else {
+            let data =
+                StaticImageTransform::encode_with_specialized_encoder(&img, target_format, params)?;
+            (data, None)
+        };
+        let encoded_data = match embedded_icc {
+            Some(icc) => ColorProfile::embed(encoded_data, target_format, icc)?,
+            None => encoded_data,
+        };
 
         info!(
             "高级图片转换完成: {} -> {}, 原始大小: {}字节, 转换后: {}字节",
@@ -93,7 +186,162 @@ impl ImageTransformService {
             encoded_data.len()
         );
 
-        Ok((encoded_data, target_mime))
+        Ok(TransformOutput {
+            data: encoded_data,
+            mime_type: target_mime,
+            quality,
+        })
+    }
+
+    /// 动图逐帧转换，保留帧延迟和循环次数
+    fn transform_animated(
+        image_data: &[u8],
+        original_mime: &str,
+        target_format: ImageFormat,
+        params: &ImageTransformParams,
+        watermark_image: Option<&[u8]>,
+    ) -> Result<TransformOutput, AppError> {
+        ImageFormatUtils::validate_format_compatibility(
+            &target_format,
+            params,
+            params.format.is_some(),
+        )?;
+
+        if params.max_bytes.is_some() {
+            return Err(AppError::BadRequest(
+                "动图不支持目标体积参数，可使用frame参数提取单帧".to_string(),
+            ));
+        }
+
+        if params.icc_mode == Some(IccMode::Keep) {
+            return Err(AppError::BadRequest(
+                "动图不支持保留ICC配置文件，可使用frame参数提取单帧".to_string(),
+            ));
+        }
+
+        let mut animation = AnimatedImageTransform::decode(image_data, original_mime)?;
+        info!("动图逐帧转换: {}帧", animation.frames.len());
+
+        let (images, delays): (Vec<_>, Vec<_>) = std::mem::take(&mut animation.frames)
+            .into_iter()
+            .map(|frame| (frame.image, frame.delay_ms))
+            .unzip();
+
+        for (img, delay_ms) in Self::transform_frames(images, params, watermark_image)?
+            .into_iter()
+            .zip(delays)
+        {
+            let img = if params.no_alpha {
+                StaticImageTransform::remove_alpha_channel_advanced(img, &params.background_color)?
+            } else {
+                img
+            };
+            animation.frames.push(AnimatedFrame {
+                image: img.to_rgba8(),
+                delay_ms,
+            });
+        }
+
+        let encoded_data = AnimatedImageTransform::encode(&animation, target_format, params)?;
+        let target_mime = params
+            .target_mime_type()
+            .unwrap_or_else(|| original_mime.to_string());
+
+        info!(
+            "动图转换完成: {} -> {}, 原始大小: {}字节, 转换后: {}字节",
+            original_mime,
+            target_mime,
+            image_data.len(),
+            encoded_data.len()
+        );
+
+        Ok(TransformOutput {
+            data: encoded_data,
+            mime_type: target_mime,
+            quality: None,
+        })
+    }
+
+    /// 对一组帧执行相同的操作流水线
+    ///
+    /// 所有帧使用第一帧确定的裁剪焦点避免画面抖动；各帧尺寸相同，水印图层只渲染一次
+    fn transform_frames(
+        frames: Vec<RgbaImage>,
+        params: &ImageTransformParams,
+        watermark_image: Option<&[u8]>,
+    ) -> Result<Vec<DynamicImage>, AppError> {
+        let operations = params.operations();
+        let mut state = PipelineState::default();
+
+        frames
+            .into_iter()
+            .map(|image| {
+                TransformPipeline::execute(
+                    DynamicImage::ImageRgba8(image),
+                    &operations,
+                    watermark_image,
+                    &mut state,
+                )
+            })
+            .collect()
+    }
+
+    /// 解析URL中的转换参数：展开预设、按设备像素比换算宽高并校验
+    ///
+    /// 不需要转换时返回None
+    pub fn resolve_params(
+        params_str: &str,
+        hints: &ClientHints,
+    ) -> Result<Option<ImageTransformParams>, AppError> {
+        let config = AppConfig::get();
+        let params_str = PresetService::expand(params_str)?;
+
+        info!("解析转换参数: {}", params_str);
+
+        let mut params = ImageTransformParams::parse(&params_str)
+            .map_err(|e| AppError::BadRequest(format!("转换参数解析失败: {}", e)))?;
+
+        // 换算结果参与缓存键
+        params.apply_device_pixel_ratio(hints, config.transform.max_dpr);
+
+        // 未指定格式时按配置默认协商格式
+        if config.transform.auto_format
+            && params.format.is_none()
+            && params.placeholder.is_none()
+            && params.needs_transform()
+        {
+            params.format = Some(AUTO_FORMAT.to_string());
+        }
+
+        if !params.needs_transform() {
+            return Ok(None);
+        }
+
+        Self::validate_params(&params)?;
+        Ok(Some(params))
+    }
+
+    /// 根据Accept请求头协商输出格式，写入参数并丢弃该格式不支持的编码参数
+    pub fn negotiate_format(
+        accept: &str,
+        original_mime: &str,
+        image_data: &[u8],
+        params: &mut ImageTransformParams,
+    ) -> Result<&'static str, AppError> {
+        let format = ImageFormatUtils::negotiate_format(accept, original_mime, image_data, params);
+        let target_format =
+            ImageFormatUtils::determine_target_format(original_mime, &Some(format.to_string()))?;
+        ImageFormatUtils::drop_unsupported_encoding_params(&target_format, params);
+        // 保留动画时逐帧编码，目标体积和保留ICC配置文件同样不适用
+        if params.frame.is_none() && ImageFormatUtils::is_animated_format(original_mime, image_data)
+        {
+            params.max_bytes = None;
+            if params.icc_mode == Some(IccMode::Keep) {
+                params.icc_mode = None;
+            }
+        }
+        params.format = Some(format.to_string());
+        Ok(format)
     }
 
     /// 验证转换参数
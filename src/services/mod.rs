@@ -1,9 +1,28 @@
+pub mod access_service;
+pub mod animated_image_transform;
 pub mod cache_service;
+pub mod color_profile;
+pub mod color_service;
+pub mod compat_url;
+pub mod decoration;
 pub mod image_format_utils;
 pub mod image_service;
 pub mod image_transform_service;
+pub mod placeholder_service;
+pub mod preset_service;
+pub mod smart_crop;
+pub mod srcset_service;
 pub mod static_image_transform;
+pub mod transform_pipeline;
+pub mod watermark_service;
 
+pub use access_service::{AccessCredentials, AccessService};
 pub use cache_service::CacheService;
+pub use color_service::ColorService;
+pub use compat_url::CompatUrl;
 pub use image_service::ImageService;
 pub use image_transform_service::ImageTransformService;
+pub use placeholder_service::PlaceholderService;
+pub use preset_service::PresetService;
+pub use srcset_service::SrcsetService;
+pub use watermark_service::WatermarkService;
@@ -0,0 +1,167 @@
+use base64::{engine::general_purpose, Engine as _};
+use image::{imageops::FilterType, DynamicImage, ImageFormat, RgbaImage};
+use std::io::Cursor;
+
+use crate::config::AppConfig;
+use crate::models::{ImageInfo, ImageTransformParams, PlaceholderKind};
+use crate::utils::AppError;
+
+/// 计算哈希的输入图片最大边长（ThumbHash要求不超过100像素）
+pub const MAX_HASH_INPUT_SIZE: u32 = 100;
+
+/// 未指定尺寸时BlurHash解码的长边像素数
+const DEFAULT_BLURHASH_SIZE: u32 = 32;
+
+/// SVG占位图的模糊半径，掩盖放大后的像素块
+const SVG_BLUR_STD_DEVIATION: f32 = 1.0;
+
+/// 图片的占位图哈希
+pub struct PlaceholderHashes {
+    pub blurhash: String,
+    pub thumbhash: String,
+}
+
+/// 占位图服务
+pub struct PlaceholderService;
+
+impl PlaceholderService {
+    /// 启动时校验占位图配置
+    pub fn validate_config(config: &AppConfig) -> Result<(), AppError> {
+        let placeholder = &config.placeholder;
+        if !(1..=9).contains(&placeholder.blurhash_components_x)
+            || !(1..=9).contains(&placeholder.blurhash_components_y)
+        {
+            return Err(AppError::Internal(
+                "BlurHash分量数必须在1-9之间".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 由缩略图计算BlurHash和ThumbHash
+    pub fn compute(rgba: &RgbaImage) -> Result<PlaceholderHashes, AppError> {
+        let (width, height) = rgba.dimensions();
+        if width > MAX_HASH_INPUT_SIZE || height > MAX_HASH_INPUT_SIZE {
+            return Err(AppError::Internal(format!(
+                "计算占位图哈希的图片不能超过{}像素",
+                MAX_HASH_INPUT_SIZE
+            )));
+        }
+
+        let config = &AppConfig::get().placeholder;
+        let blurhash = blurhash::encode(
+            config.blurhash_components_x,
+            config.blurhash_components_y,
+            width,
+            height,
+            rgba.as_raw(),
+        )
+        .map_err(|e| AppError::Internal(format!("BlurHash计算失败: {}", e)))?;
+        let thumbhash =
+            thumbhash::rgba_to_thumb_hash(width as usize, height as usize, rgba.as_raw());
+
+        Ok(PlaceholderHashes {
+            blurhash,
+            thumbhash: general_purpose::STANDARD.encode(thumbhash),
+        })
+    }
+
+    /// 将存储的哈希解码为占位图，返回数据和MIME类型
+    ///
+    /// 未指定宽高时ThumbHash使用其自带的尺寸，BlurHash长边为32像素；
+    /// BlurHash不含宽高比信息，按ThumbHash记录的近似宽高比计算
+    pub fn render(
+        image_info: &ImageInfo,
+        params: &ImageTransformParams,
+    ) -> Result<(Vec<u8>, &'static str), AppError> {
+        let thumbhash = image_info
+            .thumbhash
+            .as_deref()
+            .map(|hash| general_purpose::STANDARD.decode(hash))
+            .transpose()
+            .map_err(|_| AppError::Internal("ThumbHash数据损坏".to_string()))?;
+        let decoded_thumbhash = thumbhash
+            .as_deref()
+            .map(thumbhash::thumb_hash_to_rgba)
+            .transpose()
+            .map_err(|_| AppError::Internal("ThumbHash数据损坏".to_string()))?;
+
+        let img = match params.placeholder {
+            Some(PlaceholderKind::ThumbHash) => {
+                let (width, height, rgba) = decoded_thumbhash
+                    .ok_or_else(|| AppError::BadRequest("该图片没有ThumbHash".to_string()))?;
+                let img = RgbaImage::from_raw(width as u32, height as u32, rgba)
+                    .ok_or_else(|| AppError::Internal("ThumbHash数据损坏".to_string()))?;
+                let (target_width, target_height) = Self::target_size(
+                    width as f32 / height as f32,
+                    params,
+                    width.max(height) as u32,
+                );
+                if (target_width, target_height) == img.dimensions() {
+                    img
+                } else {
+                    image::imageops::resize(&img, target_width, target_height, FilterType::Triangle)
+                }
+            }
+            Some(PlaceholderKind::BlurHash) => {
+                let hash = image_info
+                    .blurhash
+                    .as_deref()
+                    .ok_or_else(|| AppError::BadRequest("该图片没有BlurHash".to_string()))?;
+                let aspect_ratio = decoded_thumbhash
+                    .map(|(width, height, _)| width as f32 / height as f32)
+                    .unwrap_or(1.0);
+                let (width, height) =
+                    Self::target_size(aspect_ratio, params, DEFAULT_BLURHASH_SIZE);
+                let rgba = blurhash::decode(hash, width, height, 1.0)
+                    .map_err(|e| AppError::Internal(format!("BlurHash解码失败: {}", e)))?;
+                RgbaImage::from_raw(width, height, rgba)
+                    .ok_or_else(|| AppError::Internal("BlurHash解码失败".to_string()))?
+            }
+            None => return Err(AppError::BadRequest("未指定占位图类型".to_string())),
+        };
+
+        let (width, height) = img.dimensions();
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(img)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| AppError::Internal(format!("占位图编码失败: {}", e)))?;
+
+        if !params.svg {
+            return Ok((png, "image/png"));
+        }
+
+        let svg = format!(
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" preserveAspectRatio="none">"#,
+                r#"<filter id="b" color-interpolation-filters="sRGB"><feGaussianBlur stdDeviation="{blur}"/></filter>"#,
+                r#"<image width="{w}" height="{h}" preserveAspectRatio="none" filter="url(#b)" href="data:image/png;base64,{data}"/>"#,
+                "</svg>"
+            ),
+            w = width,
+            h = height,
+            blur = SVG_BLUR_STD_DEVIATION,
+            data = general_purpose::STANDARD.encode(&png)
+        );
+        Ok((svg.into_bytes(), "image/svg+xml"))
+    }
+
+    /// 按宽高比计算占位图尺寸，只指定一边时另一边按比例计算
+    fn target_size(
+        aspect_ratio: f32,
+        params: &ImageTransformParams,
+        default_size: u32,
+    ) -> (u32, u32) {
+        let from_width = |width: u32| ((width as f32 / aspect_ratio).round() as u32).max(1);
+        let from_height = |height: u32| ((height as f32 * aspect_ratio).round() as u32).max(1);
+
+        match (params.width, params.height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, from_width(width)),
+            (None, Some(height)) => (from_height(height), height),
+            (None, None) if aspect_ratio >= 1.0 => (default_size, from_width(default_size)),
+            (None, None) => (from_height(default_size), default_size),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::config::{AppConfig, PresetsConfig};
+use crate::models::{ImageTransformParams, STAGE_SEPARATOR};
+use crate::services::image_format_utils::ImageFormatUtils;
+use crate::utils::AppError;
+
+/// 转换预设服务
+pub struct PresetService;
+
+impl PresetService {
+    /// 启动时校验预设配置，避免请求时才发现预设参数无效
+    pub fn validate_config(config: &AppConfig) -> Result<(), AppError> {
+        for (name, params) in &config.presets.definitions {
+            if name.is_empty() || name.contains(['_', '@', STAGE_SEPARATOR]) {
+                return Err(AppError::Internal(format!("预设名称无效: {}", name)));
+            }
+            // 预设展开先于参数解析，与语法参数同名的预设会改变已有URL的含义
+            if ImageTransformParams::is_token(name) {
+                return Err(AppError::Internal(format!(
+                    "预设名称与转换参数冲突: {}",
+                    name
+                )));
+            }
+
+            let parsed = ImageTransformParams::parse(params)
+                .map_err(|e| AppError::Internal(format!("预设{}配置错误: {}", name, e)))?;
+            ImageFormatUtils::validate_params(&parsed)
+                .map_err(|e| AppError::Internal(format!("预设{}配置错误: {}", name, e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// 展开参数字符串中的预设名称
+    ///
+    /// 预设在所在位置展开，其后的参数覆盖预设中的同类参数（如 thumb_q60）；
+    /// 多阶段参数按 ~ 分隔后逐段展开；严格模式下只允许单独使用一个预设
+    pub fn expand(params_str: &str) -> Result<String, AppError> {
+        Self::expand_with(&AppConfig::get().presets, params_str)
+    }
+
+    /// 按给定的预设配置展开转换参数
+    fn expand_with(presets: &PresetsConfig, params_str: &str) -> Result<String, AppError> {
+        if presets.strict && !params_str.is_empty() {
+            return presets.definitions.get(params_str).cloned().ok_or_else(|| {
+                AppError::BadRequest(format!("仅允许使用预设转换参数: {}", params_str))
+            });
+        }
+
+        Ok(params_str
+            .split(STAGE_SEPARATOR)
+            .map(|stage| {
+                stage
+                    .split('_')
+                    .map(|param| presets.definitions.get(param).map_or(param, String::as_str))
+                    .collect::<Vec<_>>()
+                    .join("_")
+            })
+            .collect::<Vec<_>>()
+            .join(&STAGE_SEPARATOR.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with(name: &str, params: &str) -> AppConfig {
+        let mut config = AppConfig::default();
+        config
+            .presets
+            .definitions
+            .insert(name.to_string(), params.to_string());
+        config
+    }
+
+    #[test]
+    fn preset_names_must_not_shadow_tokens() {
+        for name in [
+            "png",
+            "gray",
+            "auto",
+            "w100",
+            "wmilogo",
+            "blur4",
+            "circle",
+            "thumbhash",
+        ] {
+            assert!(
+                PresetService::validate_config(&config_with(name, "w200")).is_err(),
+                "{}",
+                name
+            );
+        }
+        for name in ["thumb", "hero", "avatar-small"] {
+            assert!(
+                PresetService::validate_config(&config_with(name, "w200")).is_ok(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn preset_names_must_not_contain_separators() {
+        for name in ["", "a_b", "a@b", "a~b"] {
+            assert!(PresetService::validate_config(&config_with(name, "w200")).is_err());
+        }
+    }
+
+    #[test]
+    fn expand_replaces_presets_in_every_stage() {
+        let config = config_with("thumb", "w200_h200_cover");
+        let expand = |params| PresetService::expand_with(&config.presets, params).unwrap();
+
+        assert_eq!(expand("thumb"), "w200_h200_cover");
+        assert_eq!(expand("thumb_webp"), "w200_h200_cover_webp");
+        assert_eq!(expand("gray~thumb"), "gray~w200_h200_cover");
+        assert_eq!(expand("w100_q80"), "w100_q80");
+        assert_eq!(expand(""), "");
+    }
+
+    #[test]
+    fn strict_presets_only_allow_whole_names() {
+        let mut config = config_with("thumb", "w200_h200_cover");
+        config.presets.strict = true;
+
+        assert_eq!(
+            PresetService::expand_with(&config.presets, "thumb").unwrap(),
+            "w200_h200_cover"
+        );
+        assert_eq!(PresetService::expand_with(&config.presets, "").unwrap(), "");
+        for params in ["w100", "thumb_webp", "thumb~gray"] {
+            assert!(PresetService::expand_with(&config.presets, params).is_err());
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use tracing::info;
+
+use crate::models::Gravity;
+
+/// 分析用缩略图的最大边长
+const ANALYSIS_MAX_SIDE: u32 = 256;
+
+/// 每个方向上候选窗口的最大数量
+const MAX_CANDIDATES_PER_AXIS: u32 = 24;
+
+/// 亮度直方图分箱数（用于计算信息熵）
+const ENTROPY_BINS: usize = 32;
+
+/// 边缘能量权重
+const EDGE_WEIGHT: f64 = 1.0;
+
+/// 饱和度权重
+const SATURATION_WEIGHT: f64 = 0.6;
+
+/// 信息熵权重
+const ENTROPY_WEIGHT: f64 = 0.8;
+
+/// 饱和度计入评分的亮度范围，过暗或过亮的像素饱和度不可靠
+const SATURATION_LUMA_RANGE: (f64, f64) = (0.1, 0.9);
+
+/// 内容感知的智能裁剪
+pub struct SmartCrop;
+
+/// 缩略图上的逐像素特征
+struct FeatureMap {
+    width: u32,
+    height: u32,
+    /// 亮度（0-255）
+    luma: Vec<u8>,
+    /// 边缘能量积分图
+    edge_integral: Vec<f64>,
+    /// 饱和度积分图
+    saturation_integral: Vec<f64>,
+}
+
+impl SmartCrop {
+    /// 为裁剪到目标宽高选择焦点
+    ///
+    /// 在缩小的副本上按边缘能量、饱和度和信息熵为候选窗口打分，
+    /// 返回最佳窗口中心对应的焦点
+    pub fn find_focal_point(img: &DynamicImage, target_width: u32, target_height: u32) -> Gravity {
+        let (width, height) = img.dimensions();
+        if width == 0 || height == 0 || target_width == 0 || target_height == 0 {
+            return Gravity::Center;
+        }
+
+        // 与裁剪阶段一致：窗口与目标比例相同且尽可能大
+        let scale = (target_width as f64 / width as f64).max(target_height as f64 / height as f64);
+        let crop_width = (target_width as f64 / scale).min(width as f64);
+        let crop_height = (target_height as f64 / scale).min(height as f64);
+
+        // 比例一致时无需裁剪
+        if width as f64 - crop_width < 1.0 && height as f64 - crop_height < 1.0 {
+            return Gravity::Center;
+        }
+
+        let thumbnail = if width.max(height) > ANALYSIS_MAX_SIDE {
+            img.resize(ANALYSIS_MAX_SIDE, ANALYSIS_MAX_SIDE, FilterType::Triangle)
+        } else {
+            img.clone()
+        };
+        let features = FeatureMap::build(&thumbnail);
+
+        let ratio = features.width as f64 / width as f64;
+        let window_width = ((crop_width * ratio).round() as u32).clamp(1, features.width);
+        let window_height = ((crop_height * ratio).round() as u32).clamp(1, features.height);
+
+        let edge_mean = features.mean(&features.edge_integral);
+        let saturation_mean = features.mean(&features.saturation_integral);
+
+        let mut best = (f64::MIN, 0, 0);
+        for y in Self::candidates(features.height - window_height) {
+            for x in Self::candidates(features.width - window_width) {
+                let area = (window_width * window_height) as f64;
+                let edge =
+                    features.window_sum(&features.edge_integral, x, y, window_width, window_height)
+                        / area;
+                let saturation = features.window_sum(
+                    &features.saturation_integral,
+                    x,
+                    y,
+                    window_width,
+                    window_height,
+                ) / area;
+                let entropy = features.window_entropy(x, y, window_width, window_height);
+
+                let score = EDGE_WEIGHT * edge / (edge_mean + f64::EPSILON)
+                    + SATURATION_WEIGHT * saturation / (saturation_mean + f64::EPSILON)
+                    + ENTROPY_WEIGHT * entropy / (ENTROPY_BINS as f64).log2();
+
+                if score > best.0 {
+                    best = (score, x, y);
+                }
+            }
+        }
+
+        let (_, x, y) = best;
+        let focal_x = (x as f32 + window_width as f32 / 2.0) / features.width as f32;
+        let focal_y = (y as f32 + window_height as f32 / 2.0) / features.height as f32;
+
+        info!(
+            "智能裁剪: 分析尺寸 {}x{}，窗口 {}x{}+{}+{}，焦点 ({:.3}, {:.3})",
+            features.width, features.height, window_width, window_height, x, y, focal_x, focal_y
+        );
+
+        Gravity::FocalPoint(focal_x.clamp(0.0, 1.0), focal_y.clamp(0.0, 1.0))
+    }
+
+    /// 生成某个方向上的候选偏移（包含两端）
+    fn candidates(free_space: u32) -> Vec<u32> {
+        if free_space == 0 {
+            return vec![0];
+        }
+
+        let steps = free_space.min(MAX_CANDIDATES_PER_AXIS);
+        (0..=steps)
+            .map(|i| (free_space as u64 * i as u64 / steps as u64) as u32)
+            .collect()
+    }
+}
+
+impl FeatureMap {
+    /// 计算亮度、边缘能量和饱和度
+    fn build(img: &DynamicImage) -> Self {
+        let rgb = img.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let pixel_count = (width * height) as usize;
+
+        let mut luma = Vec::with_capacity(pixel_count);
+        let mut saturation = Vec::with_capacity(pixel_count);
+        for pixel in rgb.pixels() {
+            let [r, g, b] = pixel.0;
+            let l = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+            luma.push(l.round() as u8);
+
+            let max = r.max(g).max(b) as f64;
+            let min = r.min(g).min(b) as f64;
+            let brightness = l / 255.0;
+            let s = if max > 0.0
+                && (SATURATION_LUMA_RANGE.0..=SATURATION_LUMA_RANGE.1).contains(&brightness)
+            {
+                (max - min) / max
+            } else {
+                0.0
+            };
+            saturation.push(s);
+        }
+
+        // 拉普拉斯算子的绝对值作为边缘能量
+        let mut edge = vec![0.0; pixel_count];
+        let at = |x: u32, y: u32| luma[(y * width + x) as usize] as f64;
+        for y in 1..height.saturating_sub(1) {
+            for x in 1..width.saturating_sub(1) {
+                let value =
+                    4.0 * at(x, y) - at(x - 1, y) - at(x + 1, y) - at(x, y - 1) - at(x, y + 1);
+                edge[(y * width + x) as usize] = value.abs();
+            }
+        }
+
+        Self {
+            width,
+            height,
+            edge_integral: Self::integral(&edge, width, height),
+            saturation_integral: Self::integral(&saturation, width, height),
+            luma,
+        }
+    }
+
+    /// 构建积分图（尺寸为 (width+1) x (height+1)）
+    fn integral(values: &[f64], width: u32, height: u32) -> Vec<f64> {
+        let stride = (width + 1) as usize;
+        let mut integral = vec![0.0; stride * (height + 1) as usize];
+        for y in 0..height as usize {
+            let mut row_sum = 0.0;
+            for x in 0..width as usize {
+                row_sum += values[y * width as usize + x];
+                integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row_sum;
+            }
+        }
+        integral
+    }
+
+    /// 窗口内特征值之和
+    fn window_sum(&self, integral: &[f64], x: u32, y: u32, w: u32, h: u32) -> f64 {
+        let stride = (self.width + 1) as usize;
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = ((x + w) as usize, (y + h) as usize);
+        integral[y1 * stride + x1] - integral[y0 * stride + x1] - integral[y1 * stride + x0]
+            + integral[y0 * stride + x0]
+    }
+
+    /// 整张图的特征均值
+    fn mean(&self, integral: &[f64]) -> f64 {
+        self.window_sum(integral, 0, 0, self.width, self.height)
+            / (self.width * self.height).max(1) as f64
+    }
+
+    /// 窗口内亮度直方图的信息熵（比特）
+    fn window_entropy(&self, x: u32, y: u32, w: u32, h: u32) -> f64 {
+        let mut histogram = [0u32; ENTROPY_BINS];
+        for row in y..y + h {
+            let start = (row * self.width + x) as usize;
+            for &value in &self.luma[start..start + w as usize] {
+                histogram[value as usize * ENTROPY_BINS / 256] += 1;
+            }
+        }
+
+        let total = (w * h) as f64;
+        histogram
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / total;
+                -p * p.log2()
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,317 @@
+use super::{image_format_utils::ImageFormatUtils, static_image_transform::StaticImageTransform};
+use crate::config::AppConfig;
+use crate::models::{
+    ClientHints, ImageInfo, ImageTransformParams, SrcsetManifest, SrcsetQuery, SrcsetSource,
+    SrcsetVariant,
+};
+use crate::services::ImageTransformService;
+use crate::utils::AppError;
+
+/// 单次请求最多生成的候选尺寸数
+const MAX_SRCSET_CANDIDATES: usize = 20;
+
+/// 未指定时使用的sizes属性
+const DEFAULT_SIZES: &str = "100vw";
+
+/// 响应式图片清单服务
+pub struct SrcsetService;
+
+impl SrcsetService {
+    /// 生成图片的响应式清单和<picture>代码
+    ///
+    /// 返回清单和所有变体解析后的转换参数（供预生成使用）
+    pub fn build_manifest(
+        image_info: &ImageInfo,
+        image_data: &[u8],
+        query: &SrcsetQuery,
+    ) -> Result<(SrcsetManifest, Vec<ImageTransformParams>), AppError> {
+        let (width, height) = StaticImageTransform::read_dimensions(image_data)?;
+        let candidates = Self::candidates(query)?;
+        let formats = Self::formats(query)?;
+
+        let identifier = image_info.short_id.as_deref().unwrap_or(&image_info.hash);
+        let base_url = query
+            .base_url
+            .as_deref()
+            .map(|url| url.trim_end_matches('/'))
+            .unwrap_or("");
+        let token_suffix = query
+            .token
+            .as_deref()
+            .map(|token| format!("?token={}", token))
+            .unwrap_or_default();
+
+        let mut resolved = Vec::new();
+        let mut build_source = |format: Option<&str>| -> Result<SrcsetSource, AppError> {
+            let mut variants: Vec<SrcsetVariant> = Vec::new();
+            // 同一组srcset只能有一种输出类型，<source type>才能准确描述所有候选
+            let mut source_mime_type: Option<Option<String>> = None;
+
+            for candidate in &candidates {
+                let params_str = match format {
+                    Some(format) => format!("{}_{}", candidate, format),
+                    None => candidate.clone(),
+                };
+                let params =
+                    ImageTransformService::resolve_params(&params_str, &ClientHints::default())?
+                        .unwrap_or_default();
+
+                let mime_type = if params.is_auto_format() {
+                    None
+                } else {
+                    Some(
+                        params
+                            .target_mime_type()
+                            .unwrap_or_else(|| image_info.mime_type.clone()),
+                    )
+                };
+                match source_mime_type {
+                    None => source_mime_type = Some(mime_type),
+                    Some(ref expected) if *expected != mime_type => {
+                        return Err(AppError::BadRequest(format!(
+                            "同一组srcset中各候选的输出格式不一致: {}",
+                            candidate
+                        )));
+                    }
+                    Some(_) => {}
+                }
+
+                let (variant_width, variant_height) = Self::output_size(width, height, &params);
+                // 不放大的模式下超出原图宽度的候选与原图尺寸相同，只保留一个
+                if variants.iter().any(|v| v.width == variant_width) {
+                    continue;
+                }
+
+                variants.push(SrcsetVariant {
+                    width: variant_width,
+                    height: variant_height,
+                    url: format!(
+                        "{}/images/{}@{}{}",
+                        base_url, identifier, params_str, token_suffix
+                    ),
+                    params: params_str,
+                });
+                resolved.push(params);
+            }
+
+            variants.sort_by_key(|variant| variant.width);
+            let srcset = variants
+                .iter()
+                .map(|variant| format!("{} {}w", variant.url, variant.width))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            Ok(SrcsetSource {
+                format: format.map(str::to_string),
+                mime_type: source_mime_type.flatten(),
+                srcset,
+                variants,
+            })
+        };
+
+        let sources = formats
+            .iter()
+            .map(|format| build_source(Some(format)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fallback = build_source(None)?;
+
+        let sizes = query
+            .sizes
+            .as_deref()
+            .map(str::trim)
+            .filter(|sizes| !sizes.is_empty())
+            .unwrap_or(DEFAULT_SIZES)
+            .to_string();
+        let html = Self::render_html(&sources, &fallback, &sizes);
+
+        let manifest = SrcsetManifest {
+            hash: image_info.hash.clone(),
+            width,
+            height,
+            sources,
+            fallback,
+            sizes,
+            html,
+            pregenerated: 0,
+        };
+
+        Ok((manifest, resolved))
+    }
+
+    /// 生成候选尺寸的参数字符串（不含格式）
+    fn candidates(query: &SrcsetQuery) -> Result<Vec<String>, AppError> {
+        let presets = &AppConfig::get().presets;
+
+        let candidates = if let Some(names) = non_empty(&query.presets) {
+            if non_empty(&query.widths).is_some() || non_empty(&query.preset).is_some() {
+                return Err(AppError::BadRequest(
+                    "presets参数不能与widths、preset参数同时使用".to_string(),
+                ));
+            }
+
+            names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(|name| {
+                    if presets.definitions.contains_key(name) {
+                        Ok(name.to_string())
+                    } else {
+                        Err(AppError::BadRequest(format!("预设不存在: {}", name)))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            if presets.strict {
+                return Err(AppError::BadRequest(
+                    "严格预设模式下只能通过presets参数生成srcset".to_string(),
+                ));
+            }
+
+            let widths = match non_empty(&query.widths) {
+                Some(widths) => widths
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|width| !width.is_empty())
+                    .map(|width| {
+                        width
+                            .parse::<u32>()
+                            .map_err(|_| AppError::BadRequest(format!("无效的宽度: {}", width)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => AppConfig::get().transform.srcset_widths.clone(),
+            };
+
+            // 基础预设同时指定宽高时，各候选按预设的宽高比计算高度
+            let base = non_empty(&query.preset);
+            let base_size = match base {
+                Some(name) => {
+                    let params = presets
+                        .definitions
+                        .get(name)
+                        .ok_or_else(|| AppError::BadRequest(format!("预设不存在: {}", name)))?;
+                    let params =
+                        ImageTransformParams::parse(params).map_err(AppError::BadRequest)?;
+                    params.width.zip(params.height)
+                }
+                None => None,
+            };
+
+            widths
+                .into_iter()
+                .map(|width| {
+                    let mut parts: Vec<String> = base.map(str::to_string).into_iter().collect();
+                    parts.push(format!("w{}", width));
+                    if let Some((base_width, base_height)) = base_size {
+                        let height = (width as u64 * base_height as u64 / base_width as u64).max(1);
+                        parts.push(format!("h{}", height));
+                    }
+                    parts.join("_")
+                })
+                .collect()
+        };
+
+        if candidates.is_empty() {
+            return Err(AppError::BadRequest("至少需要一个候选尺寸".to_string()));
+        }
+        if candidates.len() > MAX_SRCSET_CANDIDATES {
+            return Err(AppError::BadRequest(format!(
+                "候选尺寸不能超过{}个",
+                MAX_SRCSET_CANDIDATES
+            )));
+        }
+
+        Ok(candidates)
+    }
+
+    /// 解析输出格式列表
+    fn formats(query: &SrcsetQuery) -> Result<Vec<String>, AppError> {
+        let Some(formats) = non_empty(&query.formats) else {
+            return Ok(Vec::new());
+        };
+
+        if AppConfig::get().presets.strict {
+            return Err(AppError::BadRequest(
+                "严格预设模式下不能指定formats参数".to_string(),
+            ));
+        }
+
+        formats
+            .split(',')
+            .map(|format| format.trim().to_lowercase())
+            .filter(|format| !format.is_empty())
+            .map(|format| {
+                ImageFormatUtils::determine_target_format("", &Some(format.clone()))?;
+                Ok(format)
+            })
+            .collect()
+    }
+
+    /// 计算变体的输出尺寸：按执行顺序依次估算各操作的结果
+    fn output_size(width: u32, height: u32, params: &ImageTransformParams) -> (u32, u32) {
+        params
+            .operations()
+            .iter()
+            .fold((width, height), |size, operation| {
+                operation.output_size(size)
+            })
+    }
+
+    /// 生成<picture>代码，没有额外格式时只生成<img>
+    fn render_html(sources: &[SrcsetSource], fallback: &SrcsetSource, sizes: &str) -> String {
+        let sizes = escape_attribute(sizes);
+        let largest = fallback.variants.last();
+        let mut img = format!(
+            r#"<img src="{}" srcset="{}" sizes="{}""#,
+            largest
+                .map(|v| escape_attribute(&v.url))
+                .unwrap_or_default(),
+            escape_attribute(&fallback.srcset),
+            sizes
+        );
+        if let Some(variant) = largest {
+            img.push_str(&format!(
+                r#" width="{}" height="{}""#,
+                variant.width, variant.height
+            ));
+        }
+        img.push_str(r#" alt="" loading="lazy" decoding="async">"#);
+
+        if sources.is_empty() {
+            return img;
+        }
+
+        let mut html = String::from("<picture>\n");
+        for source in sources {
+            html.push_str("  <source");
+            if let Some(ref mime_type) = source.mime_type {
+                html.push_str(&format!(r#" type="{}""#, mime_type));
+            }
+            html.push_str(&format!(
+                r#" srcset="{}" sizes="{}">"#,
+                escape_attribute(&source.srcset),
+                sizes
+            ));
+            html.push('\n');
+        }
+        html.push_str(&format!("  {}\n</picture>", img));
+        html
+    }
+}
+
+/// 去除空白后为空的参数视为未指定
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// 转义HTML属性值
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
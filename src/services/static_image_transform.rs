@@ -1,27 +1,69 @@
+use color_quant::NeuQuant;
 use image::{
-    codecs::{
-        jpeg::JpegEncoder,
-        png::{CompressionType as PngCompression, FilterType as PngFilter, PngEncoder},
-    },
+    codecs::png::{CompressionType as PngCompression, FilterType as PngFilter, PngEncoder},
     imageops::FilterType,
-    DynamicImage, GenericImageView, ImageFormat,
+    metadata::Orientation,
+    DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader, RgbaImage,
 };
 use std::io::Cursor;
 use tracing::{error, info, warn};
 
-use crate::models::{BackgroundColor, ImageTransformParams};
+use crate::config::AppConfig;
+use crate::models::{
+    BackgroundColor, ChromaSubsampling, FitMode, Gravity, ImageFilters, ImageTransformParams,
+};
 use crate::utils::AppError;
 
+/// 目标体积搜索的默认最高质量
+const TARGET_SIZE_MAX_QUALITY: u8 = 90;
+/// 目标体积搜索的最低质量，低于此质量时改为缩小尺寸
+const TARGET_SIZE_MIN_QUALITY: u8 = 10;
+/// 目标体积搜索时每次缩小到原尺寸的百分比
+const TARGET_SIZE_DOWNSCALE_PERCENT: u32 = 75;
+/// 目标体积搜索最多缩小尺寸的次数
+const TARGET_SIZE_MAX_DOWNSCALES: u32 = 6;
+/// 目标体积搜索的最大编码次数（所有尺寸合计）
+const TARGET_SIZE_MAX_ATTEMPTS: u32 = 24;
+
+/// 调色板量化的采样因子（1-30，越小调色板越准确、越慢）
+const PALETTE_SAMPLE_FACTOR: i32 = 10;
+
 /// 静图转换服务
 pub struct StaticImageTransform;
 
 impl StaticImageTransform {
     /// 加载图片并获取颜色信息
+    ///
+    /// 启用 auto_orient 时按EXIF方向信息旋转/翻转
     pub fn load_image_with_color_info(data: &[u8]) -> Result<DynamicImage, AppError> {
-        let img = image::load_from_memory(data).map_err(|e| {
+        let load_error = |e: image::ImageError| {
             error!("图片加载失败: {}", e);
             AppError::InvalidFile
-        })?;
+        };
+
+        let mut decoder = ImageReader::new(Cursor::new(data))
+            .with_guessed_format()
+            .map_err(|e| {
+                error!("图片格式识别失败: {}", e);
+                AppError::InvalidFile
+            })?
+            .into_decoder()
+            .map_err(load_error)?;
+
+        let orientation = if AppConfig::get().transform.auto_orient {
+            decoder.orientation().unwrap_or_else(|e| {
+                warn!("读取EXIF方向信息失败，忽略: {}", e);
+                Orientation::NoTransforms
+            })
+        } else {
+            Orientation::NoTransforms
+        };
+
+        let mut img = DynamicImage::from_decoder(decoder).map_err(load_error)?;
+        if orientation != Orientation::NoTransforms {
+            info!("按EXIF方向信息调整图片: {:?}", orientation);
+            img.apply_orientation(orientation);
+        }
 
         let (width, height) = img.dimensions();
         let color_type = img.color();
@@ -33,6 +75,40 @@ impl StaticImageTransform {
         Ok(img)
     }
 
+    /// 只读取文件头获取图片尺寸（启用 auto_orient 时按EXIF方向摆正）
+    pub fn read_dimensions(data: &[u8]) -> Result<(u32, u32), AppError> {
+        let mut decoder = ImageReader::new(Cursor::new(data))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_decoder().ok())
+            .ok_or(AppError::InvalidFile)?;
+
+        let (width, height) = decoder.dimensions();
+        let orientation = if AppConfig::get().transform.auto_orient {
+            decoder.orientation().unwrap_or(Orientation::NoTransforms)
+        } else {
+            Orientation::NoTransforms
+        };
+
+        Ok(match orientation {
+            Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH => (height, width),
+            _ => (width, height),
+        })
+    }
+
+    /// 加载缩小到指定边长以内的RGBA缩略图（动图使用第一帧），用于提取占位图和颜色
+    pub fn load_thumbnail(data: &[u8], mime_type: &str, size: u32) -> Result<RgbaImage, AppError> {
+        let img = if mime_type == "image/gif" {
+            Self::load_gif_first_frame(data)?
+        } else {
+            Self::load_image_with_color_info(data)?
+        };
+        Ok(img.thumbnail(size, size).to_rgba8())
+    }
+
     /// 从GIF提取第一帧作为静态图片
     pub fn load_gif_first_frame(data: &[u8]) -> Result<DynamicImage, AppError> {
         let mut decoder = gif::DecodeOptions::new();
@@ -172,6 +248,272 @@ impl StaticImageTransform {
         Ok(resized)
     }
 
+    /// 旋转和翻转（先旋转后翻转）
+    ///
+    /// 90度倍数使用无损旋转，其他角度扩展画布并以透明填充空白区域
+    pub fn rotate_and_flip(
+        img: DynamicImage,
+        rotation: Option<f32>,
+        flip_horizontal: bool,
+        flip_vertical: bool,
+    ) -> DynamicImage {
+        let mut img = match rotation {
+            None => img,
+            Some(90.0) => img.rotate90(),
+            Some(180.0) => img.rotate180(),
+            Some(270.0) => img.rotate270(),
+            Some(angle) => Self::rotate_arbitrary(&img, angle),
+        };
+
+        if flip_horizontal {
+            img = img.fliph();
+        }
+        if flip_vertical {
+            img = img.flipv();
+        }
+
+        img
+    }
+
+    /// 任意角度顺时针旋转，画布扩展为能容纳旋转后图片的大小
+    fn rotate_arbitrary(img: &DynamicImage, angle: f32) -> DynamicImage {
+        let (width, height) = img.dimensions();
+        let radians = angle.to_radians();
+        let (sin, cos) = radians.sin_cos();
+
+        let new_width = (width as f32 * cos.abs() + height as f32 * sin.abs()).ceil() as u32;
+        let new_height = (width as f32 * sin.abs() + height as f32 * cos.abs()).ceil() as u32;
+
+        info!(
+            "任意角度旋转 {}°: {}x{} -> {}x{}",
+            angle, width, height, new_width, new_height
+        );
+
+        let source = img.to_rgba8();
+        let (src_cx, src_cy) = (width as f32 / 2.0, height as f32 / 2.0);
+        let (dst_cx, dst_cy) = (new_width as f32 / 2.0, new_height as f32 / 2.0);
+
+        let rotated = image::RgbaImage::from_fn(new_width, new_height, |x, y| {
+            // 目标像素中心逆向映射回原图坐标
+            let dx = x as f32 + 0.5 - dst_cx;
+            let dy = y as f32 + 0.5 - dst_cy;
+            let src_x = dx * cos + dy * sin + src_cx - 0.5;
+            let src_y = -dx * sin + dy * cos + src_cy - 0.5;
+
+            image::imageops::interpolate_bilinear(&source, src_x, src_y)
+                .unwrap_or(image::Rgba([0, 0, 0, 0]))
+        });
+
+        DynamicImage::ImageRgba8(rotated)
+    }
+
+    /// 按适配模式调整尺寸
+    ///
+    /// fill/contain/scale 输出精确尺寸，允许放大；fit 沿用等比缩放且不放大
+    pub fn resize_with_fit_mode(
+        img: DynamicImage,
+        width: Option<u32>,
+        height: Option<u32>,
+        fit_mode: FitMode,
+        gravity: Gravity,
+    ) -> Result<DynamicImage, AppError> {
+        let (Some(target_width), Some(target_height)) = (width, height) else {
+            return Self::resize_image_hq(img, width, height);
+        };
+
+        match fit_mode {
+            FitMode::Fit => Self::resize_image_hq(img, width, height),
+            FitMode::Fill => Ok(Self::resize_fill(img, target_width, target_height, gravity)),
+            FitMode::Contain => Ok(Self::resize_contain(
+                img,
+                target_width,
+                target_height,
+                gravity,
+            )),
+            FitMode::Scale => {
+                info!(
+                    "拉伸缩放: {}x{} -> {}x{}",
+                    img.width(),
+                    img.height(),
+                    target_width,
+                    target_height
+                );
+                Ok(img.resize_exact(target_width, target_height, FilterType::Lanczos3))
+            }
+        }
+    }
+
+    /// 等比缩放后按对齐方式裁剪为精确尺寸
+    fn resize_fill(
+        img: DynamicImage,
+        target_width: u32,
+        target_height: u32,
+        gravity: Gravity,
+    ) -> DynamicImage {
+        let (current_width, current_height) = img.dimensions();
+
+        // 先在原图上确定与目标比例一致的裁剪窗口，再缩放，避免缩放多余像素
+        let scale = (target_width as f64 / current_width as f64)
+            .max(target_height as f64 / current_height as f64);
+        let crop_width = ((target_width as f64 / scale).round() as u32).clamp(1, current_width);
+        let crop_height = ((target_height as f64 / scale).round() as u32).clamp(1, current_height);
+
+        let (anchor_x, anchor_y) = gravity.anchor();
+        let (x, y) = match gravity {
+            // 焦点：让裁剪窗口中心尽量落在焦点上
+            Gravity::FocalPoint(..) => (
+                Self::center_offset(anchor_x, current_width, crop_width),
+                Self::center_offset(anchor_y, current_height, crop_height),
+            ),
+            _ => (
+                Self::anchor_offset(anchor_x, current_width - crop_width),
+                Self::anchor_offset(anchor_y, current_height - crop_height),
+            ),
+        };
+
+        info!(
+            "裁剪缩放: {}x{} 裁剪 {}x{}+{}+{} -> {}x{}",
+            current_width,
+            current_height,
+            crop_width,
+            crop_height,
+            x,
+            y,
+            target_width,
+            target_height
+        );
+
+        img.crop_imm(x, y, crop_width, crop_height).resize_exact(
+            target_width,
+            target_height,
+            FilterType::Lanczos3,
+        )
+    }
+
+    /// 等比缩放到指定范围内，并用透明边填充为精确尺寸
+    fn resize_contain(
+        img: DynamicImage,
+        target_width: u32,
+        target_height: u32,
+        gravity: Gravity,
+    ) -> DynamicImage {
+        let (current_width, current_height) = img.dimensions();
+
+        let scale = (target_width as f64 / current_width as f64)
+            .min(target_height as f64 / current_height as f64);
+        let inner_width = ((current_width as f64 * scale).round() as u32).clamp(1, target_width);
+        let inner_height = ((current_height as f64 * scale).round() as u32).clamp(1, target_height);
+
+        let resized = img.resize_exact(inner_width, inner_height, FilterType::Lanczos3);
+
+        let (anchor_x, anchor_y) = gravity.anchor();
+        let x = Self::anchor_offset(anchor_x, target_width - inner_width);
+        let y = Self::anchor_offset(anchor_y, target_height - inner_height);
+
+        info!(
+            "留边缩放: {}x{} -> {}x{}，画布 {}x{}，偏移 +{}+{}",
+            current_width,
+            current_height,
+            inner_width,
+            inner_height,
+            target_width,
+            target_height,
+            x,
+            y
+        );
+
+        // 透明画布，输出格式不支持透明时由去透明步骤填充背景色
+        let mut canvas = image::RgbaImage::new(target_width, target_height);
+        image::imageops::overlay(&mut canvas, &resized.to_rgba8(), x as i64, y as i64);
+        DynamicImage::ImageRgba8(canvas)
+    }
+
+    /// 按相对位置计算偏移
+    fn anchor_offset(anchor: f32, free_space: u32) -> u32 {
+        (free_space as f32 * anchor).round() as u32
+    }
+
+    /// 让窗口中心对齐相对位置，并限制在图片范围内
+    fn center_offset(anchor: f32, total: u32, window: u32) -> u32 {
+        let center = total as f32 * anchor;
+        (center - window as f32 / 2.0)
+            .round()
+            .clamp(0.0, (total - window) as f32) as u32
+    }
+
+    /// 按固定顺序应用像素滤镜
+    pub fn apply_filters(mut img: DynamicImage, filters: &ImageFilters) -> DynamicImage {
+        info!("应用滤镜: {:?}", filters);
+
+        if let Some(brightness) = filters.brightness {
+            // 百分比映射到像素值偏移
+            img = img.brighten(brightness * 255 / 100);
+        }
+        if let Some(contrast) = filters.contrast {
+            img = img.adjust_contrast(contrast as f32);
+        }
+        if let Some(saturation) = filters.saturation {
+            img = Self::adjust_saturation(img, saturation);
+        }
+        if let Some(hue) = filters.hue_rotate {
+            img = img.huerotate(hue);
+        }
+        if filters.grayscale {
+            img = img.grayscale();
+        }
+        if filters.sepia {
+            img = Self::sepia(img);
+        }
+        if filters.invert {
+            img.invert();
+        }
+        if let Some(sigma) = filters.blur {
+            img = img.blur(sigma);
+        }
+        if let Some(sigma) = filters.sharpen {
+            img = img.unsharpen(sigma, 1);
+        }
+
+        img
+    }
+
+    /// 饱和度调整：在亮度灰阶与原色之间插值
+    fn adjust_saturation(img: DynamicImage, saturation: i32) -> DynamicImage {
+        let factor = 1.0 + saturation as f32 / 100.0;
+        let mut rgba = img.to_rgba8();
+
+        for pixel in rgba.pixels_mut() {
+            let [r, g, b, _] = pixel.0;
+            let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+            for channel in pixel.0.iter_mut().take(3) {
+                *channel = (luma + (*channel as f32 - luma) * factor)
+                    .round()
+                    .clamp(0.0, 255.0) as u8;
+            }
+        }
+
+        DynamicImage::ImageRgba8(rgba)
+    }
+
+    /// 复古色调
+    fn sepia(img: DynamicImage) -> DynamicImage {
+        let mut rgba = img.to_rgba8();
+
+        for pixel in rgba.pixels_mut() {
+            let [r, g, b, a] = pixel.0;
+            let (r, g, b) = (r as f32, g as f32, b as f32);
+            let tone = |rw: f32, gw: f32, bw: f32| (r * rw + g * gw + b * bw).min(255.0) as u8;
+            pixel.0 = [
+                tone(0.393, 0.769, 0.189),
+                tone(0.349, 0.686, 0.168),
+                tone(0.272, 0.534, 0.131),
+                a,
+            ];
+        }
+
+        DynamicImage::ImageRgba8(rgba)
+    }
+
     /// 高级透明通道移除
     pub fn remove_alpha_channel_advanced(
         img: DynamicImage,
@@ -223,8 +565,8 @@ impl StaticImageTransform {
     }
 
     /// 使用专用编码器进行高级编码
-    pub async fn encode_with_specialized_encoder(
-        img: DynamicImage,
+    pub fn encode_with_specialized_encoder(
+        img: &DynamicImage,
         format: ImageFormat,
         params: &ImageTransformParams,
     ) -> Result<Vec<u8>, AppError> {
@@ -233,18 +575,24 @@ impl StaticImageTransform {
 
         match format {
             ImageFormat::Jpeg => {
-                info!("使用JPEG专用编码器，质量: {}", quality);
-                let encoder = JpegEncoder::new_with_quality(&mut buffer, quality);
-                img.write_with_encoder(encoder).map_err(|e| {
-                    error!("JPEG编码失败: {}", e);
-                    AppError::Internal("JPEG编码失败".to_string())
-                })?;
+                let jpeg_data = Self::encode_jpeg(img, quality, params)?;
+                buffer.get_mut().extend_from_slice(&jpeg_data);
+            }
+
+            ImageFormat::Png if params.palette.is_some() => {
+                let colors = params.palette.unwrap_or(256);
+                info!(
+                    "使用PNG调色板量化，颜色数: {}，抖动: {}",
+                    colors, !params.no_dither
+                );
+                let png_data = Self::encode_png_palette(img, colors, !params.no_dither)?;
+                buffer.get_mut().extend_from_slice(&png_data);
             }
 
             ImageFormat::Png => {
                 info!("使用PNG专用编码器，智能压缩优化");
                 let compression = Self::map_quality_to_png_compression(quality);
-                let filter = Self::select_png_filter(&img);
+                let filter = Self::select_png_filter(img);
                 let encoder = PngEncoder::new_with_quality(&mut buffer, compression, filter);
                 img.write_with_encoder(encoder).map_err(|e| {
                     error!("PNG编码失败: {}", e);
@@ -269,8 +617,8 @@ impl StaticImageTransform {
                 };
 
                 // 根据质量选择有损或无损编码
-                let webp_data = if quality >= 95 {
-                    // 高质量使用无损编码
+                let webp_data = if params.lossless || quality >= 95 {
+                    // 显式要求或高质量时使用无损编码
                     info!("WebP使用无损编码");
                     webp::Encoder::new(&pixel_data, layout, width, height).encode_lossless()
                 } else {
@@ -283,12 +631,8 @@ impl StaticImageTransform {
             }
 
             ImageFormat::Avif => {
-                info!("使用AVIF原生编码器，质量: {}", quality);
-                // AVIF需要特殊处理，使用通用编码器
-                img.write_to(&mut buffer, format).map_err(|e| {
-                    error!("AVIF编码失败: {}", e);
-                    AppError::Internal("AVIF编码失败".to_string())
-                })?;
+                let avif_data = Self::encode_avif(img, params)?;
+                buffer.get_mut().extend_from_slice(&avif_data);
             }
 
             ImageFormat::Gif => {
@@ -317,6 +661,287 @@ impl StaticImageTransform {
         Ok(buffer.into_inner())
     }
 
+    /// 在体积上限内搜索最高的编码质量，返回编码结果和选定的质量
+    ///
+    /// 对质量进行二分搜索；最低质量仍超出上限时逐步缩小尺寸后重新搜索。
+    /// 搜索需要多次完整编码，在阻塞线程池中执行，避免占用异步运行时
+    pub async fn encode_within_size(
+        img: DynamicImage,
+        format: ImageFormat,
+        params: &ImageTransformParams,
+        max_bytes: u64,
+    ) -> Result<(Vec<u8>, u8), AppError> {
+        let params = params.clone();
+        tokio::task::spawn_blocking(move || {
+            Self::search_encode_quality(img, format, &params, max_bytes)
+        })
+        .await
+        .map_err(|e| AppError::Internal(format!("目标体积编码任务失败: {}", e)))?
+    }
+
+    /// 目标体积的质量搜索，编码次数达到上限时停止
+    fn search_encode_quality(
+        img: DynamicImage,
+        format: ImageFormat,
+        params: &ImageTransformParams,
+        max_bytes: u64,
+    ) -> Result<(Vec<u8>, u8), AppError> {
+        // WebP在95及以上使用无损编码，搜索范围限制在有损区间
+        let max_quality = match format {
+            ImageFormat::WebP => params.quality.unwrap_or(TARGET_SIZE_MAX_QUALITY).min(94),
+            _ => params.quality.unwrap_or(TARGET_SIZE_MAX_QUALITY),
+        };
+        let min_quality = TARGET_SIZE_MIN_QUALITY.min(max_quality);
+        let mut attempt_params = params.clone();
+        let mut img = img;
+        let mut attempts = 0;
+
+        for step in 0..=TARGET_SIZE_MAX_DOWNSCALES {
+            let (mut low, mut high) = (min_quality, max_quality);
+            let mut best = None;
+
+            while low <= high && attempts < TARGET_SIZE_MAX_ATTEMPTS {
+                attempts += 1;
+                let quality = low + (high - low) / 2;
+                attempt_params.quality = Some(quality);
+                let data = Self::encode_with_specialized_encoder(&img, format, &attempt_params)?;
+
+                if data.len() as u64 <= max_bytes {
+                    best = Some((data, quality));
+                    low = quality + 1;
+                } else if quality == min_quality {
+                    break;
+                } else {
+                    high = quality - 1;
+                }
+            }
+
+            if let Some((data, quality)) = best {
+                info!(
+                    "目标体积编码完成: 上限{}字节，质量{}，尺寸{}x{}，大小{}字节",
+                    max_bytes,
+                    quality,
+                    img.width(),
+                    img.height(),
+                    data.len()
+                );
+                return Ok((data, quality));
+            }
+
+            let (width, height) = img.dimensions();
+            let (new_width, new_height) = (
+                width * TARGET_SIZE_DOWNSCALE_PERCENT / 100,
+                height * TARGET_SIZE_DOWNSCALE_PERCENT / 100,
+            );
+            if step == TARGET_SIZE_MAX_DOWNSCALES
+                || attempts >= TARGET_SIZE_MAX_ATTEMPTS
+                || new_width == 0
+                || new_height == 0
+            {
+                break;
+            }
+
+            info!(
+                "最低质量仍超出体积上限，缩小尺寸: {}x{} -> {}x{}",
+                width, height, new_width, new_height
+            );
+            img = img.resize_exact(new_width, new_height, FilterType::Lanczos3);
+        }
+
+        Err(AppError::BadRequest(format!(
+            "无法将图片压缩到{}字节以内",
+            max_bytes
+        )))
+    }
+
+    /// 使用ravif编码AVIF，未指定的质量和速度使用配置默认值
+    fn encode_avif(img: &DynamicImage, params: &ImageTransformParams) -> Result<Vec<u8>, AppError> {
+        let config = &AppConfig::get().encoder;
+        let quality = params.quality.unwrap_or(config.avif_quality).clamp(1, 100);
+        let speed = params.speed.unwrap_or(config.avif_speed).clamp(1, 10);
+        let alpha_quality = config.avif_alpha_quality.unwrap_or(quality).clamp(1, 100);
+
+        info!(
+            "使用AVIF编码器，质量: {}，透明通道质量: {}，速度: {}",
+            quality, alpha_quality, speed
+        );
+
+        let encoder = ravif::Encoder::new()
+            .with_quality(quality as f32)
+            .with_alpha_quality(alpha_quality as f32)
+            .with_speed(speed);
+
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let encoded = if img.color().has_alpha() {
+            let pixels: Vec<ravif::RGBA8> = img
+                .to_rgba8()
+                .pixels()
+                .map(|p| ravif::RGBA8::new(p[0], p[1], p[2], p[3]))
+                .collect();
+            encoder.encode_rgba(ravif::Img::new(&pixels[..], width, height))
+        } else {
+            let pixels: Vec<ravif::RGB8> = img
+                .to_rgb8()
+                .pixels()
+                .map(|p| ravif::RGB8::new(p[0], p[1], p[2]))
+                .collect();
+            encoder.encode_rgb(ravif::Img::new(&pixels[..], width, height))
+        }
+        .map_err(|e| {
+            error!("AVIF编码失败: {}", e);
+            AppError::Internal("AVIF编码失败".to_string())
+        })?;
+
+        Ok(encoded.avif_file)
+    }
+
+    /// 使用jpeg-encoder编码JPEG，支持渐进式、色度抽样和霍夫曼表优化
+    fn encode_jpeg(
+        img: &DynamicImage,
+        quality: u8,
+        params: &ImageTransformParams,
+    ) -> Result<Vec<u8>, AppError> {
+        info!(
+            "使用JPEG专用编码器，质量: {}，渐进式: {}，色度抽样: {:?}，优化霍夫曼表: {}",
+            quality, params.progressive, params.chroma_subsampling, params.optimize_huffman
+        );
+
+        let (width, height) = img.dimensions();
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => {
+                return Err(AppError::BadRequest(
+                    "JPEG格式的宽高不能超过65535像素".to_string(),
+                ))
+            }
+        };
+
+        let mut buffer = Vec::new();
+        let mut encoder = jpeg_encoder::Encoder::new(&mut buffer, quality);
+        encoder.set_progressive(params.progressive);
+        encoder.set_optimized_huffman_tables(params.optimize_huffman);
+        if let Some(chroma) = params.chroma_subsampling {
+            encoder.set_sampling_factor(match chroma {
+                ChromaSubsampling::Yuv444 => jpeg_encoder::SamplingFactor::R_4_4_4,
+                ChromaSubsampling::Yuv422 => jpeg_encoder::SamplingFactor::R_4_2_2,
+                ChromaSubsampling::Yuv420 => jpeg_encoder::SamplingFactor::R_4_2_0,
+            });
+        }
+
+        // 灰度图直接编码为单通道JPEG
+        let result = match img {
+            DynamicImage::ImageLuma8(gray) => {
+                encoder.encode(gray.as_raw(), width, height, jpeg_encoder::ColorType::Luma)
+            }
+            _ => encoder.encode(
+                img.to_rgb8().as_raw(),
+                width,
+                height,
+                jpeg_encoder::ColorType::Rgb,
+            ),
+        };
+        result.map_err(|e| {
+            error!("JPEG编码失败: {}", e);
+            AppError::Internal("JPEG编码失败".to_string())
+        })?;
+
+        Ok(buffer)
+    }
+
+    /// 将图片量化为调色板并输出8位索引色PNG
+    fn encode_png_palette(
+        img: &DynamicImage,
+        colors: u16,
+        dither: bool,
+    ) -> Result<Vec<u8>, AppError> {
+        let rgba = img.to_rgba8();
+        let quantizer = NeuQuant::new(PALETTE_SAMPLE_FACTOR, colors as usize, rgba.as_raw());
+        let indices = Self::map_to_palette(&rgba, &quantizer, dither);
+
+        let color_map = quantizer.color_map_rgba();
+        let palette: Vec<u8> = color_map
+            .chunks_exact(4)
+            .flat_map(|color| [color[0], color[1], color[2]])
+            .collect();
+        let mut alpha: Vec<u8> = color_map.chunks_exact(4).map(|color| color[3]).collect();
+        // tRNS块可以省略末尾的不透明项
+        while alpha.last() == Some(&255) {
+            alpha.pop();
+        }
+
+        let mut buffer = Vec::new();
+        let mut encoder = png::Encoder::new(&mut buffer, rgba.width(), rgba.height());
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(palette);
+        if !alpha.is_empty() {
+            encoder.set_trns(alpha);
+        }
+        encoder.set_compression(png::Compression::High);
+        // 索引色数据使用滤波器通常会增大体积
+        encoder.set_filter(png::Filter::NoFilter);
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| {
+                writer.write_image_data(&indices)?;
+                writer.finish()
+            })
+            .map_err(|e| {
+                error!("PNG编码失败: {}", e);
+                AppError::Internal("PNG编码失败".to_string())
+            })?;
+
+        Ok(buffer)
+    }
+
+    /// 将像素映射到调色板索引，可选Floyd-Steinberg误差扩散抖动
+    fn map_to_palette(rgba: &RgbaImage, quantizer: &NeuQuant, dither: bool) -> Vec<u8> {
+        if !dither {
+            return rgba
+                .pixels()
+                .map(|pixel| quantizer.index_of(&pixel.0) as u8)
+                .collect();
+        }
+
+        let width = rgba.width() as usize;
+        // 当前行和下一行累积的误差，两侧各留一个元素避免越界判断
+        let mut current = vec![[0f32; 4]; width + 2];
+        let mut next = vec![[0f32; 4]; width + 2];
+        let mut indices = Vec::with_capacity(width * rgba.height() as usize);
+
+        for row in rgba.rows() {
+            for (x, pixel) in row.enumerate() {
+                let target: [u8; 4] = std::array::from_fn(|c| {
+                    (pixel[c] as f32 + current[x + 1][c])
+                        .round()
+                        .clamp(0.0, 255.0) as u8
+                });
+                let index = quantizer.index_of(&target);
+                indices.push(index as u8);
+
+                // 全透明像素的颜色不可见，不扩散误差
+                if pixel[3] == 0 {
+                    continue;
+                }
+
+                let chosen = quantizer.lookup(index).unwrap_or(target);
+                for c in 0..4 {
+                    let error = target[c] as f32 - chosen[c] as f32;
+                    current[x + 2][c] += error * 7.0 / 16.0;
+                    next[x][c] += error * 3.0 / 16.0;
+                    next[x + 1][c] += error * 5.0 / 16.0;
+                    next[x + 2][c] += error / 16.0;
+                }
+            }
+
+            std::mem::swap(&mut current, &mut next);
+            next.fill([0.0; 4]);
+        }
+
+        indices
+    }
+
     /// 智能PNG压缩级别映射
     fn map_quality_to_png_compression(quality: u8) -> PngCompression {
         match quality {
@@ -346,3 +971,107 @@ impl StaticImageTransform {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// 伪随机噪点图，难以压缩，便于构造体积上限
+    fn noise(width: u32, height: u32) -> DynamicImage {
+        let mut state = 0x2545_f491_u32;
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |_, _| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, _] = state.to_be_bytes();
+            Rgb([r, g, b])
+        }))
+    }
+
+    fn encode(img: &DynamicImage, format: ImageFormat, quality: u8) -> Vec<u8> {
+        let params = ImageTransformParams {
+            quality: Some(quality),
+            ..Default::default()
+        };
+        StaticImageTransform::encode_with_specialized_encoder(img, format, &params).unwrap()
+    }
+
+    #[tokio::test]
+    async fn target_size_picks_highest_fitting_quality() {
+        let img = noise(128, 128);
+        let max_bytes = encode(&img, ImageFormat::Jpeg, 50).len() as u64;
+
+        let (data, quality) = StaticImageTransform::encode_within_size(
+            img.clone(),
+            ImageFormat::Jpeg,
+            &ImageTransformParams::default(),
+            max_bytes,
+        )
+        .await
+        .unwrap();
+
+        assert!(data.len() as u64 <= max_bytes);
+        assert!((50..TARGET_SIZE_MAX_QUALITY).contains(&quality));
+        assert!(encode(&img, ImageFormat::Jpeg, quality + 1).len() as u64 > max_bytes);
+        let decoded = image::load_from_memory(&data).unwrap();
+        assert_eq!(decoded.dimensions(), (128, 128));
+    }
+
+    #[tokio::test]
+    async fn target_size_downscales_when_lowest_quality_is_too_large() {
+        let img = noise(128, 128);
+        let max_bytes = encode(&img, ImageFormat::Jpeg, TARGET_SIZE_MIN_QUALITY).len() as u64 / 2;
+
+        let (data, _) = StaticImageTransform::encode_within_size(
+            img,
+            ImageFormat::Jpeg,
+            &ImageTransformParams::default(),
+            max_bytes,
+        )
+        .await
+        .unwrap();
+
+        assert!(data.len() as u64 <= max_bytes);
+        let (width, height) = image::load_from_memory(&data).unwrap().dimensions();
+        assert!(width < 128 && height < 128);
+    }
+
+    #[tokio::test]
+    async fn target_size_fails_when_unreachable() {
+        let result = StaticImageTransform::encode_within_size(
+            noise(64, 64),
+            ImageFormat::Jpeg,
+            &ImageTransformParams::default(),
+            64,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn target_size_keeps_webp_lossy() {
+        let params = ImageTransformParams {
+            quality: Some(100),
+            ..Default::default()
+        };
+
+        let (_, quality) = StaticImageTransform::encode_within_size(
+            noise(32, 32),
+            ImageFormat::WebP,
+            &params,
+            u64::MAX,
+        )
+        .await
+        .unwrap();
+        assert_eq!(quality, 94);
+
+        let (_, quality) = StaticImageTransform::encode_within_size(
+            noise(32, 32),
+            ImageFormat::Jpeg,
+            &params,
+            u64::MAX,
+        )
+        .await
+        .unwrap();
+        assert_eq!(quality, 100);
+    }
+}
@@ -0,0 +1,137 @@
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use std::collections::{hash_map::Entry, HashMap};
+use tracing::info;
+
+use super::{
+    decoration::Decoration, smart_crop::SmartCrop, static_image_transform::StaticImageTransform,
+    watermark_service::WatermarkService,
+};
+use crate::models::{FitMode, Gravity, TransformOperation, MAX_DIMENSION};
+use crate::utils::AppError;
+
+/// 流水线在多帧之间共享的状态，按操作序号缓存
+///
+/// 第一帧确定的智能裁剪焦点和渲染的水印图层供后续帧复用，避免画面抖动和重复渲染
+#[derive(Default)]
+pub struct PipelineState {
+    gravities: HashMap<usize, Gravity>,
+    watermark_layers: HashMap<usize, Option<RgbaImage>>,
+}
+
+/// 转换流水线：按顺序对单帧执行操作列表
+pub struct TransformPipeline;
+
+impl TransformPipeline {
+    /// 依次执行操作，watermark_image 为图片水印的源数据
+    pub fn execute(
+        mut img: DynamicImage,
+        operations: &[TransformOperation],
+        watermark_image: Option<&[u8]>,
+        state: &mut PipelineState,
+    ) -> Result<DynamicImage, AppError> {
+        Self::check_output_size(img.dimensions(), operations)?;
+
+        for (index, operation) in operations.iter().enumerate() {
+            img = match operation {
+                TransformOperation::Orient {
+                    rotation,
+                    flip_horizontal,
+                    flip_vertical,
+                } => StaticImageTransform::rotate_and_flip(
+                    img,
+                    *rotation,
+                    *flip_horizontal,
+                    *flip_vertical,
+                ),
+                TransformOperation::Crop(region) => {
+                    let (width, height) = img.dimensions();
+                    if region.x >= width || region.y >= height {
+                        return Err(AppError::BadRequest("裁剪区域超出图片范围".to_string()));
+                    }
+                    // 超出右侧或底部的部分按图片边界截断
+                    let crop_width = region.width.min(width - region.x);
+                    let crop_height = region.height.min(height - region.y);
+                    info!(
+                        "裁剪区域: ({}, {}) {}x{}",
+                        region.x, region.y, crop_width, crop_height
+                    );
+                    img.crop_imm(region.x, region.y, crop_width, crop_height)
+                }
+                TransformOperation::Resize {
+                    width,
+                    height,
+                    fit_mode,
+                    gravity,
+                } => {
+                    if width.is_none() && height.is_none() {
+                        continue;
+                    }
+                    // 智能裁剪：根据图片内容确定裁剪焦点
+                    let gravity = *state.gravities.entry(index).or_insert_with(|| {
+                        match (fit_mode, gravity, width, height) {
+                            (FitMode::Fill, Gravity::Smart, Some(width), Some(height)) => {
+                                SmartCrop::find_focal_point(&img, *width, *height)
+                            }
+                            _ => *gravity,
+                        }
+                    });
+                    StaticImageTransform::resize_with_fit_mode(
+                        img, *width, *height, *fit_mode, gravity,
+                    )?
+                }
+                TransformOperation::Filters(filters) => {
+                    StaticImageTransform::apply_filters(img, filters)
+                }
+                TransformOperation::Watermark(watermark) => {
+                    let layer = match state.watermark_layers.entry(index) {
+                        Entry::Occupied(entry) => entry.into_mut(),
+                        Entry::Vacant(entry) => {
+                            let (width, height) = img.dimensions();
+                            entry.insert(WatermarkService::render_layer(
+                                width,
+                                height,
+                                watermark,
+                                watermark_image,
+                            )?)
+                        }
+                    };
+                    match layer {
+                        Some(layer) => WatermarkService::apply_layer(img, watermark, layer),
+                        None => img,
+                    }
+                }
+                TransformOperation::Decorate {
+                    mask,
+                    border,
+                    border_color,
+                    padding,
+                } => Decoration::apply(img, *mask, *border, *border_color, *padding),
+            };
+        }
+
+        Ok(img)
+    }
+
+    /// 执行前按操作顺序估算每一步的尺寸，避免多阶段留白、旋转等操作放大出超大画布
+    ///
+    /// 上限取最大边长与原图长边中的较大者，原图超过最大边长时仍可执行不放大的操作
+    fn check_output_size(
+        size: (u32, u32),
+        operations: &[TransformOperation],
+    ) -> Result<(), AppError> {
+        let limit = MAX_DIMENSION.max(size.0).max(size.1);
+        operations
+            .iter()
+            .try_fold(size, |size, operation| {
+                let (width, height) = operation.output_size(size);
+                if width > limit || height > limit {
+                    return Err(AppError::BadRequest(format!(
+                        "处理后的图片尺寸不能超过{}像素",
+                        limit
+                    )));
+                }
+                Ok((width, height))
+            })
+            .map(|_| ())
+    }
+}
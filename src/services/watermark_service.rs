@@ -0,0 +1,417 @@
+use std::sync::OnceLock;
+
+use ab_glyph::{point, Font, FontArc, PxScale, ScaleFont};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, Rgba, RgbaImage};
+use tracing::{info, warn};
+
+use super::{image_format_utils::ImageFormatUtils, static_image_transform::StaticImageTransform};
+use crate::config::AppConfig;
+use crate::database::DatabasePool;
+use crate::models::{
+    Gravity, ImageInfo, ImageTransformParams, Visibility, WatermarkParams, MAX_DIMENSION,
+};
+use crate::services::{AccessCredentials, ImageService};
+use crate::utils::AppError;
+
+/// 内置字体（DejaVu Sans，许可证见 assets/fonts/LICENSE-DejaVu.txt）
+const BUNDLED_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+
+/// 默认不透明度
+const DEFAULT_OPACITY: u8 = 80;
+
+/// 默认边距（像素）
+const DEFAULT_MARGIN: u32 = 16;
+
+/// 图片水印默认宽度占比
+const DEFAULT_SCALE: u8 = 20;
+
+/// 文字水印最小默认字号
+const MIN_DEFAULT_FONT_SIZE: u32 = 12;
+
+/// 文字水印最大默认字号（与wmfs参数上限一致）
+const MAX_DEFAULT_FONT_SIZE: u32 = 512;
+
+/// 阴影不透明度
+const SHADOW_ALPHA: f32 = 0.6;
+
+/// 文字水印字体
+static FONT: OnceLock<FontArc> = OnceLock::new();
+
+/// 水印服务
+pub struct WatermarkService;
+
+impl WatermarkService {
+    /// 启动时校验水印配置，避免强制水印因配置错误被静默跳过
+    pub fn validate_config(config: &AppConfig) -> Result<(), AppError> {
+        if !config.watermark.font_path.is_empty() {
+            Self::load_font_file(&config.watermark.font_path)?;
+        }
+
+        for (index, rule) in config.watermark.forced.iter().enumerate() {
+            Self::parse_forced_params(&rule.params).map_err(|e| {
+                AppError::Internal(format!("强制水印规则#{}配置错误: {}", index + 1, e))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// 解析强制水印规则中的参数
+    fn parse_forced_params(params: &str) -> Result<WatermarkParams, AppError> {
+        let watermark = ImageTransformParams::parse(params)
+            .map_err(AppError::BadRequest)?
+            .watermark
+            .ok_or_else(|| AppError::BadRequest(format!("未包含水印参数: {}", params)))?;
+        ImageFormatUtils::validate_watermark(&watermark)?;
+        Ok(watermark)
+    }
+
+    /// 获取请求命中的强制水印
+    pub fn forced_watermark(
+        path: &str,
+        credentials: &AccessCredentials,
+    ) -> Option<WatermarkParams> {
+        let rule = AppConfig::get().watermark.forced.iter().find(|rule| {
+            let unconditional = rule.path_prefixes.is_empty() && rule.api_keys.is_empty();
+            let path_matched = rule
+                .path_prefixes
+                .iter()
+                .any(|prefix| path.starts_with(prefix.as_str()));
+            let key_matched = credentials
+                .api_key()
+                .is_some_and(|key| rule.api_keys.iter().any(|k| k == key));
+            unconditional || path_matched || key_matched
+        })?;
+
+        // 配置已在启动时校验
+        Self::parse_forced_params(&rule.params).ok()
+    }
+
+    /// 解析图片水印引用的图片，并将标识符替换为图片哈希
+    ///
+    /// 别名和短ID可能被重新指向其他图片，替换后缓存键始终对应实际使用的水印图片。
+    /// 非强制水印只能引用公开或不公开列出、且不限查看次数的图片，
+    /// 避免通过水印参数泄露私有图片或绕过阅后即焚
+    pub async fn resolve_watermark_image(
+        pool: &DatabasePool,
+        watermark: &mut WatermarkParams,
+        trusted: bool,
+    ) -> Result<Option<ImageInfo>, AppError> {
+        let Some(ref identifier) = watermark.image else {
+            return Ok(None);
+        };
+
+        let not_found = || AppError::BadRequest(format!("水印图片不存在: {}", identifier));
+        let image_info = ImageService::get_image_info(pool, identifier)
+            .await?
+            .ok_or_else(not_found)?;
+
+        if !Self::can_reference(&image_info, trusted) {
+            return Err(not_found());
+        }
+
+        watermark.image = Some(image_info.hash.clone());
+        Ok(Some(image_info))
+    }
+
+    /// 检查图片能否作为水印引用
+    ///
+    /// 水印读取不计入查看次数，因此限次图片只能由强制水印引用
+    fn can_reference(image_info: &ImageInfo, trusted: bool) -> bool {
+        if image_info.is_expired() || image_info.is_view_limit_reached() {
+            return false;
+        }
+        trusted
+            || (image_info.visibility != Visibility::Private
+                && !image_info.is_password_protected()
+                && image_info.max_views.is_none())
+    }
+
+    /// 读取图片水印的源数据
+    pub async fn load_watermark_image(
+        watermark_image: Option<&ImageInfo>,
+    ) -> Result<Option<Vec<u8>>, AppError> {
+        match watermark_image {
+            Some(image_info) => Ok(Some(
+                ImageService::read_image_file_untracked(image_info).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// 为指定尺寸的目标图渲染水印图层（已应用不透明度）
+    ///
+    /// 动图各帧尺寸相同，渲染一次即可逐帧叠加
+    pub fn render_layer(
+        width: u32,
+        height: u32,
+        watermark: &WatermarkParams,
+        watermark_image: Option<&[u8]>,
+    ) -> Result<Option<RgbaImage>, AppError> {
+        let layer = if let Some(data) = watermark_image {
+            let source = StaticImageTransform::load_image_with_color_info(data)?;
+            let scale = watermark.scale.unwrap_or(DEFAULT_SCALE) as f64 / 100.0;
+            let target_width = ((width as f64 * scale).round() as u32).max(1);
+            let target_height = ((source.height() as f64 * target_width as f64
+                / source.width() as f64)
+                .round() as u32)
+                .max(1);
+            source
+                .resize_exact(target_width, target_height, FilterType::Lanczos3)
+                .to_rgba8()
+        } else if let Some(ref text) = watermark.text {
+            let font_size = watermark.font_size.unwrap_or_else(|| {
+                (height / 20).clamp(MIN_DEFAULT_FONT_SIZE, MAX_DEFAULT_FONT_SIZE)
+            });
+            let color = watermark.color.unwrap_or((255, 255, 255));
+            Self::render_text(text, font_size, color, watermark.shadow, (width, height))?
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self::apply_opacity(
+            layer,
+            watermark.opacity.unwrap_or(DEFAULT_OPACITY),
+        )))
+    }
+
+    /// 将渲染好的水印图层按位置叠加到图片上
+    pub fn apply_layer(
+        img: DynamicImage,
+        watermark: &WatermarkParams,
+        layer: &RgbaImage,
+    ) -> DynamicImage {
+        let (width, height) = img.dimensions();
+        let margin = watermark.margin.unwrap_or(DEFAULT_MARGIN);
+        let (anchor_x, anchor_y) = watermark.position.unwrap_or(Gravity::SouthEast).anchor();
+        let free_x = width.saturating_sub(layer.width() + margin * 2);
+        let free_y = height.saturating_sub(layer.height() + margin * 2);
+        let x = margin as i64 + (free_x as f32 * anchor_x).round() as i64;
+        let y = margin as i64 + (free_y as f32 * anchor_y).round() as i64;
+
+        info!(
+            "叠加水印: {}x{} 于 +{}+{}（目标 {}x{}）",
+            layer.width(),
+            layer.height(),
+            x,
+            y,
+            width,
+            height
+        );
+
+        let had_alpha = img.color().has_alpha();
+        let mut canvas = img.to_rgba8();
+        image::imageops::overlay(&mut canvas, layer, x, y);
+
+        // 原图无透明通道时保持RGB，避免输出体积增加
+        if had_alpha {
+            DynamicImage::ImageRgba8(canvas)
+        } else {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+        }
+    }
+
+    /// 按不透明度缩放透明通道
+    fn apply_opacity(mut layer: RgbaImage, opacity: u8) -> RgbaImage {
+        if opacity >= 100 {
+            return layer;
+        }
+        for pixel in layer.pixels_mut() {
+            pixel.0[3] = (pixel.0[3] as u32 * opacity as u32 / 100) as u8;
+        }
+        layer
+    }
+
+    /// 渲染单行文字为透明背景图层
+    ///
+    /// 图层不超过目标图片尺寸，超出部分的文字被截断
+    fn render_text(
+        text: &str,
+        font_size: u32,
+        color: (u8, u8, u8),
+        shadow: bool,
+        (max_width, max_height): (u32, u32),
+    ) -> Result<RgbaImage, AppError> {
+        let font = Self::font();
+        let scale = PxScale::from(font_size as f32);
+        let scaled = font.as_scaled(scale);
+
+        // 排版字形
+        let mut glyphs = Vec::new();
+        let mut caret = 0.0f32;
+        let mut previous = None;
+        for c in text.chars().filter(|c| !c.is_control()) {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id);
+            }
+            glyphs.push(id.with_scale_and_position(scale, point(caret, scaled.ascent())));
+            caret += scaled.h_advance(id);
+            previous = Some(id);
+        }
+
+        let shadow_offset = if shadow { (font_size / 16).max(1) } else { 0 };
+        let text_width = caret.ceil() as u32 + shadow_offset + 1;
+        let text_height = (scaled.ascent() - scaled.descent()).ceil() as u32 + shadow_offset + 1;
+        if text_width > MAX_DIMENSION || text_height > MAX_DIMENSION {
+            return Err(AppError::BadRequest(format!(
+                "文字水印尺寸超出限制: {}x{}，请减少文字或减小字号",
+                text_width, text_height
+            )));
+        }
+        let width = text_width.clamp(1, max_width.max(1));
+        let height = text_height.clamp(1, max_height.max(1));
+        let mut layer = RgbaImage::new(width, height);
+
+        let mut draw = |offset: u32, rgb: (u8, u8, u8), alpha: f32| {
+            for glyph in &glyphs {
+                let Some(outlined) = font.outline_glyph(glyph.clone()) else {
+                    continue;
+                };
+                let bounds = outlined.px_bounds();
+                if bounds.min.x as i64 >= width as i64 {
+                    continue;
+                }
+                outlined.draw(|gx, gy, coverage| {
+                    let x = bounds.min.x as i64 + gx as i64 + offset as i64;
+                    let y = bounds.min.y as i64 + gy as i64 + offset as i64;
+                    if x >= 0 && y >= 0 && (x as u32) < width && (y as u32) < height {
+                        Self::blend_pixel(
+                            layer.get_pixel_mut(x as u32, y as u32),
+                            rgb,
+                            coverage.clamp(0.0, 1.0) * alpha,
+                        );
+                    }
+                });
+            }
+        };
+
+        if shadow {
+            draw(shadow_offset, (0, 0, 0), SHADOW_ALPHA);
+        }
+        draw(0, color, 1.0);
+
+        Ok(layer)
+    }
+
+    /// 以"over"方式混合像素
+    fn blend_pixel(pixel: &mut Rgba<u8>, (r, g, b): (u8, u8, u8), alpha: f32) {
+        if alpha <= 0.0 {
+            return;
+        }
+
+        let [dr, dg, db, da] = pixel.0;
+        let dst_alpha = da as f32 / 255.0;
+        let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+        let mix = |src: u8, dst: u8| {
+            ((src as f32 * alpha + dst as f32 * dst_alpha * (1.0 - alpha)) / out_alpha).round()
+                as u8
+        };
+
+        pixel.0 = [
+            mix(r, dr),
+            mix(g, dg),
+            mix(b, db),
+            (out_alpha * 255.0).round() as u8,
+        ];
+    }
+
+    /// 获取文字水印字体（配置的字体加载失败时回退到内置字体）
+    fn font() -> &'static FontArc {
+        FONT.get_or_init(|| {
+            let font_path = &AppConfig::get().watermark.font_path;
+            if !font_path.is_empty() {
+                match Self::load_font_file(font_path) {
+                    Ok(font) => return font,
+                    Err(e) => warn!("加载水印字体失败，使用内置字体: {}", e),
+                }
+            }
+            FontArc::try_from_slice(BUNDLED_FONT).expect("内置字体无效")
+        })
+    }
+
+    /// 从文件加载字体
+    fn load_font_file(path: &str) -> Result<FontArc, AppError> {
+        let data = std::fs::read(path)
+            .map_err(|e| AppError::Internal(format!("读取字体文件失败 {}: {}", path, e)))?;
+        FontArc::try_from_vec(data)
+            .map_err(|e| AppError::Internal(format!("无效的字体文件 {}: {}", path, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn image(visibility: Visibility, max_views: Option<i64>, access_count: i64) -> ImageInfo {
+        ImageInfo {
+            hash: "a".repeat(64),
+            size: 0,
+            mime_type: "image/png".to_string(),
+            created_at: Utc::now(),
+            last_accessed: None,
+            extension: "png".to_string(),
+            access_count,
+            short_id: None,
+            alias: None,
+            expires_at: None,
+            max_views,
+            visibility,
+            password_hash: None,
+            blurhash: None,
+            thumbhash: None,
+            dominant_color: None,
+            palette: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn view_limited_images_cannot_be_referenced_by_request_watermarks() {
+        assert!(WatermarkService::can_reference(
+            &image(Visibility::Public, None, 0),
+            false
+        ));
+        assert!(WatermarkService::can_reference(
+            &image(Visibility::Unlisted, None, 0),
+            false
+        ));
+        assert!(!WatermarkService::can_reference(
+            &image(Visibility::Public, Some(5), 0),
+            false
+        ));
+        assert!(!WatermarkService::can_reference(
+            &image(Visibility::Private, None, 0),
+            false
+        ));
+    }
+
+    #[test]
+    fn forced_watermarks_cannot_reference_used_up_images() {
+        assert!(WatermarkService::can_reference(
+            &image(Visibility::Private, Some(5), 4),
+            true
+        ));
+        assert!(!WatermarkService::can_reference(
+            &image(Visibility::Public, Some(5), 5),
+            true
+        ));
+    }
+
+    #[test]
+    fn text_layer_is_limited_to_target_size() {
+        AppConfig::init_for_tests();
+        let layer =
+            WatermarkService::render_text("RIFS", 200, (255, 255, 255), true, (64, 32)).unwrap();
+        assert_eq!(layer.dimensions(), (64, 32));
+    }
+
+    #[test]
+    fn oversized_text_rejected_before_rendering() {
+        AppConfig::init_for_tests();
+        let text = "W".repeat(200);
+        let result =
+            WatermarkService::render_text(&text, 512, (255, 255, 255), false, (8192, 8192));
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+}
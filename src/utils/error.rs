@@ -26,6 +26,15 @@ pub enum AppError {
 
     #[error("请求格式错误: {0}")]
     BadRequest(String),
+
+    #[error("资源已失效: {0}")]
+    Gone(String),
+
+    #[error("未授权访问: {0}")]
+    Unauthorized(String),
+
+    #[error("请求过于频繁: {0}")]
+    TooManyRequests(String),
 }
 
 impl IntoResponse for AppError {
@@ -87,6 +96,30 @@ impl IntoResponse for AppError {
                     code: Some(400),
                 },
             ),
+            AppError::Gone(msg) => (
+                StatusCode::GONE,
+                ErrorResponse {
+                    success: false,
+                    message: msg,
+                    code: Some(410),
+                },
+            ),
+            AppError::Unauthorized(msg) => (
+                StatusCode::UNAUTHORIZED,
+                ErrorResponse {
+                    success: false,
+                    message: msg,
+                    code: Some(401),
+                },
+            ),
+            AppError::TooManyRequests(msg) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorResponse {
+                    success: false,
+                    message: msg,
+                    code: Some(429),
+                },
+            ),
         };
 
         (status, Json(error_response)).into_response()